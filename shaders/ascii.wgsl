@@ -0,0 +1,110 @@
+@group(0) @binding(0) var inputTexture: texture_2d<f32>;
+@group(0) @binding(2) var outputTexture: texture_storage_2d<rgba16float, write>;
+@group(0) @binding(3) var<uniform> params: Parameters;
+// two words per cell, row by row: the glyph, then the cell's average color packed as rgba8
+@group(0) @binding(5) var<storage, read_write> cells: array<u32>;
+// the ramp's glyph_count glyphs then the edge glyphs | / - \, a cell each side by side, see GlyphAtlas
+@group(1) @binding(2) var glyphTexture: texture_2d<f32>;
+
+// the share of a cell's pixels that have to be edges running one way for it to take an edge glyph
+const EDGE_SHARE: f32 = 0.2;
+
+fn cell_size() -> vec2<u32> {
+    let width = clamp(params.glyph_cell, 4u, 64u);
+    return vec2<u32>(width, width * 2u);
+}
+
+fn luminance(color: vec3<f32>) -> f32 {
+    return dot(color, vec3<f32>(0.2126, 0.7152, 0.0722));
+}
+
+fn load_luminance(pixel: vec2<i32>, size: vec2<u32>) -> f32 {
+    let clamped = clamp(pixel, vec2<i32>(0), vec2<i32>(size) - 1);
+    return luminance(textureLoad(inputTexture, clamped, 0).rgb);
+}
+
+// the sobel kernels of the sobel edge detection effect, on luminance
+fn sobel(pixel: vec2<i32>, size: vec2<u32>) -> vec2<f32> {
+    let tl = load_luminance(pixel + vec2(-1, -1), size);
+    let t = load_luminance(pixel + vec2(0, -1), size);
+    let tr = load_luminance(pixel + vec2(1, -1), size);
+    let l = load_luminance(pixel + vec2(-1, 0), size);
+    let r = load_luminance(pixel + vec2(1, 0), size);
+    let bl = load_luminance(pixel + vec2(-1, 1), size);
+    let b = load_luminance(pixel + vec2(0, 1), size);
+    let br = load_luminance(pixel + vec2(1, 1), size);
+    return vec2<f32>(
+        (tr + 2.0 * r + br) - (tl + 2.0 * l + bl),
+        (bl + 2.0 * b + br) - (tl + 2.0 * t + tr),
+    );
+}
+
+// one invocation per cell: its average color and the glyph it is drawn with,
+// from the ramp by luminance or, where enough of it is edges running one way, an edge glyph
+@compute @workgroup_size(8, 8)
+fn cells_main(@builtin(global_invocation_id) id: vec3<u32>) {
+    let size = textureDimensions(inputTexture);
+    let cell = cell_size();
+    let grid = (size + cell - 1u) / cell;
+    let index = (id.y * grid.x + id.x) * 2u;
+    if (any(id.xy >= grid) || index + 1u >= arrayLength(&cells)) {
+        return;
+    }
+
+    let start = id.xy * cell;
+    let end = min(start + cell, size);
+    var color = vec3<f32>(0.0);
+    // edge pixels per direction, the edge runs across the gradient:
+    // a horizontal gradient is a vertical edge |, then / - and \ a quarter turn apart each
+    var directions = array<u32, 4>(0u, 0u, 0u, 0u);
+    for (var y = start.y; y < end.y; y++) {
+        for (var x = start.x; x < end.x; x++) {
+            color += textureLoad(inputTexture, vec2<u32>(x, y), 0).rgb;
+            if (params.glyph_edges != 0u) {
+                let gradient = sobel(vec2<i32>(vec2<u32>(x, y)), size);
+                if (length(gradient) > params.glyph_edge_threshold) {
+                    let angle = atan2(gradient.y, gradient.x);
+                    let bin = u32(round((angle + 3.14159265) / 0.78539816)) % 4u;
+                    directions[bin]++;
+                }
+            }
+        }
+    }
+    let count = (end.x - start.x) * (end.y - start.y);
+    color /= f32(count);
+
+    let glyph_count = max(params.glyph_count, 1u);
+    var glyph = min(u32(clamp(luminance(color), 0.0, 1.0) * f32(glyph_count)), glyph_count - 1u);
+    var most = 0u;
+    for (var bin = 0u; bin < 4u; bin++) {
+        if (directions[bin] > most && f32(directions[bin]) >= EDGE_SHARE * f32(count)) {
+            most = directions[bin];
+            glyph = glyph_count + bin;
+        }
+    }
+
+    cells[index] = glyph;
+    cells[index + 1u] = pack4x8unorm(vec4<f32>(clamp(color, vec3<f32>(0.0), vec3<f32>(1.0)), 1.0));
+}
+
+// every pixel draws its part of its cell's glyph, white or in the cell's color on black
+@compute @workgroup_size(8, 8)
+fn draw_main(@builtin(global_invocation_id) id: vec3<u32>) {
+    let size = textureDimensions(inputTexture);
+    if (id.x >= size.x || id.y >= size.y) {
+        return;
+    }
+    let cell = cell_size();
+    let grid = (size + cell - 1u) / cell;
+    let position = id.xy / cell;
+    let index = (position.y * grid.x + position.x) * 2u;
+    if (index + 1u >= arrayLength(&cells)) {
+        textureStore(outputTexture, id.xy, textureLoad(inputTexture, id.xy, 0));
+        return;
+    }
+
+    let within = id.xy % cell;
+    let coverage = textureLoad(glyphTexture, vec2<u32>(cells[index] * cell.x + within.x, within.y), 0).r;
+    let ink = select(vec3<f32>(1.0), unpack4x8unorm(cells[index + 1u]).rgb, params.glyph_color == 1u);
+    textureStore(outputTexture, id.xy, vec4<f32>(ink * coverage, 1.0));
+}
@@ -0,0 +1,43 @@
+@group(0) @binding(0) var inputTexture: texture_2d<f32>;
+@group(0) @binding(1) var<uniform> bilateral: BilateralParameters;
+
+// run once along x and once along y. the range weight is taken against the
+// center of each pass, which is the usual separable approximation of the full 2D filter
+struct BilateralParameters {
+    direction: vec2<i32>,
+    radius: i32,
+    sigma_space: f32,
+    sigma_range: f32,
+}
+
+// one triangle covering the whole target, fragments read texels by position
+@vertex
+fn vert_main(@builtin(vertex_index) index: u32) -> @builtin(position) vec4<f32> {
+    let uv = vec2<f32>(f32((index << 1u) & 2u), f32(index & 2u));
+    return vec4<f32>(uv.x * 2.0 - 1.0, 1.0 - uv.y * 2.0, 0.0, 1.0);
+}
+
+@fragment
+fn frag_main(@builtin(position) position: vec4<f32>) -> @location(0) vec4<f32> {
+    let size = vec2<i32>(textureDimensions(inputTexture));
+    let texelCoord = vec2<i32>(position.xy);
+    let center = textureLoad(inputTexture, texelCoord, 0);
+
+    let space = -0.5 / (bilateral.sigma_space * bilateral.sigma_space);
+    let range = -0.5 / (bilateral.sigma_range * bilateral.sigma_range);
+
+    var result = vec4<f32>(0.0);
+    var total = 0.0;
+
+    for (var i = -bilateral.radius; i <= bilateral.radius; i++) {
+        let coord = clamp(texelCoord + bilateral.direction * i, vec2<i32>(0), size - 1);
+        let value = textureLoad(inputTexture, coord, 0);
+        let difference = value.rgb - center.rgb;
+
+        let weight = exp(f32(i * i) * space + dot(difference, difference) * range);
+        result += value * weight;
+        total += weight;
+    }
+
+    return result / total;
+}
@@ -0,0 +1,42 @@
+@group(0) @binding(0) var inputTexture: texture_2d<f32>;
+@group(0) @binding(1) var sampler0: sampler;
+@group(0) @binding(2) var<uniform> blur: BlurParameters;
+
+// taps[0] is the center texel, every other tap is mirrored on both sides.
+// x holds the offset in texels along direction, y holds the weight. as many as MAX_TAPS in blur.rs
+struct BlurParameters {
+    direction: vec2<f32>,
+    tap_count: u32,
+    taps: array<vec4<f32>, 128>,
+}
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) texcoord: vec2<f32>,
+};
+
+// one triangle covering the whole target
+@vertex
+fn vert_main(@builtin(vertex_index) index: u32) -> VertexOutput {
+    let uv = vec2<f32>(f32((index << 1u) & 2u), f32(index & 2u));
+    var out: VertexOutput;
+    out.clip_position = vec4<f32>(uv.x * 2.0 - 1.0, 1.0 - uv.y * 2.0, 0.0, 1.0);
+    out.texcoord = uv;
+    return out;
+}
+
+@fragment
+fn frag_main(@location(0) texcoord: vec2<f32>) -> @location(0) vec4<f32> {
+    let texel = blur.direction / vec2<f32>(textureDimensions(inputTexture));
+
+    var result = textureSampleLevel(inputTexture, sampler0, texcoord, 0.0) * blur.taps[0].y;
+
+    // offsets fall between texels, so the linear sampler blends each pair in one fetch
+    for (var i = 1u; i < blur.tap_count; i++) {
+        let tap = blur.taps[i];
+        result += textureSampleLevel(inputTexture, sampler0, texcoord + texel * tap.x, 0.0) * tap.y;
+        result += textureSampleLevel(inputTexture, sampler0, texcoord - texel * tap.x, 0.0) * tap.y;
+    }
+
+    return result;
+}
@@ -0,0 +1,263 @@
+@group(0) @binding(0) var inputTexture: texture_2d<f32>;
+@group(0) @binding(2) var outputTexture: texture_storage_2d<rgba16float, write>;
+@group(0) @binding(3) var<uniform> params: Parameters;
+@group(0) @binding(4) var<uniform> dispatch: Dispatch;
+// two words per cell of every layer, layer after layer and row by row: the color of the cell's
+// stroke packed as rgba8 with alpha 0 where it has none, then its direction packed as snorm16
+@group(0) @binding(5) var<storage, read_write> strokes: array<u32>;
+@group(1) @binding(1) var assetSampler: sampler;
+// how much paint a stroke leaves, running left to right, see BrushTexture
+@group(1) @binding(3) var brushTexture: texture_2d<f32>;
+
+struct Dispatch {
+    pass_index: u32,
+    iteration: u32,
+    iterations: u32,
+    round: u32,
+}
+
+// mirror the limits in brush_strokes.rs
+const MAX_LAYERS: u32 = 4u;
+const MIN_STROKE_WIDTH: u32 = 4u;
+const MAX_STROKE_WIDTH: u32 = 24u;
+const MAX_STROKE_LENGTH: f32 = 6.0;
+
+// the angle of strokes where a cell has no gradients to follow
+const FLAT_ANGLE: f32 = 0.78539816;
+// how far a stroke may turn away from the gradients, in radians either way
+const ANGLE_JITTER: f32 = 0.15;
+
+fn layers() -> u32 {
+    return clamp(params.stroke_layers, 1u, MAX_LAYERS);
+}
+
+// the width of the strokes of a layer, mirrors layer_width in brush_strokes.rs
+fn layer_width(layer: u32) -> u32 {
+    return max(clamp(params.stroke_width, MIN_STROKE_WIDTH, MAX_STROKE_WIDTH) >> layer, MIN_STROKE_WIDTH);
+}
+
+fn origin() -> vec2<u32> {
+    return vec2<u32>(params.tile_x, params.tile_y);
+}
+
+// the first of a layer's cells over the tile, counted from the corner of the whole image
+fn first_cell(layer: u32) -> vec2<u32> {
+    return origin() / layer_width(layer);
+}
+
+// columns and rows of a layer's cells over the tile, mirrors grid in brush_strokes.rs
+fn grid(layer: u32) -> vec2<u32> {
+    let size = textureDimensions(inputTexture);
+    return (origin() + size - 1u) / layer_width(layer) - first_cell(layer) + 1u;
+}
+
+// where the strokes of a layer start in the buffer
+fn layer_offset(layer: u32) -> u32 {
+    var offset = 0u;
+    for (var l = 0u; l < layer; l++) {
+        let cells = grid(l);
+        offset += cells.x * cells.y * 2u;
+    }
+    return offset;
+}
+
+// the index of the stroke of a cell of the whole image, in the layer starting at offset.
+// arrayLength for cells outside the tile or the buffer
+fn stroke_index(layer: u32, offset: u32, cell: vec2<i32>) -> u32 {
+    let local = cell - vec2<i32>(first_cell(layer));
+    let cells = grid(layer);
+    if (any(local < vec2<i32>(0)) || any(local >= vec2<i32>(cells))) {
+        return arrayLength(&strokes);
+    }
+    let index = offset + (u32(local.y) * cells.x + u32(local.x)) * 2u;
+    return select(index, arrayLength(&strokes), index + 1u >= arrayLength(&strokes));
+}
+
+fn luminance(color: vec3<f32>) -> f32 {
+    return dot(color, vec3<f32>(0.2126, 0.7152, 0.0722));
+}
+
+fn load_luminance(pixel: vec2<i32>, size: vec2<u32>) -> f32 {
+    let clamped = clamp(pixel, vec2<i32>(0), vec2<i32>(size) - 1);
+    return luminance(textureLoad(inputTexture, clamped, 0).rgb);
+}
+
+// the sobel kernels of the edge direction effect, on luminance
+fn sobel(pixel: vec2<i32>, size: vec2<u32>) -> vec2<f32> {
+    let tl = load_luminance(pixel + vec2(-1, -1), size);
+    let t = load_luminance(pixel + vec2(0, -1), size);
+    let tr = load_luminance(pixel + vec2(1, -1), size);
+    let l = load_luminance(pixel + vec2(-1, 0), size);
+    let r = load_luminance(pixel + vec2(1, 0), size);
+    let bl = load_luminance(pixel + vec2(-1, 1), size);
+    let b = load_luminance(pixel + vec2(0, 1), size);
+    let br = load_luminance(pixel + vec2(1, 1), size);
+    return vec2<f32>(
+        (tr + 2.0 * r + br) - (tl + 2.0 * l + bl),
+        (bl + 2.0 * b + br) - (tl + 2.0 * t + tr),
+    );
+}
+
+// mark jarzynski and marc olano's pcg3d hash, as in fragment.wgsl
+fn pcg3d(input: vec3<u32>) -> vec3<u32> {
+    var v = input * 1664525u + 1013904223u;
+    v.x += v.y * v.z;
+    v.y += v.z * v.x;
+    v.z += v.x * v.y;
+    v ^= v >> vec3<u32>(16u);
+    v.x += v.y * v.z;
+    v.y += v.z * v.x;
+    v.z += v.x * v.y;
+    return v;
+}
+
+// three values in [0, 1) the stroke of a cell always draws, a stream each
+fn random_stroke(cell: vec2<u32>, layer: u32, stream: u32) -> vec3<f32> {
+    let v = pcg3d(vec3<u32>(cell, layer * 2u + stream));
+    return vec3<f32>(v >> vec3<u32>(8u)) / 16777216.0;
+}
+
+struct Stroke {
+    center: vec2<f32>,
+    half_length: f32,
+    // the strokes of a layer over a pixel are laid in order of depth, the deepest on top
+    depth: f32,
+}
+
+// where the stroke of a cell lies, somewhere around the middle of the cell
+fn stroke_shape(cell: vec2<u32>, layer: u32) -> Stroke {
+    let width = f32(layer_width(layer));
+    let r = random_stroke(cell, layer, 0u);
+    let length = width * clamp(params.stroke_length, 1.0, MAX_STROKE_LENGTH) * (0.8 + 0.4 * random_stroke(cell, layer, 1u).x);
+    return Stroke((vec2<f32>(cell) + 0.25 + 0.5 * r.xy) * width, max(length, width) * 0.5, r.z);
+}
+
+// how much paint a stroke leaves at offset from its center, direction along its length
+fn stroke_coverage(offset: vec2<f32>, direction: vec2<f32>, half_length: f32, half_width: f32) -> f32 {
+    let along = dot(offset, direction);
+    let across = dot(offset, vec2<f32>(-direction.y, direction.x));
+    if (params.stroke_texture != 0u) {
+        let uv = vec2<f32>(along / half_length, across / half_width) * 0.5 + 0.5;
+        if (any(uv < vec2<f32>(0.0)) || any(uv > vec2<f32>(1.0))) {
+            return 0.0;
+        }
+        return textureSampleLevel(brushTexture, assetSampler, uv, 0.0).r;
+    }
+    // round ends and a pixel wide edge
+    let distance = length(vec2<f32>(max(abs(along) - half_length + half_width, 0.0), across)) - half_width;
+    return clamp(0.5 - distance, 0.0, 1.0);
+}
+
+// one invocation per cell of the layer of this dispatch: the average color of the cell,
+// and its gradients' dominant direction from their structure tensor, both over the cell
+// and half a cell around it. cells of the first layer always take a stroke, cells of the
+// finer layers only where their colors spread more than stroke_detail
+@compute @workgroup_size(8, 8)
+fn strokes_main(@builtin(global_invocation_id) id: vec3<u32>) {
+    let layer = dispatch.iteration;
+    let cells = grid(layer);
+    let index = layer_offset(layer) + (id.y * cells.x + id.x) * 2u;
+    if (any(id.xy >= cells) || index + 1u >= arrayLength(&strokes)) {
+        return;
+    }
+
+    let size = textureDimensions(inputTexture);
+    let width = i32(layer_width(layer));
+    let cell = first_cell(layer) + id.xy;
+    let corner = vec2<i32>(cell) * width - vec2<i32>(origin());
+    let start = max(corner - width / 2, vec2<i32>(0));
+    let end = min(corner + width + width / 2, vec2<i32>(size));
+
+    var color = vec3<f32>(0.0);
+    var squares = vec3<f32>(0.0);
+    // xx, xy and yy
+    var tensor = vec3<f32>(0.0);
+    for (var y = start.y; y < end.y; y++) {
+        for (var x = start.x; x < end.x; x++) {
+            let c = textureLoad(inputTexture, vec2<i32>(x, y), 0).rgb;
+            color += c;
+            squares += c * c;
+            let gradient = sobel(vec2<i32>(x, y), size);
+            tensor += vec3<f32>(gradient.x * gradient.x, gradient.x * gradient.y, gradient.y * gradient.y);
+        }
+    }
+    let count = f32((end.x - start.x) * (end.y - start.y));
+    color /= count;
+    let spread = sqrt(max(dot(squares / count - color * color, vec3<f32>(1.0 / 3.0)), 0.0));
+
+    // strokes run across the gradients, along the edges
+    var angle = FLAT_ANGLE;
+    if (tensor.x + tensor.z > 1e-4 * count) {
+        angle = 0.5 * atan2(2.0 * tensor.y, tensor.x - tensor.z) + 1.57079633;
+    }
+    angle += (random_stroke(cell, layer, 1u).y * 2.0 - 1.0) * ANGLE_JITTER;
+
+    let placed = layer == 0u || spread >= params.stroke_detail;
+    strokes[index] = pack4x8unorm(vec4<f32>(clamp(color, vec3<f32>(0.0), vec3<f32>(1.0)), select(0.0, 1.0, placed)));
+    strokes[index + 1u] = pack2x16snorm(vec2<f32>(cos(angle), sin(angle)));
+}
+
+// every pixel starts out in the color of its cell of the first layer, then every layer
+// lays the two deepest of its strokes over the pixel on top of it, the deeper one last
+@compute @workgroup_size(8, 8)
+fn draw_main(@builtin(global_invocation_id) id: vec3<u32>) {
+    let size = textureDimensions(inputTexture);
+    if (any(id.xy >= size)) {
+        return;
+    }
+    let pixel = origin() + id.xy;
+    let position = vec2<f32>(pixel) + 0.5;
+
+    let under = stroke_index(0u, 0u, vec2<i32>(pixel / layer_width(0u)));
+    if (under >= arrayLength(&strokes)) {
+        textureStore(outputTexture, id.xy, textureLoad(inputTexture, id.xy, 0));
+        return;
+    }
+    var canvas = unpack4x8unorm(strokes[under]).rgb;
+
+    // the cells away from the pixel's own that hold strokes long enough to reach it
+    let reach = i32(ceil(0.6 * clamp(params.stroke_length, 1.0, MAX_STROKE_LENGTH) + 0.75));
+    var offset = 0u;
+    for (var layer = 0u; layer < layers(); layer++) {
+        let width = f32(layer_width(layer));
+        let cell = vec2<i32>(pixel / layer_width(layer));
+        var top = vec4<f32>(0.0);
+        var top_depth = -1.0;
+        var below = vec4<f32>(0.0);
+        var below_depth = -1.0;
+        for (var y = -reach; y <= reach; y++) {
+            for (var x = -reach; x <= reach; x++) {
+                let neighbour = cell + vec2<i32>(x, y);
+                let index = stroke_index(layer, offset, neighbour);
+                if (index >= arrayLength(&strokes)) {
+                    continue;
+                }
+                let color = unpack4x8unorm(strokes[index]);
+                if (color.a == 0.0) {
+                    continue;
+                }
+                let stroke = stroke_shape(vec2<u32>(neighbour), layer);
+                let direction = normalize(unpack2x16snorm(strokes[index + 1u]));
+                let coverage = stroke_coverage(position - stroke.center, direction, stroke.half_length, width * 0.5);
+                if (coverage <= 0.0) {
+                    continue;
+                }
+                let paint = vec4<f32>(color.rgb, coverage);
+                if (stroke.depth > top_depth) {
+                    below = top;
+                    below_depth = top_depth;
+                    top = paint;
+                    top_depth = stroke.depth;
+                } else if (stroke.depth > below_depth) {
+                    below = paint;
+                    below_depth = stroke.depth;
+                }
+            }
+        }
+        canvas = mix(mix(canvas, below.rgb, below.a), top.rgb, top.a);
+        let cells = grid(layer);
+        offset += cells.x * cells.y * 2u;
+    }
+
+    textureStore(outputTexture, id.xy, vec4<f32>(canvas, 1.0));
+}
@@ -0,0 +1,139 @@
+@group(0) @binding(0) var inputTexture: texture_2d<f32>;
+@group(0) @binding(1) var gradientOutput: texture_storage_2d<rgba32float, write>;
+@group(0) @binding(2) var edgeOutput: texture_storage_2d<r32float, write>;
+@group(0) @binding(3) var<uniform> canny: CannyParameters;
+
+struct CannyParameters {
+    low_threshold: f32,
+    high_threshold: f32,
+}
+
+// edge texture values
+const WEAK: f32 = 0.5;
+const STRONG: f32 = 1.0;
+
+fn load(coord: vec2<i32>) -> vec4<f32> {
+    let size = vec2<i32>(textureDimensions(inputTexture));
+    return textureLoad(inputTexture, clamp(coord, vec2<i32>(0), size - 1), 0);
+}
+
+fn luminance(coord: vec2<i32>) -> f32 {
+    return dot(load(coord).rgb, vec3<f32>(0.2126, 0.7152, 0.0722));
+}
+
+
+// *******************************************
+//     sobel gradient of the blurred image
+// writes (gx, gy, magnitude, 0)
+
+@compute @workgroup_size(8, 8)
+fn gradient_main(@builtin(global_invocation_id) id: vec3<u32>) {
+    let size = textureDimensions(inputTexture);
+    if (id.x >= size.x || id.y >= size.y) {
+        return;
+    }
+
+    let texelCoord = vec2<i32>(id.xy);
+
+    var sobel_x = array<vec3<f32>, 3>(
+        vec3<f32>(-1.0, 0.0, 1.0),
+        vec3<f32>(-2.0, 0.0, 2.0),
+        vec3<f32>(-1.0, 0.0, 1.0)
+    );
+
+    var sobel_y = array<vec3<f32>, 3>(
+        vec3<f32>(-1.0, -2.0, -1.0),
+        vec3<f32>( 0.0,  0.0,  0.0),
+        vec3<f32>( 1.0,  2.0,  1.0)
+    );
+
+    var gradientX: f32 = 0.0;
+    var gradientY: f32 = 0.0;
+
+    for (var j: i32 = -1; j <= 1; j++) {
+        for (var i: i32 = -1; i <= 1; i++) {
+            let value = luminance(texelCoord + vec2<i32>(i, j));
+            gradientX += value * sobel_x[j + 1][i + 1];
+            gradientY += value * sobel_y[j + 1][i + 1];
+        }
+    }
+
+    let magnitude = sqrt(gradientX * gradientX + gradientY * gradientY);
+    textureStore(gradientOutput, texelCoord, vec4<f32>(gradientX, gradientY, magnitude, 0.0));
+}
+
+
+// *******************************************
+//  non-maximum suppression + double threshold
+// input is the gradient texture
+
+@compute @workgroup_size(8, 8)
+fn suppress_main(@builtin(global_invocation_id) id: vec3<u32>) {
+    let size = textureDimensions(inputTexture);
+    if (id.x >= size.x || id.y >= size.y) {
+        return;
+    }
+
+    let texelCoord = vec2<i32>(id.xy);
+    let gradient = load(texelCoord);
+    let magnitude = gradient.z;
+
+    // round the gradient direction to one of the four neighbour axes
+    var angle = degrees(atan2(gradient.y, gradient.x));
+    if (angle < 0.0) {
+        angle += 180.0;
+    }
+
+    var direction = vec2<i32>(1, 0);
+    if (angle >= 22.5 && angle < 67.5) {
+        direction = vec2<i32>(1, 1);
+    } else if (angle >= 67.5 && angle < 112.5) {
+        direction = vec2<i32>(0, 1);
+    } else if (angle >= 112.5 && angle < 157.5) {
+        direction = vec2<i32>(-1, 1);
+    }
+
+    // keep only the ridge of the gradient
+    let ahead = load(texelCoord + direction).z;
+    let behind = load(texelCoord - direction).z;
+
+    var edge = 0.0;
+    if (magnitude >= ahead && magnitude > behind) {
+        if (magnitude >= canny.high_threshold) {
+            edge = STRONG;
+        } else if (magnitude >= canny.low_threshold) {
+            edge = WEAK;
+        }
+    }
+
+    textureStore(edgeOutput, texelCoord, vec4<f32>(edge, 0.0, 0.0, 0.0));
+}
+
+
+// *******************************************
+//        hysteresis edge tracking
+// one step of promoting weak edges touching strong ones,
+// run repeatedly so strong edges grow along connected weak pixels
+
+@compute @workgroup_size(8, 8)
+fn hysteresis_main(@builtin(global_invocation_id) id: vec3<u32>) {
+    let size = textureDimensions(inputTexture);
+    if (id.x >= size.x || id.y >= size.y) {
+        return;
+    }
+
+    let texelCoord = vec2<i32>(id.xy);
+    var edge = load(texelCoord).r;
+
+    if (edge == WEAK) {
+        for (var j: i32 = -1; j <= 1; j++) {
+            for (var i: i32 = -1; i <= 1; i++) {
+                if (load(texelCoord + vec2<i32>(i, j)).r == STRONG) {
+                    edge = STRONG;
+                }
+            }
+        }
+    }
+
+    textureStore(edgeOutput, texelCoord, vec4<f32>(edge, 0.0, 0.0, 0.0));
+}
@@ -0,0 +1,172 @@
+@group(0) @binding(0) var baseTexture: texture_2d<f32>;
+@group(0) @binding(1) var effectTexture: texture_2d<f32>;
+@group(0) @binding(2) var sampler0: sampler;
+@group(0) @binding(3) var<uniform> composite: CompositeParameters;
+
+// blends the effect stage over the base stage
+struct CompositeParameters {
+    blend_mode: u32,
+    opacity: f32,
+    // the space both stages are in, see ColorSpace::get_index
+    working_space: u32,
+    // the target expects linear values, like an srgb surface that encodes on write or an exr file
+    output_linear: u32,
+    // stops applied before output, 0.0 when exporting
+    exposure: f32,
+    // rolls values above the display range off smoothly instead of clipping them
+    compress_highlights: u32,
+    // filter when zoomed in past 1:1, see Magnification::get_index
+    magnification: u32,
+}
+
+const NEAREST: u32 = 0u;
+const LANCZOS: u32 = 2u;
+const PI: f32 = 3.14159265;
+
+fn srgb_to_linear(c: vec3<f32>) -> vec3<f32> {
+    return select(pow((max(c, vec3<f32>(0.0)) + 0.055) / 1.055, vec3<f32>(2.4)), c / 12.92, c <= vec3<f32>(0.04045));
+}
+
+fn linear_to_srgb(c: vec3<f32>) -> vec3<f32> {
+    return select(1.055 * pow(max(c, vec3<f32>(0.0)), vec3<f32>(1.0 / 2.4)) - 0.055, c * 12.92, c <= vec3<f32>(0.0031308));
+}
+
+fn overlay(base: vec3<f32>, top: vec3<f32>) -> vec3<f32> {
+    return select(
+        1.0 - 2.0 * (1.0 - base) * (1.0 - top),
+        2.0 * base * top,
+        base < vec3<f32>(0.5)
+    );
+}
+
+// w3c compositing soft light
+fn soft_light(base: vec3<f32>, top: vec3<f32>) -> vec3<f32> {
+    let d = select(sqrt(base), ((16.0 * base - 12.0) * base + 4.0) * base, base <= vec3<f32>(0.25));
+    return select(
+        base + (2.0 * top - 1.0) * (d - base),
+        base - (1.0 - 2.0 * top) * base * (1.0 - base),
+        top <= vec3<f32>(0.5)
+    );
+}
+
+// linear below the knee, then an exponential shoulder that approaches 1.0
+fn compress(c: vec3<f32>) -> vec3<f32> {
+    let knee = 0.8;
+    let shoulder = knee + (1.0 - knee) * (1.0 - exp(-(c - knee) / (1.0 - knee)));
+    return select(c, shoulder, c > vec3<f32>(knee));
+}
+
+fn lanczos(x: f32) -> f32 {
+    if (abs(x) < 1e-5) {
+        return 1.0;
+    }
+    if (abs(x) >= 3.0) {
+        return 0.0;
+    }
+    let px = PI * x;
+    return 3.0 * sin(px) * sin(px / 3.0) / (px * px);
+}
+
+// 6x6 lanczos window around texcoord, sharper than bilinear without its blockiness
+fn sample_lanczos(t: texture_2d<f32>, texcoord: vec2<f32>) -> vec4<f32> {
+    let size = vec2<i32>(textureDimensions(t));
+    let position = texcoord * vec2<f32>(size) - 0.5;
+    let origin = floor(position);
+    let fraction = position - origin;
+
+    var sum = vec4<f32>(0.0);
+    var total = 0.0;
+    for (var y = -2; y <= 3; y++) {
+        let wy = lanczos(fraction.y - f32(y));
+        for (var x = -2; x <= 3; x++) {
+            let w = lanczos(fraction.x - f32(x)) * wy;
+            let coord = clamp(vec2<i32>(origin) + vec2<i32>(x, y), vec2<i32>(0), size - 1);
+            sum += textureLoad(t, coord, 0) * w;
+            total += w;
+        }
+    }
+    return sum / total;
+}
+
+// bilinear from the first level. the sampler is anisotropic, which some backends
+// apply even at 1:1 where it blends neighbouring texels
+fn sample_bilinear(t: texture_2d<f32>, texcoord: vec2<f32>) -> vec4<f32> {
+    let size = vec2<i32>(textureDimensions(t));
+    let position = texcoord * vec2<f32>(size) - 0.5;
+    let origin = floor(position);
+    let fraction = position - origin;
+    let first = clamp(vec2<i32>(origin), vec2<i32>(0), size - 1);
+    let last = clamp(vec2<i32>(origin) + 1, vec2<i32>(0), size - 1);
+
+    let top = mix(textureLoad(t, first, 0), textureLoad(t, vec2<i32>(last.x, first.y), 0), fraction.x);
+    let bottom = mix(textureLoad(t, vec2<i32>(first.x, last.y), 0), textureLoad(t, last, 0), fraction.x);
+    return mix(top, bottom, fraction.y);
+}
+
+// zoomed out the sampler filters across the mip chain, zoomed in past 1:1
+// the selected magnification fills in between texels.
+// the gradients come from frag_main, derivatives need uniform control flow.
+// 1:1 counts as zoomed in so exports and the unzoomed view read texels as they are
+fn sample_image(t: texture_2d<f32>, texcoord: vec2<f32>, dx: vec2<f32>, dy: vec2<f32>) -> vec4<f32> {
+    let size = vec2<f32>(textureDimensions(t));
+    let footprint = max(length(dx * size), length(dy * size));
+    if (footprint > 1.001) {
+        return textureSampleGrad(t, sampler0, texcoord, dx, dy);
+    }
+    switch composite.magnification {
+        case NEAREST: {
+            let coord = clamp(vec2<i32>(floor(texcoord * size)), vec2<i32>(0), vec2<i32>(size) - 1);
+            return textureLoad(t, coord, 0);
+        }
+        case LANCZOS: {
+            return sample_lanczos(t, texcoord);
+        }
+        default: {
+            return sample_bilinear(t, texcoord);
+        }
+    }
+}
+
+@fragment
+fn frag_main(@location(0) texcoord: vec2<f32>) -> @location(0) vec4<f32> {
+    let dx = dpdx(texcoord);
+    let dy = dpdy(texcoord);
+    let base = sample_image(baseTexture, texcoord, dx, dy);
+    let effect = sample_image(effectTexture, texcoord, dx, dy);
+
+    // blending happens in the working space, display encoded matches image editors,
+    // linear matches how light adds up. values above 1.0 from hdr sources are kept
+    // except by the modes only defined on the unit range
+    let b = max(base.rgb, vec3<f32>(0.0));
+    let t = max(effect.rgb, vec3<f32>(0.0));
+    let b01 = min(b, vec3<f32>(1.0));
+    let t01 = min(t, vec3<f32>(1.0));
+
+    var blended = t;
+    switch composite.blend_mode {
+        case 1u: { blended = b * t; }
+        case 2u: { blended = 1.0 - (1.0 - b01) * (1.0 - t01); }
+        case 3u: { blended = overlay(b01, t01); }
+        case 4u: { blended = soft_light(b01, t01); }
+        case 5u: { blended = abs(b - t); }
+        case 6u: { blended = min(b, t); }
+        case 7u: { blended = max(b, t); }
+        case 8u: { blended = b + t; }
+        default: {}
+    }
+
+    var color = mix(b, blended, composite.opacity);
+    if (composite.working_space == 1u) {
+        color = srgb_to_linear(color);
+    }
+
+    color *= exp2(composite.exposure);
+    if (composite.compress_highlights != 0u) {
+        color = compress(color);
+    }
+
+    if (composite.output_linear == 0u) {
+        color = linear_to_srgb(color);
+    }
+    return vec4<f32>(color, mix(base.a, effect.a, composite.opacity));
+}
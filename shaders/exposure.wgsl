@@ -0,0 +1,69 @@
+@group(0) @binding(0) var inputTexture: texture_2d<f32>;
+@group(0) @binding(1) var<storage, read_write> histogram: array<atomic<u32>, 256>;
+// 1x1, average scene luminance
+@group(0) @binding(2) var averageTexture: texture_storage_2d<r32float, write>;
+@group(0) @binding(3) var<uniform> exposure: ExposureParameters;
+
+// log2 luminance between min_log_luminance and min_log_luminance + log_luminance_range
+// is spread over bins 1 to 255, bin 0 collects black and transparent pixels
+struct ExposureParameters {
+    min_log_luminance: f32,
+    log_luminance_range: f32,
+    // x, y, width, height of the pixels to count
+    region: vec4<u32>,
+}
+
+var<workgroup> bins: array<atomic<u32>, 256>;
+var<workgroup> weighted: array<f32, 256>;
+var<workgroup> counted: array<f32, 256>;
+
+fn bin_index(color: vec4<f32>) -> u32 {
+    let luminance = dot(color.rgb, vec3<f32>(0.2126, 0.7152, 0.0722));
+    if (luminance < 1e-5 || color.a <= 0.0) {
+        return 0u;
+    }
+    let t = clamp((log2(luminance) - exposure.min_log_luminance) / exposure.log_luminance_range, 0.0, 1.0);
+    return u32(t * 254.0 + 1.0);
+}
+
+// each workgroup counts its tile in shared memory, then adds it to the global histogram
+@compute @workgroup_size(16, 16)
+fn histogram_main(@builtin(global_invocation_id) id: vec3<u32>, @builtin(local_invocation_index) index: u32) {
+    atomicStore(&bins[index], 0u);
+    workgroupBarrier();
+
+    if (id.x < exposure.region.z && id.y < exposure.region.w) {
+        let color = textureLoad(inputTexture, vec2<i32>(exposure.region.xy + id.xy), 0);
+        atomicAdd(&bins[bin_index(color)], 1u);
+    }
+    workgroupBarrier();
+
+    atomicAdd(&histogram[index], atomicLoad(&bins[index]));
+}
+
+// one workgroup, one thread per bin. reduces the histogram to its mean bin
+// and clears it for the next frame
+@compute @workgroup_size(256)
+fn average_main(@builtin(local_invocation_index) index: u32) {
+    // bin 0 has no luminance to average
+    let count = select(f32(atomicLoad(&histogram[index])), 0.0, index == 0u);
+    weighted[index] = count * f32(index);
+    counted[index] = count;
+    atomicStore(&histogram[index], 0u);
+    workgroupBarrier();
+
+    for (var stride = 128u; stride > 0u; stride >>= 1u) {
+        if (index < stride) {
+            weighted[index] += weighted[index + stride];
+            counted[index] += counted[index + stride];
+        }
+        workgroupBarrier();
+    }
+
+    if (index == 0u) {
+        let lit = max(counted[0], 1.0);
+        let mean_bin = weighted[0] / lit;
+        let log_average = (mean_bin - 1.0) / 254.0 * exposure.log_luminance_range + exposure.min_log_luminance;
+        textureStore(averageTexture, vec2<i32>(0, 0), vec4<f32>(exp2(log_average), 0.0, 0.0, 0.0));
+    }
+}
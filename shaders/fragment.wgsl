@@ -1,20 +1,462 @@
 @group(0) @binding(0) var inputTexture: texture_2d<f32>;
 @group(0) @binding(1) var sampler0: sampler;
+// separable gaussian blurs of inputTexture, rendered ahead of this pass.
+// blurTexture1 uses sigma1 and blurTexture2 uses sigma1 / 16
+@group(0) @binding(2) var blurTexture1: texture_2d<f32>;
+@group(0) @binding(3) var blurTexture2: texture_2d<f32>;
+// canny edges computed from blurTexture1, 1.0 on edges
+@group(0) @binding(4) var cannyTexture: texture_2d<f32>;
+// edge preserving smoothing of inputTexture (bilateral or guided), rendered ahead of this pass.
+// the blur textures are computed from it instead of inputTexture when DoG / XDoG use a prefilter
+@group(0) @binding(5) var smoothTexture: texture_2d<f32>;
+// 1x1 average luminance of inputTexture for auto exposure, only written when tone mapping uses it
+@group(0) @binding(6) var exposureTexture: texture_2d<f32>;
+// the inputs an effect can declare besides inputTexture, see Shader::inputs. they hold the
+// space in inputSpaces, loadInput reads them in the space the effect runs in.
+// the base stage has no previous stage, previousTexture is the original for it
+@group(0) @binding(7) var originalTexture: texture_2d<f32>;
+@group(0) @binding(8) var previousTexture: texture_2d<f32>;
+@group(0) @binding(9) var secondTexture: texture_2d<f32>;
+// the stage's mask as painted, 1.0 where the effect shows
+@group(0) @binding(10) var maskTexture: texture_2d<f32>;
+@group(0) @binding(11) var<uniform> inputSpaces: InputSpaces;
 
 @group(1) @binding(0) var<uniform> params: Parameters;
 
-struct Parameters {
-    sigma1: f32,
-    tau: f32,
-    gfact: f32,
-    epsilon: f32,
-    num_gvf_iterations: i32,
-    enable_xdog: u32,
-    shader_index: u32,
-    colors: f32,
-    intensity: f32,
+@group(2) @binding(0) var lutTexture: texture_3d<f32>;
+@group(2) @binding(1) var lutSampler: sampler;
+// thresholds in (0, 1) ranked by void and cluster, see BlueNoise
+@group(2) @binding(4) var blueNoiseTexture: texture_2d<f32>;
+
+// color spaces, see ColorSpace::get_index
+const LINEAR: u32 = 0u;
+
+struct InputSpaces {
+    original: u32,
+    previous_stage: u32,
+    second_image: u32,
+    effect: u32,
+}
+
+// the texel of a declared input under texcoord, converted as in stage.wgsl
+fn loadInput(input: texture_2d<f32>, space: u32, texcoord: vec2<f32>) -> vec4<f32> {
+    let size = vec2<i32>(textureDimensions(input));
+    let value = textureLoad(input, clamp(vec2<i32>(texcoord * vec2<f32>(size)), vec2<i32>(0), size - 1), 0);
+    if (space == inputSpaces.effect) {
+        return value;
+    }
+    let c = max(value.rgb, vec3<f32>(0.0));
+    if (inputSpaces.effect == LINEAR) {
+        return vec4<f32>(select(pow((c + 0.055) / 1.055, vec3<f32>(2.4)), c / 12.92, c <= vec3<f32>(0.04045)), value.a);
+    }
+    return vec4<f32>(select(1.055 * pow(c, vec3<f32>(1.0 / 2.4)) - 0.055, c * 12.92, c <= vec3<f32>(0.0031308)), value.a);
+}
+
+fn loadOriginal(texcoord: vec2<f32>) -> vec4<f32> {
+    return loadInput(originalTexture, inputSpaces.original, texcoord);
+}
+
+fn loadPrevious(texcoord: vec2<f32>) -> vec4<f32> {
+    return loadInput(previousTexture, inputSpaces.previous_stage, texcoord);
+}
+
+fn loadSecond(texcoord: vec2<f32>) -> vec4<f32> {
+    return loadInput(secondTexture, inputSpaces.second_image, texcoord);
+}
+
+fn loadMask(texcoord: vec2<f32>) -> f32 {
+    let size = vec2<i32>(textureDimensions(maskTexture));
+    return textureLoad(maskTexture, clamp(vec2<i32>(texcoord * vec2<f32>(size)), vec2<i32>(0), size - 1), 0).r;
+}
+
+// krzysztof narkowicz's fit of the aces reference rendering transform
+fn aces(x: vec3<f32>) -> vec3<f32> {
+    return (x * (2.51 * x + 0.03)) / (x * (2.43 * x + 0.59) + 0.14);
+}
+
+// john hable's uncharted 2 filmic curve
+fn hable(x: vec3<f32>) -> vec3<f32> {
+    let a = 0.15;
+    let b = 0.50;
+    let c = 0.10;
+    let d = 0.20;
+    let e = 0.02;
+    let f = 0.30;
+    return ((x * (a * x + c * b) + d * e) / (x * (a * x + b) + d * f)) - e / f;
+}
+
+// minimal agx with the default look, after benjamin wrensch
+fn agx(x: vec3<f32>) -> vec3<f32> {
+    let inset = mat3x3<f32>(
+        0.842479062253094, 0.0423282422610123, 0.0423756549057051,
+        0.0784335999999992, 0.878468636469772, 0.0784336,
+        0.0792237451477643, 0.0791661274605434, 0.879142973793104,
+    );
+    let outset = mat3x3<f32>(
+        1.19687900512017, -0.0528968517574562, -0.0529716355144438,
+        -0.0980208811401368, 1.15190312990417, -0.0980434501171241,
+        -0.0990297440797205, -0.0989611768448433, 1.15107367264116,
+    );
+    let min_ev = -12.47393;
+    let max_ev = 4.026069;
+
+    var v = inset * max(x, vec3<f32>(1e-10));
+    v = (clamp(log2(v), vec3<f32>(min_ev), vec3<f32>(max_ev)) - min_ev) / (max_ev - min_ev);
+
+    // sigmoid contrast approximation
+    let v2 = v * v;
+    let v4 = v2 * v2;
+    v = 15.5 * v4 * v2 - 40.14 * v4 * v + 31.96 * v4 - 6.868 * v2 * v + 0.4298 * v2 + 0.1191 * v - 0.00232;
+
+    return pow(max(outset * v, vec3<f32>(0.0)), vec3<f32>(2.2));
+}
+
+// maps scene linear values into 0..1 display linear values.
+// every operator is normalized so white_point lands on 1.0
+fn tone_map(x: vec3<f32>, white: f32) -> vec3<f32> {
+    switch params.tone_operator {
+        case 1u: {
+            // extended reinhard on luminance, which keeps hues
+            let luminance = dot(x, vec3<f32>(0.2126, 0.7152, 0.0722));
+            let mapped = luminance * (1.0 + luminance / (white * white)) / (1.0 + luminance);
+            return x * (mapped / max(luminance, 1e-6));
+        }
+        case 2u: {
+            return agx(x) / agx(vec3<f32>(white));
+        }
+        case 3u: {
+            // hable's curve is authored for an exposure bias of 2
+            return hable(x * 2.0) / hable(vec3<f32>(white));
+        }
+        default: {
+            return aces(x) / aces(vec3<f32>(white));
+        }
+    }
+}
+
+// thresholds of the bayer matrix grown to 2^order pixels square, in (0, 1].
+// order 2 is the 4x4 matrix of bayer dithering
+fn bayer(pixel: vec2<u32>, order: u32) -> f32 {
+    var index = 0u;
+    for (var bit = 0u; bit < order; bit++) {
+        let x = (pixel.x >> bit) & 1u;
+        let y = (pixel.y >> bit) & 1u;
+        index |= (2u * (x ^ y) + y) << (2u * (order - 1u - bit));
+    }
+    return f32(index + 1u) / f32(1u << (2u * order));
+}
+
+// the center of texcoord's pixel in the whole image, so patterns line up across the tiles of a tiled image
+fn image_position(texcoord: vec2<f32>) -> vec2<f32> {
+    return floor(texcoord * vec2<f32>(textureDimensions(inputTexture))) + 0.5 + vec2<f32>(f32(params.tile_x), f32(params.tile_y));
+}
+
+// the size of the whole image, which only the stage's input shows when the image is not tiled
+fn image_size() -> vec2<f32> {
+    if (params.image_width == 0u) {
+        return vec2<f32>(textureDimensions(inputTexture));
+    }
+    return vec2<f32>(f32(params.image_width), f32(params.image_height));
+}
+
+// the input at a position in the whole image, which has to fall in the tile being rendered
+fn sample_image(position: vec2<f32>) -> vec4<f32> {
+    let origin = vec2<f32>(f32(params.tile_x), f32(params.tile_y));
+    return textureSampleLevel(inputTexture, sampler0, (position - origin) / vec2<f32>(textureDimensions(inputTexture)), 0.0);
+}
+
+// cmyk ink coverages of a display encoded color, black taking what the other three share
+fn cmyk(color: vec3<f32>) -> vec4<f32> {
+    let k = 1.0 - max(color.r, max(color.g, color.b));
+    let cmy = (1.0 - color - k) / max(1.0 - k, 1e-5);
+    return vec4<f32>(clamp(cmy, vec3<f32>(0.0), vec3<f32>(1.0)), k);
+}
+
+// coverage of one of the cmyk inks, 4 for the black ink of single color halftones.
+// dot gain spreads a 50% dot by dot_gain and less towards either end
+fn ink_coverage(color: vec3<f32>, ink: u32) -> f32 {
+    var coverage = 1.0 - dot(color, vec3<f32>(0.2126, 0.7152, 0.0722));
+    if (ink < 4u) {
+        coverage = cmyk(color)[ink];
+    }
+    return clamp(coverage + params.dot_gain * 4.0 * coverage * (1.0 - coverage), 0.0, 1.0);
+}
+
+// spot functions of the dot shapes across a cell from -1 to 1, 0 in its middle and 1 in its corners.
+// pixels take ink where the spot is under the ink's coverage
+fn spot(cell: vec2<f32>) -> f32 {
+    let c = cos(cell * 3.14159265);
+    switch params.dot_shape {
+        // lines
+        case 1u: {
+            return (1.0 - c.y) / 2.0;
+        }
+        // ellipses, which chain up along the screen's y axis
+        case 2u: {
+            return (3.0 - 2.0 * c.x - c.y) / 6.0;
+        }
+        // squares, whose area is the coverage
+        case 3u: {
+            let d = max(abs(cell.x), abs(cell.y));
+            return d * d;
+        }
+        // round dots that meet in a checkerboard at 50% and leave round holes past it
+        default: {
+            return (2.0 - c.x - c.y) / 4.0;
+        }
+    }
+}
+
+// how much of the pixel at position a screen of halftone_cell pixel dots at angle degrees inks.
+// each dot is sized by the color around its center
+fn screen(position: vec2<f32>, angle: f32, ink: u32) -> f32 {
+    let size = max(params.halftone_cell, 1.0);
+    let a = radians(angle);
+    let to_image = mat2x2<f32>(cos(a), sin(a), -sin(a), cos(a));
+    let on_screen = transpose(to_image) * position / size;
+    let center = floor(on_screen) + 0.5;
+
+    var color = vec3<f32>(0.0);
+    for (var i = 0u; i < 4u; i++) {
+        let offset = vec2<f32>(select(-0.25, 0.25, (i & 1u) == 1u), select(-0.25, 0.25, i >= 2u));
+        color += sample_image(to_image * (center + offset) * size).rgb;
+    }
+    let coverage = ink_coverage(color / 4.0, ink);
+
+    // antialiased over the pixel's footprint on the spot function, measured a pixel across
+    // rather than with fwidth, which would change with how a tile's pixels fall into quads
+    let cell = (on_screen - center) * 2.0;
+    let s = spot(cell);
+    let pixel = transpose(to_image) * (2.0 / size);
+    let footprint = abs(spot(cell + pixel[0]) - s) + abs(spot(cell + pixel[1]) - s);
+    let inked = clamp((coverage - s) / max(footprint, 1e-4) + 0.5, 0.0, 1.0);
+    return select(inked, 0.0, coverage <= 0.0);
 }
 
+// the eight colors of quantization
+const BASIC_PALETTE = array<vec3<f32>, 8>(
+    vec3<f32>(0.0, 0.0, 0.0), // Black
+    vec3<f32>(1.0, 1.0, 1.0), // White
+    vec3<f32>(1.0, 0.0, 0.0), // Red
+    vec3<f32>(0.0, 1.0, 0.0), // Green
+    vec3<f32>(0.0, 0.0, 1.0), // Blue
+    vec3<f32>(1.0, 1.0, 0.0), // Yellow
+    vec3<f32>(1.0, 0.0, 1.0), // Magenta
+    vec3<f32>(0.0, 1.0, 1.0)  // Cyan
+);
+
+// the four shades of the original game boy's screen, dark to light
+const GAME_BOY_PALETTE = array<vec3<f32>, 4>(
+    vec3<f32>(0.059, 0.220, 0.059), vec3<f32>(0.188, 0.384, 0.188), vec3<f32>(0.545, 0.675, 0.059), vec3<f32>(0.608, 0.737, 0.059)
+);
+
+// cga's four color graphics mode with the high intensity cyan, magenta and white palette
+const CGA_PALETTE = array<vec3<f32>, 4>(
+    vec3<f32>(0.000, 0.000, 0.000), vec3<f32>(0.333, 1.000, 1.000), vec3<f32>(1.000, 0.333, 1.000), vec3<f32>(1.000, 1.000, 1.000)
+);
+
+// the colors the nes's 2c02 puts out, the repeated blacks left out
+const NES_PALETTE = array<vec3<f32>, 55>(
+    vec3<f32>(0.486, 0.486, 0.486), vec3<f32>(0.000, 0.000, 0.988), vec3<f32>(0.000, 0.000, 0.737), vec3<f32>(0.267, 0.157, 0.737),
+    vec3<f32>(0.580, 0.000, 0.518), vec3<f32>(0.659, 0.000, 0.125), vec3<f32>(0.659, 0.063, 0.000), vec3<f32>(0.533, 0.078, 0.000),
+    vec3<f32>(0.314, 0.188, 0.000), vec3<f32>(0.000, 0.471, 0.000), vec3<f32>(0.000, 0.408, 0.000), vec3<f32>(0.000, 0.345, 0.000),
+    vec3<f32>(0.000, 0.251, 0.345),
+    vec3<f32>(0.737, 0.737, 0.737), vec3<f32>(0.000, 0.471, 0.973), vec3<f32>(0.000, 0.345, 0.973), vec3<f32>(0.408, 0.267, 0.988),
+    vec3<f32>(0.847, 0.000, 0.800), vec3<f32>(0.894, 0.000, 0.345), vec3<f32>(0.973, 0.220, 0.000), vec3<f32>(0.894, 0.361, 0.063),
+    vec3<f32>(0.675, 0.486, 0.000), vec3<f32>(0.000, 0.722, 0.000), vec3<f32>(0.000, 0.659, 0.000), vec3<f32>(0.000, 0.659, 0.267),
+    vec3<f32>(0.000, 0.533, 0.533),
+    vec3<f32>(0.973, 0.973, 0.973), vec3<f32>(0.235, 0.737, 0.988), vec3<f32>(0.408, 0.533, 0.988), vec3<f32>(0.596, 0.471, 0.973),
+    vec3<f32>(0.973, 0.471, 0.973), vec3<f32>(0.973, 0.345, 0.596), vec3<f32>(0.973, 0.471, 0.345), vec3<f32>(0.988, 0.627, 0.267),
+    vec3<f32>(0.973, 0.722, 0.000), vec3<f32>(0.722, 0.973, 0.094), vec3<f32>(0.345, 0.847, 0.329), vec3<f32>(0.345, 0.973, 0.596),
+    vec3<f32>(0.000, 0.910, 0.847), vec3<f32>(0.471, 0.471, 0.471),
+    vec3<f32>(0.988, 0.988, 0.988), vec3<f32>(0.643, 0.894, 0.988), vec3<f32>(0.722, 0.722, 0.973), vec3<f32>(0.847, 0.722, 0.973),
+    vec3<f32>(0.973, 0.722, 0.973), vec3<f32>(0.973, 0.643, 0.753), vec3<f32>(0.941, 0.816, 0.690), vec3<f32>(0.988, 0.878, 0.659),
+    vec3<f32>(0.973, 0.847, 0.471), vec3<f32>(0.847, 0.973, 0.471), vec3<f32>(0.722, 0.973, 0.722), vec3<f32>(0.722, 0.973, 0.847),
+    vec3<f32>(0.000, 0.988, 0.988), vec3<f32>(0.973, 0.847, 0.973), vec3<f32>(0.000, 0.000, 0.000)
+);
+
+// the closest color of a palette, 0 the colors of quantization, then the palettes of ConsolePalette
+fn nearest_color(color: vec3<f32>, palette: u32) -> vec3<f32> {
+    switch palette {
+        // the game boy shows shades rather than colors, picked by luminance
+        case 1u: {
+            let luminance = dot(color, vec3<f32>(0.2126, 0.7152, 0.0722));
+            var shades = GAME_BOY_PALETTE;
+            return shades[min(u32(luminance * 4.0), 3u)];
+        }
+        case 2u: {
+            var colors = CGA_PALETTE;
+            var closest = colors[0];
+            for (var i = 1u; i < 4u; i++) {
+                if (length(color - colors[i]) < length(color - closest)) {
+                    closest = colors[i];
+                }
+            }
+            return closest;
+        }
+        case 3u: {
+            var colors = NES_PALETTE;
+            var closest = colors[0];
+            for (var i = 1u; i < 55u; i++) {
+                if (length(color - colors[i]) < length(color - closest)) {
+                    closest = colors[i];
+                }
+            }
+            return closest;
+        }
+        default: {
+            var colors = BASIC_PALETTE;
+            var closest = colors[0];
+            for (var i = 1u; i < 8u; i++) {
+                if (length(color - colors[i]) < length(color - closest)) {
+                    closest = colors[i];
+                }
+            }
+            return closest;
+        }
+    }
+}
+
+// mark jarzynski and marc olano's pcg3d hash, three well mixed values from three
+fn pcg3d(input: vec3<u32>) -> vec3<u32> {
+    var v = input * 1664525u + 1013904223u;
+    v.x += v.y * v.z;
+    v.y += v.z * v.x;
+    v.z += v.x * v.y;
+    v ^= v >> vec3<u32>(16u);
+    v.x += v.y * v.z;
+    v.y += v.z * v.x;
+    v.z += v.x * v.y;
+    return v;
+}
+
+// three values in [0, 1) for a point of a lattice anchored to the image. the same seed always
+// draws the same values, and they change with the frame only while grain animates
+fn random3(point: vec2<i32>, seed: u32) -> vec3<f32> {
+    let v = pcg3d(vec3<u32>(bitcast<vec2<u32>>(point), seed ^ (params.frame * 0x9e3779b9u)));
+    return vec3<f32>(v >> vec3<u32>(8u)) / 16777216.0;
+}
+
+// random values on the lattice, blended smoothly in between
+fn value_noise(position: vec2<f32>, seed: u32) -> vec3<f32> {
+    let cell = floor(position);
+    let i = vec2<i32>(cell);
+    let f = position - cell;
+    let u = f * f * (3.0 - 2.0 * f);
+    let a = random3(i, seed);
+    let b = random3(i + vec2<i32>(1, 0), seed);
+    let c = random3(i + vec2<i32>(0, 1), seed);
+    let d = random3(i + vec2<i32>(1, 1), seed);
+    return mix(mix(a, b, u.x), mix(c, d, u.x), u.y);
+}
+
+// the slope of perlin noise's random gradient at a corner of position's cell, over to position
+fn perlin_corner(i: vec2<i32>, corner: vec2<i32>, f: vec2<f32>, seed: u32) -> f32 {
+    let angle = random3(i + corner, seed).x * 6.28318531;
+    return dot(vec2<f32>(cos(angle), sin(angle)), f - vec2<f32>(corner));
+}
+
+// noise of kind NoiseType in [0, 1], with features about a lattice cell across
+fn noise(position: vec2<f32>, kind: u32, seed: u32) -> f32 {
+    let cell = floor(position);
+    let i = vec2<i32>(cell);
+    switch kind {
+        case 1u: {
+            return value_noise(position, seed).x;
+        }
+        // perlin's improved gradient noise
+        case 2u: {
+            let f = position - cell;
+            let u = f * f * f * (f * (f * 6.0 - 15.0) + 10.0);
+            let a = perlin_corner(i, vec2<i32>(0, 0), f, seed);
+            let b = perlin_corner(i, vec2<i32>(1, 0), f, seed);
+            let c = perlin_corner(i, vec2<i32>(0, 1), f, seed);
+            let d = perlin_corner(i, vec2<i32>(1, 1), f, seed);
+            return clamp(0.5 + 0.7071 * mix(mix(a, b, u.x), mix(c, d, u.x), u.y), 0.0, 1.0);
+        }
+        // the blue noise tile repeated over the image, every seed shifts it by its own offset
+        case 3u: {
+            let size = vec2<i32>(textureDimensions(blueNoiseTexture));
+            let offset = vec2<i32>(random3(vec2<i32>(0), seed).xy * vec2<f32>(size));
+            return textureLoad(blueNoiseTexture, ((i + offset) % size + size) % size, 0).r;
+        }
+        default: {
+            return random3(i, seed).x;
+        }
+    }
+}
+
+// rough sensitivities of the red, green and blue channels to light of a wavelength in nanometres.
+// red picks up violet too, so the blue end of fringes turns purple
+fn spectrum(wavelength: f32) -> vec3<f32> {
+    let peaks = vec4<f32>(600.0, 550.0, 450.0, 440.0);
+    let widths = vec4<f32>(45.0, 45.0, 35.0, 25.0);
+    let d = (wavelength - peaks) / widths;
+    let lobes = exp(-0.5 * d * d);
+    return vec3<f32>(lobes.x + 0.3 * lobes.w, lobes.y, lobes.z);
+}
+
+// one pixel of the picture a crt shows, pitch pixels of the image across, in the console palette if one is picked
+fn crt_pixel(pixel: vec2<f32>, pitch: vec2<f32>) -> vec3<f32> {
+    let center = (pixel + 0.5) * pitch;
+    var color = vec3<f32>(0.0);
+    for (var i = 0u; i < 4u; i++) {
+        let offset = vec2<f32>(select(-0.25, 0.25, (i & 1u) == 1u), select(-0.25, 0.25, i >= 2u));
+        color += sample_image(center + offset * pitch).rgb;
+    }
+    color /= 4.0;
+    if (params.crt_palette != 0u) {
+        color = nearest_color(color, params.crt_palette);
+    }
+    return color;
+}
+
+// what the beam draws along a line at x, blending into the next pixel only close to it
+fn crt_line(line: f32, x: f32, pitch: vec2<f32>) -> vec3<f32> {
+    let position = x / pitch.x - 0.5;
+    let left = floor(position);
+    let a = crt_pixel(vec2<f32>(left, line), pitch);
+    let b = crt_pixel(vec2<f32>(left + 1.0, line), pitch);
+    return mix(a, b, smoothstep(0.25, 0.75, position - left));
+}
+
+// a line's light distance lines away from its middle. brighter beams spread wider
+fn crt_beam(color: vec3<f32>, distance: f32) -> vec3<f32> {
+    let sigma = mix(vec3<f32>(0.3), vec3<f32>(0.5), color);
+    return color * exp(-distance * distance / (2.0 * sigma * sigma));
+}
+
+// how the phosphors of a mask let through each channel at a pixel of the image
+fn crt_mask(position: vec2<f32>) -> vec3<f32> {
+    let dark = vec3<f32>(1.0 - params.mask_strength);
+    let phosphor = max(floor(params.scanline_size / 4.0), 1.0);
+    var column = floor(position.x / phosphor);
+    switch params.crt_mask {
+        // vertical stripes, every other row of triads shifted half a triad like the dots of a delta mask
+        case 2u: {
+            let row = floor(position.y / (2.0 * phosphor));
+            column = floor(position.x / phosphor + 1.5 * (row % 2.0));
+        }
+        // stripes broken into slots, staggered between neighbouring triads
+        case 3u: {
+            let triad = floor(column / 3.0);
+            let row = floor(position.y / phosphor + 2.0 * (triad % 2.0));
+            if (row % 4.0 == 3.0) {
+                return dark;
+            }
+        }
+        case 1u: {}
+        default: {
+            return vec3<f32>(1.0);
+        }
+    }
+    let channel = u32(column % 3.0);
+    return select(dark, vec3<f32>(1.0 + params.mask_strength), vec3<u32>(0u, 1u, 2u) == vec3<u32>(channel));
+}
+
+// the stage hands each effect its input in the color space the effect expects,
+// see Shader::color_space
+
 @fragment
 fn frag_main(@location(0) texcoord: vec2<f32>) -> @location(0) vec4<f32> {
     if (params.shader_index == 0u) { 
@@ -35,31 +477,7 @@ fn frag_main(@location(0) texcoord: vec2<f32>) -> @location(0) vec4<f32> {
         //                gaussian blur
 
 
-        var kernelSize: i32 = i32(ceil(params.sigma1 * 3) * 2.0 + 1.0);
-
-        var textureSize: vec2<f32> = vec2<f32>(textureDimensions(inputTexture));
-
-        var result: vec4<f32> = vec4<f32>(0.0, 0.0, 0.0, 0.0);
-        var sum: f32 = 0.0;
-
-        // Horizontal pass
-        for (var offsetX : i32 = -kernelSize / 2; offsetX <= kernelSize / 2; offsetX++) {
-            var samplePos: vec2<f32> = texcoord + vec2<f32>(f32(offsetX) / textureSize.x, 0.0);
-            var weight: f32 = exp(-(f32(offsetX) * f32(offsetX)) / (2.0 * params.sigma1 * params.sigma1)) / (sqrt(2.0 * 3.14159) * params.sigma1);
-            result += textureSample(inputTexture, sampler0, samplePos) * weight;
-            sum += weight;
-        }
-        result /= sum;
-
-        // Vertical pass
-        sum = 0.0;
-        for (var offsetY : i32 = -kernelSize / 2; offsetY <= kernelSize / 2; offsetY++) {
-            var samplePos: vec2<f32> = texcoord + vec2<f32>(0.0, f32(offsetY) / textureSize.y);
-            var weight: f32 = exp(-(f32(offsetY) * f32(offsetY)) / (2.0 * params.sigma1 * params.sigma1)) / (sqrt(2.0 * 3.14159) * params.sigma1);
-            result += textureSample(inputTexture, sampler0, samplePos) * weight;
-            sum += weight;
-        }
-        result /= sum;
+        var result: vec4<f32> = textureSample(blurTexture1, sampler0, texcoord);
 
         return result;
     } else if (params.shader_index == 2u) { 
@@ -69,69 +487,12 @@ fn frag_main(@location(0) texcoord: vec2<f32>) -> @location(0) vec4<f32> {
 
 
         let texelCoord = vec2<i32>(texcoord * vec2<f32>(textureDimensions(inputTexture)));
-        let srcPixel: vec4<f32> = textureLoad(inputTexture, texelCoord, 0);
-
-        // Quantize the pixel to a predefined set of colors
-        let palette: array<vec3<f32>, 8> = array<vec3<f32>, 8>(
-            vec3<f32>(0.0, 0.0, 0.0), // Black
-            vec3<f32>(1.0, 1.0, 1.0), // White
-            vec3<f32>(1.0, 0.0, 0.0), // Red
-            vec3<f32>(0.0, 1.0, 0.0), // Green
-            vec3<f32>(0.0, 0.0, 1.0), // Blue
-            vec3<f32>(1.0, 1.0, 0.0), // Yellow
-            vec3<f32>(1.0, 0.0, 1.0), // Magenta
-            vec3<f32>(0.0, 1.0, 1.0)  // Cyan
-        );
-
-        // Find the closest color in the palette
-        let black_distance = length(srcPixel.rgb - palette[0]);
-        let white_distance = length(srcPixel.rgb - palette[1]);
-        let red_distance = length(srcPixel.rgb - palette[2]);
-        let green_distance = length(srcPixel.rgb - palette[3]);
-        let blue_distance = length(srcPixel.rgb - palette[4]);
-        let yellow_distance = length(srcPixel.rgb - palette[5]);
-        let magenta_distance = length(srcPixel.rgb - palette[6]);
-        let cyan_distance = length(srcPixel.rgb - palette[7]);
-
-        var minDistance: f32 = black_distance;
-        var closestColor: vec3<f32> = palette[0];
-
-        if (white_distance < minDistance) {
-            minDistance = white_distance;
-            closestColor = palette[1];
-        }
-
-        if (red_distance < minDistance) {
-            minDistance = red_distance;
-            closestColor = palette[2];
-        }
-
-        if (green_distance < minDistance) {
-            minDistance = green_distance;
-            closestColor = palette[3];
-        }
-
-        if (blue_distance < minDistance) {
-            minDistance = blue_distance;
-            closestColor = palette[4];
-        }
-
-        if (yellow_distance < minDistance) {
-            minDistance = yellow_distance;
-            closestColor = palette[5];
-        }
-
-        if (magenta_distance < minDistance) {
-            minDistance = magenta_distance;
-            closestColor = palette[6];
-        }
-
-        if (cyan_distance < minDistance) {
-            minDistance = cyan_distance;
-            closestColor = palette[7];
+        var srcPixel: vec4<f32> = textureLoad(inputTexture, texelCoord, 0);
+        if (params.prefilter != 0u) {
+            srcPixel = textureLoad(smoothTexture, texelCoord, 0);
         }
 
-        return vec4<f32>(closestColor, 1.0);
+        return vec4<f32>(nearest_color(srcPixel.rgb, 0u), 1.0);
     } else if (params.shader_index == 3u) { 
         
         // *******************************************
@@ -177,37 +538,8 @@ fn frag_main(@location(0) texcoord: vec2<f32>) -> @location(0) vec4<f32> {
 
 
 
-        var sigma2 = params.sigma1 / 16.0;
-        var radius1 = params.sigma1 * 3.0;
-        var radius2 = sigma2 * 2.0;
-
-        var textureSize: vec2<f32> = vec2<f32>(textureDimensions(inputTexture));
-
-        // ----- Gaussian Blur 1 -----
-        var blurredImage1: vec4<f32> = vec4<f32>(0.0, 0.0, 0.0, 0.0); 
-        var sum1: f32 = 0.0;
-
-        var kernelSize1: i32 = i32(ceil(radius1) * 2.0 + 1.0); 
-        for (var offsetX : i32 = -kernelSize1 / 2; offsetX <= kernelSize1 / 2; offsetX++) {
-            var samplePos: vec2<f32> = texcoord + vec2<f32>(f32(offsetX) / textureSize.x, 0.0);
-            var weight: f32 = exp(-(f32(offsetX) * f32(offsetX)) / (2.0 * params.sigma1 * params.sigma1)) / (sqrt(2.0 * 3.14159) * params.sigma1);
-            blurredImage1 += textureSample(inputTexture, sampler0, samplePos) * weight;
-            sum1 += weight;
-        }
-        blurredImage1 /= sum1;
-
-        // ----- Gaussian Blur 2 -----
-        var blurredImage2: vec4<f32> = vec4<f32>(0.0, 0.0, 0.0, 0.0); 
-        var sum2: f32 = 0.0;
-
-        var kernelSize2: i32 = i32(ceil(radius2) * 2.0 + 1.0); 
-        for (var offsetX : i32 = -kernelSize2 / 2; offsetX <= kernelSize2 / 2; offsetX++) {
-            var samplePos: vec2<f32> = texcoord + vec2<f32>(f32(offsetX) / textureSize.x, 0.0);
-            var weight: f32 = exp(-(f32(offsetX) * f32(offsetX)) / (2.0 * sigma2 * sigma2)) / (sqrt(2.0 * 3.14159) * sigma2);
-            blurredImage2 += textureSample(inputTexture, sampler0, samplePos) * weight;
-            sum2 += weight;
-        }
-        blurredImage2 /= sum2;
+        var blurredImage1: vec4<f32> = textureSample(blurTexture1, sampler0, texcoord);
+        var blurredImage2: vec4<f32> = textureSample(blurTexture2, sampler0, texcoord);
 
         var difference = blurredImage1.r - blurredImage2.r; 
 
@@ -230,10 +562,6 @@ fn frag_main(@location(0) texcoord: vec2<f32>) -> @location(0) vec4<f32> {
         var color = textureSample(inputTexture, sampler0, texcoord);
         var original_color = color;
 
-        var sigma2 = params.sigma1 / 16.0;
-        var radius1 = params.sigma1 * 3.0;
-        var radius2 = sigma2 * 2.0;
-
         if (params.enable_xdog == 1u) {
             // Gradient calculation using Sobel operators
             var sobel_x = array<vec3<f32>, 3>(
@@ -277,50 +605,8 @@ fn frag_main(@location(0) texcoord: vec2<f32>) -> @location(0) vec4<f32> {
                 v = v_temp / norm_factor;
             }
 
-            // Gaussian Blur Implementation
-            var kernelSize1 : i32 = i32(ceil(radius1) * 2.0 + 1.0); 
-            var kernelSize2 : i32 = i32(ceil(radius2) * 2.0 + 1.0);
-            var blurredImage1 = color; 
-            var blurredImage2 = color; 
-            var total_weight: f32 = 0.0;
-
-            // Blur 1 Horizontal Pass
-            for (var offsetX : i32 = -kernelSize1 / 2; offsetX <= kernelSize1 / 2; offsetX++) {
-                let samplePos: vec2<f32> = texcoord + vec2<f32>(f32(offsetX) / f32(textureDimensions(inputTexture).x), 0.0);
-                let weight: f32 = exp(-(f32(offsetX) * f32(offsetX)) / (2.0 * params.sigma1 * params.sigma1)) / (sqrt(2.0 * 3.14159) * params.sigma1);
-                blurredImage1 += textureSample(inputTexture, sampler0, samplePos) * weight;
-                total_weight += weight; // Keep track of total weight
-            }
-
-
-            // Blur 1 Vertical Pass
-            for (var offsetY : i32 = -kernelSize1 / 2; offsetY <= kernelSize1 / 2; offsetY++) {
-                let samplePos: vec2<f32> = texcoord + vec2<f32>(0.0, f32(offsetY) / f32(textureDimensions(inputTexture).y));
-                let weight: f32 = exp(-(f32(offsetY) * f32(offsetY)) / (2.0 * params.sigma1 * params.sigma1)) / (sqrt(2.0 * 3.14159) * params.sigma1);
-                blurredImage1 += textureSample(inputTexture, sampler0, samplePos) * weight;
-                total_weight += weight; // Keep track of total weight
-            }
-
-
-            // Blur 2 Horizontal Pass
-            for (var offsetX : i32 = -kernelSize2 / 2; offsetX <= kernelSize2 / 2; offsetX++) {
-                let samplePos: vec2<f32> = texcoord + vec2<f32>(f32(offsetX) / f32(textureDimensions(inputTexture).x), 0.0);
-                let weight: f32 = exp(-(f32(offsetX) * f32(offsetX)) / (2.0 * sigma2 * sigma2)) / (sqrt(2.0 * 3.14159) * sigma2);
-                blurredImage2 += textureSample(inputTexture, sampler0, samplePos) * weight;
-                total_weight += weight; // Keep track of total weight
-            }
-
-
-            // Blur 2 Vertical Pass
-            for (var offsetY : i32 = -kernelSize2 / 2; offsetY <= kernelSize2 / 2; offsetY++) {
-                let samplePos: vec2<f32> = texcoord + vec2<f32>(0.0, f32(offsetY) / f32(textureDimensions(inputTexture).y));
-                let weight: f32 = exp(-(f32(offsetY) * f32(offsetY)) / (2.0 * sigma2 * sigma2)) / (sqrt(2.0 * 3.14159) * sigma2);
-                blurredImage2 += textureSample(inputTexture, sampler0, samplePos) * weight;
-                total_weight += weight; // Keep track of total weight
-            }
-
-            blurredImage1 /= total_weight;
-            blurredImage2 /= total_weight;
+            var blurredImage1 = textureSample(blurTexture1, sampler0, texcoord);
+            var blurredImage2 = textureSample(blurTexture2, sampler0, texcoord);
 
             var xdog_difference = blurredImage2.r - blurredImage1.r; // Assumes only using red channel
 
@@ -401,13 +687,6 @@ fn frag_main(@location(0) texcoord: vec2<f32>) -> @location(0) vec4<f32> {
         //          bayer dithering
 
 
-        var ditherMatrix = array<vec4<f32>, 4>(
-            vec4<f32>( 1.0/16.0,  9.0/16.0,  3.0/16.0, 11.0/16.0),
-            vec4<f32>(13.0/16.0,  5.0/16.0, 15.0/16.0,  7.0/16.0),
-            vec4<f32>( 4.0/16.0, 12.0/16.0,  2.0/16.0, 10.0/16.0),
-            vec4<f32>(16.0/16.0,  8.0/16.0, 14.0/16.0,  6.0/16.0) 
-        );
-
         let td = textureDimensions(inputTexture);
         var color = textureSample(inputTexture, sampler0, texcoord);
 
@@ -429,7 +708,7 @@ fn frag_main(@location(0) texcoord: vec2<f32>) -> @location(0) vec4<f32> {
         // var matrix_coords = coords * 4.0;
         // var fcoords = vec2<i32>(floor(matrix_coords));
 
-        var threshold = ditherMatrix[i32(y2)][i32(x2)];
+        var threshold = bayer(vec2<u32>(u32(x2), u32(y2)), 2u);
         var out = color * threshold;
 
         var luminance = (color.rgb * vec3<f32>(0.2126, 0.7152, 0.0722));
@@ -479,15 +758,200 @@ fn frag_main(@location(0) texcoord: vec2<f32>) -> @location(0) vec4<f32> {
     
     
     } else if (params.shader_index == 8u) {
-        let offset_strength = params.intensity;
-
-        // Sample each color channel with an arbitrary shift
-        return vec4<f32>(
-            textureSample(inputTexture, sampler0, texcoord + vec2<f32>(offset_strength, -offset_strength)).r,
-            textureSample(inputTexture, sampler0, texcoord + vec2<f32>(-offset_strength, 0.0)).g,
-            textureSample(inputTexture, sampler0, texcoord + vec2<f32>(0.0, offset_strength)).b,
-            1.0
-        );
+
+
+        // *******************************************
+        //       lens distortion and chromatic aberration
+
+
+        let size = image_size();
+        let center = vec2<f32>(params.lens_center_x, params.lens_center_y) * size;
+        // radii are measured in half diagonals of the image
+        let radius = length(size) / 2.0;
+        let offset = (image_position(texcoord) - center) / radius;
+
+        // brown-conrady radial distortion, positive k1 bends straight lines into a barrel, negative into a pincushion
+        let r2 = dot(offset, offset);
+        let undistorted = offset * (1.0 + params.distortion_k1 * r2 + params.distortion_k2 * r2 * r2);
+
+        // every wavelength is magnified a little differently, red the most, so fringes grow towards the edges
+        let fringe = params.intensity * pow(length(undistorted), params.aberration_falloff);
+        let samples = max(params.spectral_samples, 3u);
+        var color = vec3<f32>(0.0);
+        var weights = vec3<f32>(0.0);
+        var alpha = 0.0;
+        for (var i = 0u; i < samples; i++) {
+            let wavelength = 400.0 + 300.0 * (f32(i) + 0.5) / f32(samples);
+            let position = center + undistorted * (1.0 + fringe * (wavelength - 550.0) / 150.0) * radius;
+            let inside = all(position >= vec2<f32>(0.0)) && all(position <= size);
+            let texel = select(vec4<f32>(0.0), sample_image(position), inside);
+            let weight = spectrum(wavelength);
+            color += texel.rgb * weight;
+            weights += weight;
+            alpha += texel.a;
+        }
+        return vec4<f32>(color / weights, alpha / f32(samples));
+    } else if (params.shader_index == 9u) {
+
+
+        // *******************************************
+        //            3D LUT color grading
+
+
+        let color = textureSample(inputTexture, sampler0, texcoord);
+
+        // display encoded input, which is what luts are authored against
+        let size = f32(textureDimensions(lutTexture).x);
+        let encoded = clamp(color.rgb, vec3<f32>(0.0), vec3<f32>(1.0));
+        let lutCoord = encoded * ((size - 1.0) / size) + 0.5 / size;
+        let graded = textureSampleLevel(lutTexture, lutSampler, lutCoord, 0.0).rgb;
+
+        return vec4<f32>(mix(color.rgb, graded, params.lut_intensity), color.a);
+    } else if (params.shader_index == 10u) {
+
+
+        // *******************************************
+        //          canny edge detection
+
+
+        let size = vec2<i32>(textureDimensions(cannyTexture));
+        let texelCoord = min(vec2<i32>(texcoord * vec2<f32>(size)), size - 1);
+
+        // weak pixels left over after hysteresis are not edges
+        let edge = select(0.0, 1.0, textureLoad(cannyTexture, texelCoord, 0).r >= 1.0);
+
+        if (params.canny_overlay == 1u) {
+            let color = textureSample(inputTexture, sampler0, texcoord);
+            return vec4<f32>(mix(color.rgb, vec3<f32>(0.0), edge), color.a);
+        }
+
+        return vec4<f32>(edge, edge, edge, 1.0);
+    } else if (params.shader_index == 11u || params.shader_index == 12u) {
+
+
+        // *******************************************
+        //      bilateral filter / guided filter
+
+
+        return textureSample(smoothTexture, sampler0, texcoord);
+    } else if (params.shader_index == 13u) {
+
+
+        // *******************************************
+        //                tone mapping
+
+
+        let color = textureSample(inputTexture, sampler0, texcoord);
+
+        var exposure = exp2(params.tone_exposure);
+        if (params.auto_exposure != 0u) {
+            // bring the average luminance to middle gray
+            let average = textureLoad(exposureTexture, vec2<i32>(0, 0), 0).r;
+            exposure *= 0.18 / max(average, 1e-3);
+        }
+
+        // contrast pivots around middle gray
+        let exposed = max(color.rgb * exposure, vec3<f32>(0.0));
+        let contrasted = 0.18 * pow(exposed / 0.18, vec3<f32>(params.contrast));
+        let white = max(params.white_point, 1e-3);
+
+        return vec4<f32>(clamp(tone_map(contrasted, white), vec3<f32>(0.0), vec3<f32>(1.0)), color.a);
+    } else if (params.shader_index == 16u) {
+
+
+        // *******************************************
+        //                 halftone
+
+
+        let position = image_position(texcoord);
+        let alpha = textureSample(inputTexture, sampler0, texcoord).a;
+
+        if (params.halftone_mode == 1u) {
+            // am, one black screen of dots sized by luminance
+            return vec4<f32>(vec3<f32>(1.0 - screen(position, params.screen_black, 4u)), alpha);
+        } else if (params.halftone_mode == 2u) {
+            // fm, dots of a fixed size spread by a bayer screen, 8x8 of them span an am cell
+            let size = max(params.halftone_cell / 8.0, 1.0);
+            let dot_position = floor(position / size);
+            let color = sample_image((dot_position + 0.5) * size).rgb;
+            let threshold = bayer(vec2<u32>(vec2<i32>(dot_position) & vec2<i32>(7)), 3u);
+            return vec4<f32>(vec3<f32>(select(1.0, 0.0, ink_coverage(color, 4u) >= threshold)), alpha);
+        }
+
+        // cmyk, each ink screened at its own angle and printed over the others on white paper
+        let c = screen(position, params.screen_cyan, 0u);
+        let m = screen(position, params.screen_magenta, 1u);
+        let y = screen(position, params.screen_yellow, 2u);
+        let k = screen(position, params.screen_black, 3u);
+        return vec4<f32>((1.0 - vec3<f32>(c, m, y)) * (1.0 - k), alpha);
+    } else if (params.shader_index == 17u) {
+
+
+        // *******************************************
+        //                    crt
+
+
+        let size = image_size();
+        let position = image_position(texcoord);
+
+        // bulged out from the middle of the screen, where it meets the bezel the picture ends
+        let screen = position / size * 2.0 - 1.0;
+        let curved = screen * (1.0 + params.crt_curvature * screen.yx * screen.yx);
+        let inside = clamp((1.0 - max(abs(curved.x), abs(curved.y))) * min(size.x, size.y) / 2.0, 0.0, 1.0);
+        if (inside <= 0.0) {
+            return vec4<f32>(0.0, 0.0, 0.0, 1.0);
+        }
+        let source = (curved + 1.0) / 2.0 * size;
+
+        // an interlaced picture has twice the lines, the field drawn before this one already fading
+        let pixel = max(params.scanline_size, 1.0);
+        let pitch = vec2<f32>(pixel, select(pixel, pixel / 2.0, params.crt_interlace != 0u));
+        let line = source.y / pitch.y - 0.5;
+        let above = floor(line);
+        let field = select(vec2<f32>(1.0), select(vec2<f32>(1.0, 0.6), vec2<f32>(0.6, 1.0), abs(above % 2.0) == 1.0), params.crt_interlace != 0u);
+        let upper = crt_line(above, source.x, pitch) * field.x;
+        let lower = crt_line(above + 1.0, source.x, pitch) * field.y;
+
+        let flat = mix(upper, lower, line - above);
+        let beams = crt_beam(upper, line - above) + crt_beam(lower, above + 1.0 - line);
+        var color = mix(flat, beams, params.scanline_strength) * crt_mask(position);
+
+        // light scattered in the glass
+        let origin = vec2<f32>(f32(params.tile_x), f32(params.tile_y));
+        color += textureSampleLevel(blurTexture1, sampler0, (source - origin) / vec2<f32>(textureDimensions(inputTexture)), 0.0).rgb * params.crt_bloom;
+
+        let uv = source / size;
+        let vignette = pow(clamp(16.0 * uv.x * uv.y * (1.0 - uv.x) * (1.0 - uv.y), 0.0, 1.0), params.crt_vignette);
+        return vec4<f32>(clamp(color * vignette * inside, vec3<f32>(0.0), vec3<f32>(1.0)), 1.0);
+    } else if (params.shader_index == 18u) {
+
+
+        // *******************************************
+        //                 film grain
+
+
+        let color = textureSample(inputTexture, sampler0, texcoord);
+
+        // clumps about grain_size pixels across, lattice points on pixel centers
+        let position = image_position(texcoord) / max(params.grain_size, 0.1) - 0.5;
+        let n = value_noise(position, params.grain_seed) * 2.0 - 1.0;
+        let grain = mix(vec3<f32>(n.x), n, params.grain_chroma);
+
+        // grain shows most in the midtones, where the crystals of a negative are half exposed
+        let luminance = dot(color.rgb, vec3<f32>(0.2126, 0.7152, 0.0722));
+        let response = pow(clamp(4.0 * luminance * (1.0 - luminance), 0.0, 1.0), params.grain_response);
+
+        return vec4<f32>(clamp(color.rgb + grain * params.grain_intensity * response, vec3<f32>(0.0), vec3<f32>(1.0)), color.a);
+    } else if (params.shader_index == 19u) {
+
+
+        // *******************************************
+        //                   noise
+
+
+        let color = textureSample(inputTexture, sampler0, texcoord);
+        let n = noise(image_position(texcoord) / max(params.noise_scale, 0.1), params.noise_type, params.noise_seed);
+        return vec4<f32>(clamp(color.rgb + (n - 0.5) * params.noise_intensity, vec3<f32>(0.0), vec3<f32>(1.0)), color.a);
     }
     else {
         return textureSample(inputTexture, sampler0, texcoord);
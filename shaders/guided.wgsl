@@ -0,0 +1,55 @@
+@group(0) @binding(0) var texture0: texture_2d<f32>;
+@group(0) @binding(1) var texture1: texture_2d<f32>;
+@group(0) @binding(2) var texture2: texture_2d<f32>;
+@group(0) @binding(3) var<uniform> guided: GuidedParameters;
+
+// self guided filter, run on each color channel independently.
+// the box means between these passes come from the separable blur
+struct GuidedParameters {
+    epsilon: f32,
+}
+
+struct Coefficients {
+    @location(0) a: vec4<f32>,
+    @location(1) b: vec4<f32>,
+};
+
+// one triangle covering the whole target, fragments read texels by position
+@vertex
+fn vert_main(@builtin(vertex_index) index: u32) -> @builtin(position) vec4<f32> {
+    let uv = vec2<f32>(f32((index << 1u) & 2u), f32(index & 2u));
+    return vec4<f32>(uv.x * 2.0 - 1.0, 1.0 - uv.y * 2.0, 0.0, 1.0);
+}
+
+// texture0: image
+@fragment
+fn square_main(@builtin(position) position: vec4<f32>) -> @location(0) vec4<f32> {
+    let value = textureLoad(texture0, vec2<i32>(position.xy), 0);
+    return value * value;
+}
+
+// texture0: mean of the image, texture1: mean of the squared image
+@fragment
+fn coefficients_main(@builtin(position) position: vec4<f32>) -> Coefficients {
+    let texelCoord = vec2<i32>(position.xy);
+    let mean = textureLoad(texture0, texelCoord, 0);
+    let meanSquared = textureLoad(texture1, texelCoord, 0);
+
+    let variance = max(meanSquared - mean * mean, vec4<f32>(0.0));
+
+    var out: Coefficients;
+    out.a = variance / (variance + guided.epsilon);
+    out.b = mean - out.a * mean;
+    return out;
+}
+
+// texture0: image, texture1: mean of a, texture2: mean of b
+@fragment
+fn combine_main(@builtin(position) position: vec4<f32>) -> @location(0) vec4<f32> {
+    let texelCoord = vec2<i32>(position.xy);
+    let value = textureLoad(texture0, texelCoord, 0);
+    let a = textureLoad(texture1, texelCoord, 0);
+    let b = textureLoad(texture2, texelCoord, 0);
+
+    return vec4<f32>(a.rgb * value.rgb + b.rgb, value.a);
+}
@@ -0,0 +1,29 @@
+@group(0) @binding(0) var sourceTexture: texture_2d<f32>;
+@group(0) @binding(1) var sourceSampler: sampler;
+
+// one triangle covering the whole target, fragments read texels by position
+@vertex
+fn vert_main(@builtin(vertex_index) index: u32) -> @builtin(position) vec4<f32> {
+    let uv = vec2<f32>(f32((index << 1u) & 2u), f32(index & 2u));
+    return vec4<f32>(uv.x * 2.0 - 1.0, 1.0 - uv.y * 2.0, 0.0, 1.0);
+}
+
+// the next level from the one above it. sampling between the four texels a target
+// texel covers averages them, a box filter. for odd sizes it is only close to one
+@fragment
+fn frag_main(@builtin(position) position: vec4<f32>) -> @location(0) vec4<f32> {
+    let size = max(textureDimensions(sourceTexture) / 2u, vec2<u32>(1u));
+    return textureSampleLevel(sourceTexture, sourceSampler, position.xy / vec2<f32>(size), 0.0);
+}
+
+// the same for formats that can't be filtered, the four texels averaged by hand
+@fragment
+fn load_main(@builtin(position) position: vec4<f32>) -> @location(0) vec4<f32> {
+    let last = vec2<i32>(textureDimensions(sourceTexture)) - 1;
+    let texel = vec2<i32>(position.xy) * 2;
+    var sum = vec4<f32>(0.0);
+    for (var i = 0; i < 4; i++) {
+        sum += textureLoad(sourceTexture, min(texel + vec2<i32>(i & 1, i >> 1), last), 0);
+    }
+    return sum / 4.0;
+}
@@ -0,0 +1,86 @@
+// the uniform every effect reads, mirrors Parameters in scene.rs. prepended to the shaders that use it,
+// see PARAMETERS_WGSL in scene.rs
+struct Parameters {
+    sigma1: f32,
+    tau: f32,
+    gfact: f32,
+    epsilon: f32,
+    num_gvf_iterations: i32,
+    enable_xdog: u32,
+    shader_index: u32,
+    colors: f32,
+    intensity: f32,
+    lut_intensity: f32,
+    low_threshold: f32,
+    high_threshold: f32,
+    canny_overlay: u32,
+    sigma_space: f32,
+    sigma_range: f32,
+    guided_radius: i32,
+    guided_epsilon: f32,
+    prefilter: u32,
+    blend_mode: u32,
+    opacity: f32,
+    tone_operator: u32,
+    tone_exposure: f32,
+    white_point: f32,
+    contrast: f32,
+    auto_exposure: u32,
+    sort_angle: f32,
+    sort_span: u32,
+    sort_low: f32,
+    sort_high: f32,
+    sort_key: u32,
+    sort_descending: u32,
+    glyph_cell: u32,
+    glyph_count: u32,
+    glyph_color: u32,
+    glyph_edges: u32,
+    glyph_edge_threshold: f32,
+    tile_x: u32,
+    tile_y: u32,
+    halftone_mode: u32,
+    dot_shape: u32,
+    halftone_cell: f32,
+    screen_cyan: f32,
+    screen_magenta: f32,
+    screen_yellow: f32,
+    screen_black: f32,
+    dot_gain: f32,
+    image_width: u32,
+    image_height: u32,
+    crt_mask: u32,
+    crt_palette: u32,
+    crt_interlace: u32,
+    crt_curvature: f32,
+    scanline_size: f32,
+    scanline_strength: f32,
+    mask_strength: f32,
+    crt_bloom: f32,
+    crt_vignette: f32,
+    lens_center_x: f32,
+    lens_center_y: f32,
+    aberration_falloff: f32,
+    distortion_k1: f32,
+    distortion_k2: f32,
+    spectral_samples: u32,
+    frame: u32,
+    grain_seed: u32,
+    grain_intensity: f32,
+    grain_size: f32,
+    grain_chroma: f32,
+    grain_response: f32,
+    noise_type: u32,
+    noise_seed: u32,
+    noise_scale: f32,
+    noise_intensity: f32,
+    stroke_width: u32,
+    stroke_layers: u32,
+    stroke_length: f32,
+    stroke_detail: f32,
+    stroke_texture: u32,
+    superpixel_count: u32,
+    superpixel_compactness: f32,
+    superpixel_iterations: u32,
+    superpixel_boundaries: u32,
+}
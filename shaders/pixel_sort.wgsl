@@ -0,0 +1,201 @@
+@group(0) @binding(0) var inputTexture: texture_2d<f32>;
+// what the rounds before this one wrote, the lines they sorted
+@group(0) @binding(1) var previousTexture: texture_2d<f32>;
+@group(0) @binding(2) var outputTexture: texture_storage_2d<rgba16float, write>;
+@group(0) @binding(3) var<uniform> params: Parameters;
+@group(0) @binding(4) var<uniform> dispatch: Dispatch;
+// two words per position of every line of the round, padded to a power of two:
+// the span start above the 16 bit sort key, then where the element started on the line
+@group(0) @binding(5) var<storage, read_write> elements: array<u32>;
+
+struct Dispatch {
+    pass_index: u32,
+    iteration: u32,
+    iterations: u32,
+    round: u32,
+}
+
+// the lines pixels are sorted along, see Lines in pixel_sort.rs
+struct Lines {
+    horizontal: bool,
+    reversed: bool,
+    slope: f32,
+    offset: i32,
+    length: u32,
+    padded: u32,
+    count: u32,
+}
+
+fn offset_at(lines: Lines, along: u32) -> i32 {
+    return i32(floor(f32(along) * lines.slope + 0.5));
+}
+
+fn lines_for(size: vec2<u32>) -> Lines {
+    let angle = radians(params.sort_angle);
+    let s = sin(angle);
+    let c = cos(angle);
+
+    var lines: Lines;
+    var across: u32;
+    lines.horizontal = abs(c) >= abs(s);
+    if (lines.horizontal) {
+        lines.length = size.x;
+        across = size.y;
+        lines.slope = s / c;
+        lines.reversed = c < 0.0;
+    } else {
+        lines.length = size.y;
+        across = size.x;
+        lines.slope = c / s;
+        lines.reversed = s < 0.0;
+    }
+    lines.padded = select(1u << (32u - countLeadingZeros(lines.length - 1u)), 1u, lines.length <= 1u);
+
+    let last = offset_at(lines, lines.length - 1u);
+    lines.offset = max(last, 0);
+    lines.count = across + u32(max(last, 0) - min(last, 0));
+    return lines;
+}
+
+// lines the storage buffer holds, every round sorts that many lines after the last round's
+fn lines_stored(lines: Lines) -> u32 {
+    return min(lines.count, arrayLength(&elements) / (lines.padded * 2u));
+}
+
+// the line at index in the storage buffer, count or more past the last line
+fn round_line(lines: Lines, index: u32) -> u32 {
+    return select(lines.count, dispatch.round * lines_stored(lines) + index, index < lines_stored(lines));
+}
+
+// the pixel at position t < length of line, which may lie outside the image
+fn line_pixel(lines: Lines, line: u32, t: u32) -> vec2<i32> {
+    let along = select(t, lines.length - 1u - t, lines.reversed);
+    let across = i32(line) + offset_at(lines, along) - lines.offset;
+    return select(vec2<i32>(across, i32(along)), vec2<i32>(i32(along), across), lines.horizontal);
+}
+
+// luminance, hue as a fraction of the circle or saturation, see SortKey
+fn channel(color: vec3<f32>, key: u32) -> f32 {
+    let high = max(color.r, max(color.g, color.b));
+    let low = min(color.r, min(color.g, color.b));
+    let chroma = high - low;
+    switch key {
+        case 1u: {
+            var sector = 0.0;
+            if (chroma < 1e-5) {
+                sector = 0.0;
+            } else if (high == color.r) {
+                let x = (color.g - color.b) / chroma;
+                sector = x - 6.0 * floor(x / 6.0);
+            } else if (high == color.g) {
+                sector = (color.b - color.r) / chroma + 2.0;
+            } else {
+                sector = (color.r - color.g) / chroma + 4.0;
+            }
+            return sector / 6.0;
+        }
+        case 2u: {
+            return select(0.0, chroma / high, high > 0.0);
+        }
+        default: {
+            return 0.2126 * color.r + 0.7152 * color.g + 0.0722 * color.b;
+        }
+    }
+}
+
+// one invocation per line: the start of the span every position belongs to and its key.
+// positions outside a span, or outside the image, start a span of their own
+@compute @workgroup_size(64, 1)
+fn spans_main(@builtin(global_invocation_id) id: vec3<u32>) {
+    let size = textureDimensions(inputTexture);
+    let lines = lines_for(size);
+    let line = round_line(lines, id.x);
+    if (line >= lines.count) {
+        return;
+    }
+
+    var start = 0u;
+    var previous = false;
+    for (var t = 0u; t < lines.padded; t++) {
+        var in_span = false;
+        var key = 0u;
+        if (t < lines.length) {
+            let pixel = line_pixel(lines, line, t);
+            if (all(pixel >= vec2<i32>(0)) && all(pixel < vec2<i32>(size))) {
+                let color = textureLoad(inputTexture, pixel, 0).rgb;
+                let value = channel(color, params.sort_span);
+                in_span = params.sort_low <= value && value <= params.sort_high;
+                key = u32(floor(clamp(channel(color, params.sort_key), 0.0, 1.0) * 65535.0 + 0.5));
+                if (params.sort_descending != 0u) {
+                    key = 65535u - key;
+                }
+            }
+        }
+        if (!(in_span && previous)) {
+            start = t;
+        }
+        previous = in_span;
+
+        let index = (id.x * lines.padded + t) * 2u;
+        elements[index] = (start << 16u) | select(0u, key, in_span);
+        elements[index + 1u] = t;
+    }
+}
+
+// one compare and swap of the bitonic network per invocation, padded / 2 of them per line.
+// the iteration walks the network: merges of size k = 2, 4, .. padded, each in steps j = k / 2 .. 1
+@compute @workgroup_size(64, 1)
+fn sort_main(@builtin(global_invocation_id) id: vec3<u32>) {
+    let lines = lines_for(textureDimensions(inputTexture));
+    if (round_line(lines, id.y) >= lines.count || id.x >= lines.padded / 2u) {
+        return;
+    }
+
+    var step = dispatch.iteration;
+    var stage = 1u;
+    while (step >= stage) {
+        step -= stage;
+        stage++;
+    }
+    let k = 1u << stage;
+    let j = 1u << (stage - 1u - step);
+
+    let low = id.x & (j - 1u);
+    let i = (id.x - low) * 2u + low;
+    let a = (id.y * lines.padded + i) * 2u;
+    let b = a + j * 2u;
+    let ascending = (i & k) == 0u;
+    let ordered = elements[a] < elements[b] || (elements[a] == elements[b] && elements[a + 1u] < elements[b + 1u]);
+    if (ordered != ascending) {
+        let key = elements[a];
+        let t = elements[a + 1u];
+        elements[a] = elements[b];
+        elements[a + 1u] = elements[b + 1u];
+        elements[b] = key;
+        elements[b + 1u] = t;
+    }
+}
+
+// every pixel on a line of this round takes the one sorted into its place,
+// the rest keep what the rounds before wrote
+@compute @workgroup_size(8, 8)
+fn write_main(@builtin(global_invocation_id) id: vec3<u32>) {
+    let size = textureDimensions(inputTexture);
+    if (id.x >= size.x || id.y >= size.y) {
+        return;
+    }
+    let lines = lines_for(size);
+
+    let along = select(id.y, id.x, lines.horizontal);
+    let across = i32(select(id.x, id.y, lines.horizontal));
+    let t = select(along, lines.length - 1u - along, lines.reversed);
+    let line = u32(across - offset_at(lines, along) + lines.offset);
+
+    let index = line - dispatch.round * lines_stored(lines);
+    if (line < dispatch.round * lines_stored(lines) || index >= lines_stored(lines)) {
+        textureStore(outputTexture, id.xy, textureLoad(previousTexture, id.xy, 0));
+        return;
+    }
+    let source = line_pixel(lines, line, elements[(index * lines.padded + t) * 2u + 1u]);
+    textureStore(outputTexture, id.xy, textureLoad(inputTexture, source, 0));
+}
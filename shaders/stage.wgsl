@@ -0,0 +1,64 @@
+@group(0) @binding(0) var sourceTexture: texture_2d<f32>;
+@group(0) @binding(1) var effectTexture: texture_2d<f32>;
+// feathered mask, 1.0 where the effect shows
+@group(0) @binding(2) var maskTexture: texture_2d<f32>;
+@group(0) @binding(3) var<uniform> color: ColorParameters;
+
+// color spaces, see ColorSpace::get_index
+const LINEAR: u32 = 0u;
+const DISPLAY: u32 = 1u;
+
+struct ColorParameters {
+    source_space: u32,
+    effect_space: u32,
+    working_space: u32,
+    masked: u32,
+}
+
+fn srgb_to_linear(c: vec3<f32>) -> vec3<f32> {
+    return select(pow((max(c, vec3<f32>(0.0)) + 0.055) / 1.055, vec3<f32>(2.4)), c / 12.92, c <= vec3<f32>(0.04045));
+}
+
+fn linear_to_srgb(c: vec3<f32>) -> vec3<f32> {
+    return select(1.055 * pow(max(c, vec3<f32>(0.0)), vec3<f32>(1.0 / 2.4)) - 0.055, c * 12.92, c <= vec3<f32>(0.0031308));
+}
+
+fn convert(value: vec4<f32>, source_space: u32, target_space: u32) -> vec4<f32> {
+    if (source_space == target_space) {
+        return value;
+    }
+    if (target_space == LINEAR) {
+        return vec4<f32>(srgb_to_linear(value.rgb), value.a);
+    }
+    return vec4<f32>(linear_to_srgb(value.rgb), value.a);
+}
+
+// one triangle covering the whole target, fragments read texels by position
+@vertex
+fn vert_main(@builtin(vertex_index) index: u32) -> @builtin(position) vec4<f32> {
+    let uv = vec2<f32>(f32((index << 1u) & 2u), f32(index & 2u));
+    return vec4<f32>(uv.x * 2.0 - 1.0, 1.0 - uv.y * 2.0, 0.0, 1.0);
+}
+
+// source into the space the effect expects
+@fragment
+fn input_main(@builtin(position) position: vec4<f32>) -> @location(0) vec4<f32> {
+    let source = textureLoad(sourceTexture, vec2<i32>(position.xy), 0);
+    return convert(source, color.source_space, color.effect_space);
+}
+
+// effect back into the working space, gated by the mask
+@fragment
+fn output_main(@builtin(position) position: vec4<f32>) -> @location(0) vec4<f32> {
+    let texelCoord = vec2<i32>(position.xy);
+    let effect = convert(textureLoad(effectTexture, texelCoord, 0), color.effect_space, color.working_space);
+
+    if (color.masked == 0u) {
+        return effect;
+    }
+
+    let source = convert(textureLoad(sourceTexture, texelCoord, 0), color.source_space, color.working_space);
+    let mask = textureLoad(maskTexture, texelCoord, 0).r;
+
+    return mix(source, effect, mask);
+}
@@ -0,0 +1,50 @@
+@group(0) @binding(0) var baseTexture: texture_2d<f32>;
+@group(0) @binding(1) var effectTexture: texture_2d<f32>;
+@group(0) @binding(2) var textureSampler: sampler;
+@group(0) @binding(3) var<uniform> stitch: StitchParameters;
+
+// where one tile's core lands in the display textures.
+// the pass is drawn with its viewport set to destination
+struct StitchParameters {
+    // x, y, width, height in display pixels
+    destination: vec4<f32>,
+    // x, y, width, height in stage texels
+    core: vec4<f32>,
+}
+
+struct Targets {
+    @location(0) base: vec4<f32>,
+    @location(1) effect: vec4<f32>,
+}
+
+// one triangle covering the whole target, fragments read texels by position
+@vertex
+fn vert_main(@builtin(vertex_index) index: u32) -> @builtin(position) vec4<f32> {
+    let uv = vec2<f32>(f32((index << 1u) & 2u), f32(index & 2u));
+    return vec4<f32>(uv.x * 2.0 - 1.0, 1.0 - uv.y * 2.0, 0.0, 1.0);
+}
+
+// averages the stage texels under each display pixel, a box filter over the footprint
+// so downscaling large images doesn't alias
+@fragment
+fn frag_main(@builtin(position) position: vec4<f32>) -> Targets {
+    let size = vec2<f32>(textureDimensions(baseTexture));
+    let footprint = stitch.core.zw / stitch.destination.zw;
+    let corner = stitch.core.xy + (position.xy - vec2<f32>(0.5) - stitch.destination.xy) / stitch.destination.zw * stitch.core.zw;
+    let samples = vec2<u32>(clamp(ceil(footprint), vec2<f32>(1.0), vec2<f32>(8.0)));
+
+    var targets: Targets;
+    targets.base = vec4<f32>(0.0);
+    targets.effect = vec4<f32>(0.0);
+    for (var y = 0u; y < samples.y; y++) {
+        for (var x = 0u; x < samples.x; x++) {
+            let texel = corner + (vec2<f32>(f32(x), f32(y)) + 0.5) / vec2<f32>(samples) * footprint;
+            targets.base += textureSampleLevel(baseTexture, textureSampler, texel / size, 0.0);
+            targets.effect += textureSampleLevel(effectTexture, textureSampler, texel / size, 0.0);
+        }
+    }
+    let count = f32(samples.x * samples.y);
+    targets.base /= count;
+    targets.effect /= count;
+    return targets;
+}
@@ -0,0 +1,259 @@
+@group(0) @binding(0) var inputTexture: texture_2d<f32>;
+@group(0) @binding(2) var outputTexture: texture_storage_2d<rgba16float, write>;
+@group(0) @binding(3) var<uniform> params: Parameters;
+@group(0) @binding(4) var<uniform> dispatch: Dispatch;
+// a word per pixel of the tile, row by row: the index of the center of its region or NO_REGION.
+// then five words per center, row by row over the seeds: x and y in the tile, then its
+// average color in cielab, all as f32 bits
+@group(0) @binding(5) var<storage, read_write> regions: array<u32>;
+
+struct Dispatch {
+    pass_index: u32,
+    iteration: u32,
+    iterations: u32,
+    round: u32,
+}
+
+// mirror the limits in superpixels.rs
+const MIN_STEP: u32 = 4u;
+const MAX_REGIONS: u32 = 20000u;
+
+const NO_REGION: u32 = 0xffffffffu;
+
+struct Center {
+    position: vec2<f32>,
+    color: vec3<f32>,
+}
+
+// the spacing of seeds for the requested number of regions over the whole image,
+// mirrors step in superpixels.rs
+fn seed_step() -> u32 {
+    var size = vec2<f32>(textureDimensions(inputTexture));
+    if (params.image_width != 0u) {
+        size = vec2<f32>(f32(params.image_width), f32(params.image_height));
+    }
+    let area = size.x * size.y / f32(clamp(params.superpixel_count, 1u, MAX_REGIONS));
+    return max(u32(floor(sqrt(area) + 0.5)), MIN_STEP);
+}
+
+fn origin() -> vec2<u32> {
+    return vec2<u32>(params.tile_x, params.tile_y);
+}
+
+// the first seed over the tile, counted from the corner of the whole image
+fn first_cell() -> vec2<u32> {
+    return origin() / seed_step();
+}
+
+// columns and rows of seeds over the tile, mirrors grid in superpixels.rs
+fn grid() -> vec2<u32> {
+    let size = textureDimensions(inputTexture);
+    return (origin() + size - 1u) / seed_step() - first_cell() + 1u;
+}
+
+fn label_index(pixel: vec2<u32>) -> u32 {
+    return pixel.y * textureDimensions(inputTexture).x + pixel.x;
+}
+
+fn center_index(k: u32) -> u32 {
+    let size = textureDimensions(inputTexture);
+    return size.x * size.y + k * 5u;
+}
+
+// whether the buffer had room for a center
+fn center_stored(k: u32) -> bool {
+    return center_index(k) + 4u < arrayLength(&regions);
+}
+
+fn load_center(k: u32) -> Center {
+    let i = center_index(k);
+    return Center(
+        vec2<f32>(bitcast<f32>(regions[i]), bitcast<f32>(regions[i + 1u])),
+        vec3<f32>(bitcast<f32>(regions[i + 2u]), bitcast<f32>(regions[i + 3u]), bitcast<f32>(regions[i + 4u])),
+    );
+}
+
+fn store_center(k: u32, position: vec2<f32>, color: vec3<f32>) {
+    let i = center_index(k);
+    regions[i] = bitcast<u32>(position.x);
+    regions[i + 1u] = bitcast<u32>(position.y);
+    regions[i + 2u] = bitcast<u32>(color.x);
+    regions[i + 3u] = bitcast<u32>(color.y);
+    regions[i + 4u] = bitcast<u32>(color.z);
+}
+
+fn srgb_to_linear(color: vec3<f32>) -> vec3<f32> {
+    return select(pow((color + 0.055) / 1.055, vec3<f32>(2.4)), color / 12.92, color <= vec3<f32>(0.04045));
+}
+
+fn linear_to_srgb(color: vec3<f32>) -> vec3<f32> {
+    let c = max(color, vec3<f32>(0.0));
+    return select(1.055 * pow(c, vec3<f32>(1.0 / 2.4)) - 0.055, c * 12.92, c <= vec3<f32>(0.0031308));
+}
+
+// cielab of a display encoded color, under d65. distances in it roughly follow how different colors look
+fn rgb_to_lab(color: vec3<f32>) -> vec3<f32> {
+    let c = srgb_to_linear(clamp(color, vec3<f32>(0.0), vec3<f32>(1.0)));
+    let xyz = vec3<f32>(
+        dot(c, vec3<f32>(0.4124564, 0.3575761, 0.1804375)),
+        dot(c, vec3<f32>(0.2126729, 0.7151522, 0.0721750)),
+        dot(c, vec3<f32>(0.0193339, 0.1191920, 0.9503041)),
+    ) / vec3<f32>(0.95047, 1.0, 1.08883);
+    let f = select(7.787 * xyz + 16.0 / 116.0, pow(xyz, vec3<f32>(1.0 / 3.0)), xyz > vec3<f32>(0.008856));
+    return vec3<f32>(116.0 * f.y - 16.0, 500.0 * (f.x - f.y), 200.0 * (f.y - f.z));
+}
+
+fn lab_to_rgb(lab: vec3<f32>) -> vec3<f32> {
+    let fy = (lab.x + 16.0) / 116.0;
+    let f = vec3<f32>(fy + lab.y / 500.0, fy, fy - lab.z / 200.0);
+    let cubed = f * f * f;
+    let xyz = select((f - 16.0 / 116.0) / 7.787, cubed, cubed > vec3<f32>(0.008856)) * vec3<f32>(0.95047, 1.0, 1.08883);
+    let c = vec3<f32>(
+        dot(xyz, vec3<f32>(3.2404542, -1.5371385, -0.4985314)),
+        dot(xyz, vec3<f32>(-0.9692660, 1.8760108, 0.0415560)),
+        dot(xyz, vec3<f32>(0.0556434, -0.2040259, 1.0572252)),
+    );
+    return linear_to_srgb(c);
+}
+
+fn load_lab(pixel: vec2<i32>) -> vec3<f32> {
+    let clamped = clamp(pixel, vec2<i32>(0), vec2<i32>(textureDimensions(inputTexture)) - 1);
+    return rgb_to_lab(textureLoad(inputTexture, clamped, 0).rgb);
+}
+
+// one invocation per seed: the middle of its grid cell, moved to the flattest pixel around it
+// so that regions do not start out on an edge or on noise
+@compute @workgroup_size(8, 8)
+fn seed_main(@builtin(global_invocation_id) id: vec3<u32>) {
+    let cells = grid();
+    let k = id.y * cells.x + id.x;
+    if (any(id.xy >= cells) || !center_stored(k)) {
+        return;
+    }
+
+    let size = vec2<i32>(textureDimensions(inputTexture));
+    let step = seed_step();
+    let seed = clamp(vec2<i32>((first_cell() + id.xy) * step + step / 2u) - vec2<i32>(origin()), vec2<i32>(0), size - 1);
+    var best = seed;
+    var flattest = 1e30;
+    for (var y = -1; y <= 1; y++) {
+        for (var x = -1; x <= 1; x++) {
+            let pixel = clamp(seed + vec2<i32>(x, y), vec2<i32>(0), size - 1);
+            let dx = load_lab(pixel + vec2<i32>(1, 0)) - load_lab(pixel - vec2<i32>(1, 0));
+            let dy = load_lab(pixel + vec2<i32>(0, 1)) - load_lab(pixel - vec2<i32>(0, 1));
+            let gradient = dot(dx, dx) + dot(dy, dy);
+            if (gradient < flattest) {
+                flattest = gradient;
+                best = pixel;
+            }
+        }
+    }
+    store_center(k, vec2<f32>(best) + 0.5, load_lab(best));
+}
+
+// every pixel goes to the closest of the centers seeded in the grid cells around its own.
+// compactness weighs distance in seed steps against distance in color
+fn assign(pixel: vec2<u32>) {
+    let size = textureDimensions(inputTexture);
+    if (any(pixel >= size) || label_index(pixel) >= arrayLength(&regions)) {
+        return;
+    }
+
+    let cells = grid();
+    let cell = vec2<i32>((origin() + pixel) / seed_step() - first_cell());
+    let color = load_lab(vec2<i32>(pixel));
+    let position = vec2<f32>(pixel) + 0.5;
+    let weight = params.superpixel_compactness / f32(seed_step());
+
+    var label = NO_REGION;
+    var closest = 1e30;
+    for (var y = -1; y <= 1; y++) {
+        for (var x = -1; x <= 1; x++) {
+            let neighbour = cell + vec2<i32>(x, y);
+            if (any(neighbour < vec2<i32>(0)) || any(neighbour >= vec2<i32>(cells))) {
+                continue;
+            }
+            let k = u32(neighbour.y) * cells.x + u32(neighbour.x);
+            if (!center_stored(k)) {
+                continue;
+            }
+            let center = load_center(k);
+            let color_distance = center.color - color;
+            let distance = (center.position - position) * weight;
+            let total = dot(color_distance, color_distance) + dot(distance, distance);
+            if (total < closest) {
+                closest = total;
+                label = k;
+            }
+        }
+    }
+    regions[label_index(pixel)] = label;
+}
+
+// every center moves to the average position and color of its pixels, which can only lie
+// in the grid cells around its own. id counts centers row by row across the tile
+fn update(id: vec2<u32>) {
+    let size = textureDimensions(inputTexture);
+    let cells = grid();
+    let k = id.y * size.x + id.x;
+    if (k >= cells.x * cells.y || !center_stored(k)) {
+        return;
+    }
+
+    let step = seed_step();
+    let cell = first_cell() + vec2<u32>(k % cells.x, k / cells.x);
+    let start = max(vec2<i32>(cell * step) - i32(step) - vec2<i32>(origin()), vec2<i32>(0));
+    let end = min(vec2<i32>((cell + 2u) * step) - vec2<i32>(origin()), vec2<i32>(size));
+
+    var position = vec2<f32>(0.0);
+    var color = vec3<f32>(0.0);
+    var count = 0.0;
+    for (var y = start.y; y < end.y; y++) {
+        for (var x = start.x; x < end.x; x++) {
+            if (regions[label_index(vec2<u32>(vec2<i32>(x, y)))] == k) {
+                position += vec2<f32>(f32(x), f32(y)) + 0.5;
+                color += load_lab(vec2<i32>(x, y));
+                count += 1.0;
+            }
+        }
+    }
+    // a center that lost all its pixels stays where it was
+    if (count > 0.0) {
+        store_center(k, position / count, color / count);
+    }
+}
+
+// assigns on even iterations and updates on odd ones, see SUPERPIXELS
+@compute @workgroup_size(8, 8)
+fn iterate_main(@builtin(global_invocation_id) id: vec3<u32>) {
+    if (dispatch.iteration % 2u == 0u) {
+        assign(id.xy);
+    } else {
+        update(id.xy);
+    }
+}
+
+// every pixel takes the average color of its region, and where the region to the right
+// or below is another one, the outline
+@compute @workgroup_size(8, 8)
+fn draw_main(@builtin(global_invocation_id) id: vec3<u32>) {
+    let size = textureDimensions(inputTexture);
+    if (any(id.xy >= size)) {
+        return;
+    }
+    let label = select(NO_REGION, regions[label_index(id.xy)], label_index(id.xy) < arrayLength(&regions));
+    if (label == NO_REGION || !center_stored(label)) {
+        textureStore(outputTexture, id.xy, textureLoad(inputTexture, id.xy, 0));
+        return;
+    }
+
+    var color = lab_to_rgb(load_center(label).color);
+    if (params.superpixel_boundaries != 0u) {
+        let right = min(id.xy + vec2<u32>(1u, 0u), size - 1u);
+        let below = min(id.xy + vec2<u32>(0u, 1u), size - 1u);
+        if (regions[label_index(right)] != label || regions[label_index(below)] != label) {
+            color = vec3<f32>(0.0);
+        }
+    }
+    textureStore(outputTexture, id.xy, vec4<f32>(clamp(color, vec3<f32>(0.0), vec3<f32>(1.0)), 1.0));
+}
@@ -0,0 +1,180 @@
+use ab_glyph::{Font, FontArc, PxScale, ScaleFont};
+use iced_wgpu::wgpu;
+use crate::compute::{ComputeEffect, ComputePass, Domain};
+
+
+// drawn when no font file is picked
+const DEFAULT_FONT: &[u8] = include_bytes!("../fonts/FiraMono-Medium.ttf");
+
+pub const DEFAULT_RAMP: &str = " .:-=+*#%@";
+
+// glyphs a ramp can hold, the atlas has to fit in a texture at the widest cell
+const MAX_GLYPHS: usize = 64;
+
+// drawn along edges instead of the ramp, in the order of the direction bins in ascii.wgsl
+const EDGE_GLYPHS: [char; 4] = ['|', '/', '-', '\\'];
+
+// u32 words per cell: its glyph, then its average color packed as rgba8
+const CELL_WORDS: u64 = 2;
+
+// the narrowest cell the controls allow, what the storage buffer is sized for
+pub const MIN_CELL_WIDTH: u32 = 4;
+pub const MAX_CELL_WIDTH: u32 = 64;
+
+
+// draws the image as glyphs, see ascii.wgsl. a pass per cell averages its color, finds its
+// edge direction and picks its glyph, a pass per pixel draws the glyph from the atlas.
+// a tiled image starts its cells again at the corner of every tile
+pub static ASCII: ComputeEffect = ComputeEffect {
+    label: "ASCII",
+    source: include_str!("../shaders/ascii.wgsl"),
+    passes: &[
+        ComputePass {
+            entry_point: "cells_main",
+            workgroup_size: [8, 8],
+            domain: Domain::custom(|params, width, height| grid(params.glyph_cell, width, height)),
+            iterations: |_, _, _| 1,
+            writes: false,
+        },
+        ComputePass {
+            entry_point: "draw_main",
+            workgroup_size: [8, 8],
+            domain: Domain::pixels,
+            iterations: |_, _, _| 1,
+            writes: true,
+        },
+    ],
+    buffer_size: |width, height| {
+        let (columns, rows) = grid(MIN_CELL_WIDTH, width, height);
+        columns as u64 * rows as u64 * CELL_WORDS * 4
+    },
+    rounds: |_, _, _, _| 1,
+};
+
+// cells are twice as tall as they are wide, like characters in a terminal,
+// so the text export keeps the image's proportions
+pub fn cell_size(cell_width: u32) -> (u32, u32) {
+    let width = cell_width.clamp(MIN_CELL_WIDTH, MAX_CELL_WIDTH);
+    (width, width * 2)
+}
+
+// columns and rows of cells covering width x height, the last ones may be cut off
+pub fn grid(cell_width: u32, width: u32, height: u32) -> (u32, u32) {
+    let (cell_width, cell_height) = cell_size(cell_width);
+    (width.div_ceil(cell_width), height.div_ceil(cell_height))
+}
+
+// the glyphs of a ramp, dark to light. an empty ramp draws nothing
+pub fn ramp(text: &str) -> Vec<char> {
+    let glyphs: Vec<char> = text.chars().filter(|c| !c.is_control()).take(MAX_GLYPHS).collect();
+    if glyphs.is_empty() { vec![' '] } else { glyphs }
+}
+
+
+// the glyphs of a ramp and the edge glyphs rasterized side by side, one cell each
+pub struct GlyphAtlas {
+    image: image::GrayImage,
+}
+
+impl GlyphAtlas {
+    // font is a path to a font file, empty for the bundled one
+    pub fn new(font: &str, ramp_text: &str, cell_width: u32) -> Result<GlyphAtlas, String> {
+        let font = match font {
+            "" => FontArc::try_from_vec(DEFAULT_FONT.to_vec()),
+            path => FontArc::try_from_vec(std::fs::read(path).map_err(|e| format!("could not read {path}: {e}"))?),
+        }.map_err(|e| e.to_string())?;
+
+        let (cell_width, cell_height) = cell_size(cell_width);
+        let glyphs: Vec<char> = ramp(ramp_text).into_iter().chain(EDGE_GLYPHS).collect();
+        let mut image = image::GrayImage::new(cell_width * glyphs.len() as u32, cell_height);
+
+        // as tall as the cell, or narrower if the widest glyph would not fit across it
+        let widest = glyphs.iter()
+            .map(|c| font.as_scaled(cell_height as f32).h_advance(font.glyph_id(*c)))
+            .fold(0.0, f32::max);
+        let scale = PxScale::from(cell_height as f32 * (cell_width as f32 / widest).min(1.0));
+        let scaled = font.as_scaled(scale);
+        let baseline = (cell_height as f32 - scaled.height()) / 2.0 + scaled.ascent();
+
+        for (i, c) in glyphs.iter().enumerate() {
+            let id = font.glyph_id(*c);
+            let left = (i as u32 * cell_width) as f32 + (cell_width as f32 - scaled.h_advance(id)) / 2.0;
+            let Some(outline) = font.outline_glyph(id.with_scale_and_position(scale, ab_glyph::point(left, baseline))) else {
+                continue;
+            };
+            let bounds = outline.px_bounds();
+            let cell = (i as u32 * cell_width) as i32..((i as u32 + 1) * cell_width) as i32;
+            outline.draw(|x, y, coverage| {
+                let (x, y) = (bounds.min.x as i32 + x as i32, bounds.min.y as i32 + y as i32);
+                if cell.contains(&x) && (0..cell_height as i32).contains(&y) {
+                    image.put_pixel(x as u32, y as u32, image::Luma([(coverage.clamp(0.0, 1.0) * 255.0).round() as u8]));
+                }
+            });
+        }
+
+        Ok(GlyphAtlas { image })
+    }
+
+    pub fn create_texture(&self, device: &wgpu::Device, queue: &wgpu::Queue) -> wgpu::Texture {
+        let size = wgpu::Extent3d {
+            width: self.image.width(),
+            height: self.image.height(),
+            depth_or_array_layers: 1,
+        };
+
+        let texture = device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("Glyph Atlas Texture"),
+            size,
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: wgpu::TextureFormat::R8Unorm,
+            usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
+            view_formats: &[],
+        });
+
+        queue.write_texture(
+            wgpu::ImageCopyTexture {
+                texture: &texture,
+                mip_level: 0,
+                origin: wgpu::Origin3d::ZERO,
+                aspect: wgpu::TextureAspect::All,
+            },
+            self.image.as_raw(),
+            wgpu::ImageDataLayout {
+                offset: 0,
+                bytes_per_row: Some(self.image.width()),
+                rows_per_image: Some(self.image.height()),
+            },
+            size,
+        );
+
+        texture
+    }
+}
+
+
+// the cells the effect picked as text, a line per row of cells. ansi colors every
+// glyph with its cell's color using 24 bit escape codes
+pub fn text(cells: &[u32], columns: u32, rows: u32, ramp_text: &str, ansi: bool) -> String {
+    let glyphs: Vec<char> = ramp(ramp_text).into_iter().chain(EDGE_GLYPHS).collect();
+    let mut text = String::new();
+    for row in cells.chunks_exact((columns as u64 * CELL_WORDS) as usize).take(rows as usize) {
+        let mut color = None;
+        for cell in row.chunks_exact(CELL_WORDS as usize) {
+            let glyph = glyphs.get(cell[0] as usize).copied().unwrap_or(' ');
+            // escapes only where the color changes
+            if ansi && color != Some(cell[1]) {
+                let [r, g, b, _] = cell[1].to_le_bytes();
+                text.push_str(&format!("\x1b[38;2;{r};{g};{b}m"));
+                color = Some(cell[1]);
+            }
+            text.push(glyph);
+        }
+        if ansi {
+            text.push_str("\x1b[0m");
+        }
+        text.push('\n');
+    }
+    text
+}
@@ -0,0 +1,201 @@
+use std::sync::Arc;
+use std::time::Instant;
+use iced_wgpu::wgpu;
+use iced_winit::core::Color;
+use iced_winit::futures;
+use crate::compute;
+use crate::controls::{Controls, Message, Shader};
+use crate::loader::Loader;
+use crate::scene::Scene;
+use crate::timing::{self, Timings};
+
+
+const USAGE: &str = "usage: rts --benchmark <image> [--effect <name>] [--frames <n>] [--sigma <s>] [--iterations <n>]";
+
+// frames rendered before measuring, while drivers settle
+const WARMUP_FRAMES: usize = 10;
+
+// size of the offscreen view the composite draws into
+const VIEW_WIDTH: u32 = 1920;
+const VIEW_HEIGHT: u32 = 1080;
+
+
+// renders an image with one effect headless and prints the frame and per pass gpu times as json.
+// every frame renders the stages again, the same work the viewer does while a control changes
+pub fn run(args: &[String]) -> Result<(), Box<dyn std::error::Error>> {
+    let mut controls = Controls::new();
+    let mut frames = 100;
+    let mut image = None;
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        let mut value = || args.next().ok_or_else(|| format!("{arg} needs a value\n{USAGE}"));
+        match arg.as_str() {
+            "--effect" => {
+                let name = value()?;
+                controls.selected_shader = Some(Shader::from_name(name).ok_or_else(|| format!("unknown effect {name}"))?);
+            }
+            "--frames" => frames = value()?.parse()?,
+            "--sigma" => controls.sigma1 = value()?.parse()?,
+            "--iterations" => controls.num_gvf_iterations = value()?.parse()?,
+            path if image.is_none() && !path.starts_with("--") => image = Some(path.to_string()),
+            _ => return Err(format!("unexpected argument {arg}\n{USAGE}").into()),
+        }
+    }
+    controls.selected_image = image.ok_or(USAGE)?;
+
+    let instance = wgpu::Instance::new(wgpu::InstanceDescriptor {
+        backends: wgpu::util::backend_bits_from_env().unwrap_or(wgpu::Backends::PRIMARY),
+        ..Default::default()
+    });
+    let (adapter, device, queue) = futures::futures::executor::block_on(async {
+        let adapter = instance.request_adapter(&wgpu::RequestAdapterOptions {
+            power_preference: wgpu::PowerPreference::HighPerformance,
+            compatible_surface: None,
+            force_fallback_adapter: false,
+        })
+        .await
+        .ok_or("no adapter")?;
+
+        let (device, queue) = adapter
+            .request_device(
+                &wgpu::DeviceDescriptor {
+                    label: None,
+                    required_features: adapter.features() & (wgpu::Features::default() | timing::FEATURES),
+                    required_limits: compute::required_limits(&adapter),
+                },
+                None,
+            )
+            .await?;
+        Ok::<_, Box<dyn std::error::Error>>((adapter, device, queue))
+    })?;
+    let device = Arc::new(device);
+    let queue = Arc::new(queue);
+
+    let format = wgpu::TextureFormat::Rgba8UnormSrgb;
+    let loader = Loader::new(device.clone(), queue.clone(), || {});
+    let mut scene = Scene::new(&device, format, &queue, controls.selected_shader, loader);
+
+    // the image loads on a worker thread, prepare picks it up once it is decoded
+    loop {
+        let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor { label: None });
+        let messages = scene.prepare(&mut encoder, &queue, &device, &controls);
+        queue.submit(Some(encoder.finish()));
+        if let Some(message) = messages.into_iter().find(|message| matches!(message, Message::ImageLoaded(_) | Message::ImageFailed(..))) {
+            if let Message::ImageFailed(path, error) = message {
+                return Err(format!("{path}: {error}").into());
+            }
+            break;
+        }
+        std::thread::sleep(std::time::Duration::from_millis(10));
+    }
+
+    let target = device.create_texture(&wgpu::TextureDescriptor {
+        label: Some("Benchmark Target"),
+        size: wgpu::Extent3d {
+            width: VIEW_WIDTH,
+            height: VIEW_HEIGHT,
+            depth_or_array_layers: 1
+        },
+        mip_level_count: 1,
+        sample_count: 1,
+        dimension: wgpu::TextureDimension::D2,
+        format,
+        usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
+        view_formats: &[],
+    });
+    let view = target.create_view(&wgpu::TextureViewDescriptor::default());
+
+    controls.show_timings = true;
+    let mut measured = Vec::new();
+    for frame in 0..WARMUP_FRAMES + frames {
+        let start = Instant::now();
+        let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor { label: None });
+        scene.invalidate();
+        scene.prepare(&mut encoder, &queue, &device, &controls);
+        {
+            let mut render_pass = Scene::clear(&view, &mut encoder, Color::BLACK);
+            scene.draw(&mut render_pass, &queue, &device, VIEW_WIDTH as f32 / VIEW_HEIGHT as f32, 0, 0, VIEW_WIDTH, VIEW_HEIGHT, &[0.0, 0.0], &1.0);
+        }
+        scene.finish_timing(&mut encoder);
+        queue.submit(Some(encoder.finish()));
+        scene.timings_submitted();
+        let cpu = start.elapsed();
+        device.poll(wgpu::Maintain::Wait);
+        let passes = scene.poll_timings(&device, true).unwrap_or_default();
+        let elapsed = start.elapsed();
+
+        if frame >= WARMUP_FRAMES {
+            measured.push(scene.timings(passes, elapsed.as_secs_f32() * 1000.0, cpu.as_secs_f32() * 1000.0));
+        }
+    }
+
+    println!("{}", report(&adapter.get_info(), &controls, &measured));
+    Ok(())
+}
+
+fn report(info: &wgpu::AdapterInfo, controls: &Controls, measured: &[Timings]) -> String {
+    let first = measured.first().cloned().unwrap_or_default();
+    let summary = |values: Vec<f32>| {
+        let count = values.len().max(1) as f32;
+        let mean = values.iter().sum::<f32>() / count;
+        let min = values.iter().copied().fold(f32::INFINITY, f32::min);
+        let max = values.iter().copied().fold(0.0, f32::max);
+        format!("{{\"mean\": {mean:.4}, \"min\": {:.4}, \"max\": {max:.4}}}", if values.is_empty() { 0.0 } else { min })
+    };
+
+    // mean per pass over the frames, in the order the passes ran
+    let mut passes: Vec<(String, f32)> = Vec::new();
+    for timings in measured {
+        for (label, ms) in &timings.passes {
+            match passes.iter_mut().find(|(name, _)| name == label) {
+                Some((_, total)) => *total += ms,
+                None => passes.push((label.clone(), *ms)),
+            }
+        }
+    }
+    let timestamps = !passes.is_empty();
+    let passes: Vec<String> = passes.iter()
+        .map(|(label, total)| format!("    {}: {:.4}", json_string(label), total / measured.len().max(1) as f32))
+        .collect();
+
+    let shader = controls.selected_shader.map(|shader| shader.to_string()).unwrap_or_default();
+    let lines = [
+        format!("  \"adapter\": {}", json_string(&info.name)),
+        format!("  \"backend\": {}", json_string(&format!("{:?}", info.backend))),
+        format!("  \"image\": {}", json_string(&controls.selected_image)),
+        format!("  \"effect\": {}", json_string(&shader)),
+        format!("  \"width\": {}", first.width),
+        format!("  \"height\": {}", first.height),
+        format!("  \"stage_width\": {}", first.stage_width),
+        format!("  \"stage_height\": {}", first.stage_height),
+        format!("  \"tiles\": {}", first.tiles),
+        format!("  \"frames\": {}", measured.len()),
+        format!("  \"gpu_timestamps\": {timestamps}"),
+        format!("  \"frame_ms\": {}", summary(measured.iter().map(|timings| timings.frame).collect())),
+        format!("  \"cpu_ms\": {}", summary(measured.iter().map(|timings| timings.cpu).collect())),
+        // without timestamp queries only the frame and cpu times mean anything
+        match timestamps {
+            true => format!("  \"gpu_ms\": {}", summary(measured.iter().map(Timings::gpu_total).collect())),
+            false => String::from("  \"gpu_ms\": null"),
+        },
+        match timestamps {
+            true => format!("  \"passes_ms\": {{\n{}\n  }}", passes.join(",\n")),
+            false => String::from("  \"passes_ms\": {}"),
+        },
+    ];
+    format!("{{\n{}\n}}", lines.join(",\n"))
+}
+
+fn json_string(value: &str) -> String {
+    let mut escaped = String::from("\"");
+    for c in value.chars() {
+        match c {
+            '"' => escaped.push_str("\\\""),
+            '\\' => escaped.push_str("\\\\"),
+            c if c.is_control() => escaped.push_str(&format!("\\u{:04x}", c as u32)),
+            c => escaped.push(c),
+        }
+    }
+    escaped.push('"');
+    escaped
+}
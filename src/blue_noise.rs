@@ -0,0 +1,202 @@
+use iced_wgpu::wgpu;
+
+
+// pixels across the tile, it repeats over the image
+const SIZE: u32 = 64;
+// spread of the gaussian that measures how crowded a pixel is by the points around it
+const SIGMA: f32 = 1.9;
+// one in this many pixels is a point of the pattern the ranking starts from
+const INITIAL_SHARE: u32 = 10;
+
+
+// a tile of blue noise made with robert ulichney's void and cluster method. pixels are
+// ranked by adding them one at a time where points are the sparsest, so thresholding it at
+// any level spreads points evenly without clumps or gaps. the tile wraps around its edges,
+// see noise in fragment.wgsl
+pub struct BlueNoise {
+    // the place of every pixel in the order, row by row
+    ranks: Vec<u32>,
+}
+
+// points on the tile and how crowded every pixel is by them
+#[derive(Clone)]
+struct Pattern {
+    points: Vec<bool>,
+    energy: Vec<f32>,
+}
+
+impl Pattern {
+    // adds a point at pixel, or takes it away. kernel is the gaussian by the offset
+    // from pixel, wrapped around the tile
+    fn toggle(&mut self, pixel: usize, kernel: &[f32]) {
+        self.points[pixel] = !self.points[pixel];
+        let sign = if self.points[pixel] { 1.0 } else { -1.0 };
+        let (px, py) = (pixel as u32 % SIZE, pixel as u32 / SIZE);
+        for y in 0..SIZE {
+            for x in 0..SIZE {
+                let offset = (y + SIZE - py) % SIZE * SIZE + (x + SIZE - px) % SIZE;
+                self.energy[(y * SIZE + x) as usize] += sign * kernel[offset as usize];
+            }
+        }
+    }
+
+    // the most crowded point
+    fn tightest_cluster(&self) -> usize {
+        self.most(true, |a, b| a > b)
+    }
+
+    // the least crowded pixel without a point
+    fn largest_void(&self) -> usize {
+        self.most(false, |a, b| a < b)
+    }
+
+    fn most(&self, point: bool, better: fn(f32, f32) -> bool) -> usize {
+        let mut best = None;
+        for (pixel, &energy) in self.energy.iter().enumerate() {
+            if self.points[pixel] == point && best.is_none_or(|best| better(energy, self.energy[best])) {
+                best = Some(pixel);
+            }
+        }
+        best.unwrap()
+    }
+}
+
+impl BlueNoise {
+    pub fn new() -> BlueNoise {
+        let count = (SIZE * SIZE) as usize;
+        let wrap = |d: u32| d.min(SIZE - d) as f32;
+        let kernel: Vec<f32> = (0..SIZE * SIZE).map(|offset| {
+            let (dx, dy) = (wrap(offset % SIZE), wrap(offset / SIZE));
+            (-(dx * dx + dy * dy) / (2.0 * SIGMA * SIGMA)).exp()
+        }).collect();
+
+        // scattered at random, the same points every time
+        let mut pattern = Pattern { points: vec![false; count], energy: vec![0.0; count] };
+        let mut state = 0x9e3779b9u32;
+        let mut added = 0;
+        while added < count / INITIAL_SHARE as usize {
+            state ^= state << 13;
+            state ^= state >> 17;
+            state ^= state << 5;
+            let pixel = state as usize % count;
+            if !pattern.points[pixel] {
+                pattern.toggle(pixel, &kernel);
+                added += 1;
+            }
+        }
+
+        // evened out by moving the most crowded point to the emptiest spot until it stays put
+        loop {
+            let cluster = pattern.tightest_cluster();
+            pattern.toggle(cluster, &kernel);
+            let void = pattern.largest_void();
+            pattern.toggle(void, &kernel);
+            if void == cluster {
+                break;
+            }
+        }
+
+        // the points rank below the rest, the most crowded lowest. the pixels left over rank in
+        // the order they fill the emptiest spot, which is also where the pixels without a point
+        // crowd each other most, so it does for the second half of ulichney's ranking too
+        let mut ranks = vec![0; count];
+        let mut removing = pattern.clone();
+        for rank in (0..added).rev() {
+            let cluster = removing.tightest_cluster();
+            removing.toggle(cluster, &kernel);
+            ranks[cluster] = rank as u32;
+        }
+        for rank in added..count {
+            let void = pattern.largest_void();
+            pattern.toggle(void, &kernel);
+            ranks[void] = rank as u32;
+        }
+        BlueNoise { ranks }
+    }
+
+    // the ranks as thresholds in (0, 1)
+    pub fn create_texture(&self, device: &wgpu::Device, queue: &wgpu::Queue) -> wgpu::Texture {
+        let size = wgpu::Extent3d {
+            width: SIZE,
+            height: SIZE,
+            depth_or_array_layers: 1,
+        };
+
+        let texture = device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("Blue Noise Texture"),
+            size,
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: wgpu::TextureFormat::R32Float,
+            usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
+            view_formats: &[],
+        });
+
+        let count = self.ranks.len() as f32;
+        let thresholds: Vec<f32> = self.ranks.iter().map(|&rank| (rank as f32 + 0.5) / count).collect();
+        queue.write_texture(
+            wgpu::ImageCopyTexture {
+                texture: &texture,
+                mip_level: 0,
+                origin: wgpu::Origin3d::ZERO,
+                aspect: wgpu::TextureAspect::All,
+            },
+            bytemuck::cast_slice(&thresholds),
+            wgpu::ImageDataLayout {
+                offset: 0,
+                bytes_per_row: Some(SIZE * 4),
+                rows_per_image: Some(SIZE),
+            },
+            size,
+        );
+
+        texture
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // how far the points of the pixels ranked below level stray from the share expected in
+    // every 8 x 8 box of the tile, wrapped around, on average
+    fn box_deviation(ranks: &[u32], level: u32) -> f32 {
+        let expected = 64.0 * level as f32 / ranks.len() as f32;
+        let mut deviation = 0.0;
+        for y in 0..SIZE {
+            for x in 0..SIZE {
+                let points = (0..64).filter(|i| {
+                    let pixel = (y + i / 8) % SIZE * SIZE + (x + i % 8) % SIZE;
+                    ranks[pixel as usize] < level
+                }).count();
+                deviation += (points as f32 - expected).abs();
+            }
+        }
+        deviation / (SIZE * SIZE) as f32
+    }
+
+    #[test]
+    fn ranks_every_pixel_once() {
+        let mut ranks = BlueNoise::new().ranks;
+        ranks.sort_unstable();
+        assert!(ranks.iter().copied().eq(0..SIZE * SIZE));
+    }
+
+    #[test]
+    fn spreads_every_level_evenly() {
+        let ranks = BlueNoise::new().ranks;
+        // the same ranks shuffled, white noise
+        let mut state = 1u32;
+        let mut white = ranks.clone();
+        for i in (1..white.len()).rev() {
+            state = state.wrapping_mul(1664525).wrapping_add(1013904223);
+            white.swap(i, (state >> 8) as usize % (i + 1));
+        }
+        for level in [SIZE * SIZE / 10, SIZE * SIZE / 4, SIZE * SIZE / 2, SIZE * SIZE * 9 / 10] {
+            let (blue, white) = (box_deviation(&ranks, level), box_deviation(&white, level));
+            assert!(blue < white / 2.0, "{blue} against {white} for white noise at level {level}");
+        }
+    }
+}
@@ -0,0 +1,206 @@
+use iced_wgpu::wgpu;
+use crate::pass;
+
+
+// taps after folding, mirrors the taps array in blur.wgsl. enough for the crt's bloom at its
+// widest scanlines
+pub const MAX_TAPS: usize = 128;
+
+// texels the taps reach out from the center, every tap after the first folds two together
+pub const REACH: u32 = 2 * (MAX_TAPS as u32 - 1);
+
+// the widest gaussian the taps hold out to three sigma, wider ones are blurred at this sigma
+// rather than cut off
+pub const MAX_SIGMA: f32 = REACH as f32 / 3.0;
+
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+struct BlurParameters {
+    direction: [f32; 2],
+    tap_count: u32,
+    _padding: u32,
+    taps: [[f32; 4]; MAX_TAPS],
+}
+
+
+// one sided gaussian kernel as (offset, weight) pairs, center first.
+// neighbouring texels are folded together so the linear sampler fetches both at once,
+// which halves the number of samples per pass. sigma is capped at MAX_SIGMA
+pub fn gaussian_taps(sigma: f32) -> Vec<[f32; 2]> {
+    if sigma < 0.01 {
+        return vec![[0.0, 1.0]];
+    }
+
+    let sigma = sigma.min(MAX_SIGMA);
+    let radius = ((sigma * 3.0).ceil() as usize).min(REACH as usize);
+    let weights: Vec<f32> = (0..=radius)
+        .map(|i| (-((i * i) as f32) / (2.0 * sigma * sigma)).exp())
+        .collect();
+    let total = weights[0] + 2.0 * weights[1..].iter().sum::<f32>();
+
+    let mut taps = vec![[0.0, weights[0] / total]];
+    let mut i = 1;
+    while i <= radius {
+        let (w1, w2) = (weights[i], if i < radius { weights[i + 1] } else { 0.0 });
+        let weight = w1 + w2;
+        let offset = (i as f32 * w1 + (i + 1) as f32 * w2) / weight;
+        taps.push([offset, weight / total]);
+        i += 2;
+    }
+
+    taps
+}
+
+// box kernel of width 2 * radius + 1 folded into pairs the same way, radius capped at REACH
+pub fn box_taps(radius: u32) -> Vec<[f32; 2]> {
+    let radius = (radius as usize).min(REACH as usize);
+    let weight = 1.0 / (2 * radius + 1) as f32;
+
+    let mut taps = vec![[0.0, weight]];
+    let mut i = 1;
+    while i <= radius {
+        if i < radius {
+            taps.push([i as f32 + 0.5, 2.0 * weight]);
+        } else {
+            taps.push([i as f32, weight]);
+        }
+        i += 2;
+    }
+
+    taps
+}
+
+
+// gaussian or box blur as a horizontal pass into an intermediate texture
+// followed by a vertical pass into the output texture
+pub struct SeparableBlur {
+    pipeline: wgpu::RenderPipeline,
+    bind_group_layout: wgpu::BindGroupLayout,
+    sampler: wgpu::Sampler,
+    horizontal_buffer: wgpu::Buffer,
+    vertical_buffer: wgpu::Buffer,
+    horizontal_bind_group: wgpu::BindGroup,
+    vertical_bind_group: wgpu::BindGroup,
+    intermediate_view: wgpu::TextureView,
+    output_view: wgpu::TextureView,
+}
+
+impl SeparableBlur {
+    pub fn new(device: &wgpu::Device, input: &wgpu::TextureView, width: u32, height: u32) -> SeparableBlur {
+        let module = device.create_shader_module(wgpu::include_wgsl!("../shaders/blur.wgsl"));
+
+        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("Blur Bind Group Layout"),
+            entries: &[
+                pass::texture_entry(0),
+                pass::sampler_entry(1),
+                pass::uniform_entry(2),
+            ],
+        });
+
+        let pipeline = pass::create_pipeline(device, "Blur Pipeline", &module, "frag_main", &bind_group_layout, 1);
+
+        // the tap offsets rely on bilinear filtering between texels
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            address_mode_u: wgpu::AddressMode::ClampToEdge,
+            address_mode_v: wgpu::AddressMode::ClampToEdge,
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            mipmap_filter: wgpu::FilterMode::Nearest,
+            ..Default::default()
+        });
+
+        let create_buffer = |label| device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some(label),
+            size: std::mem::size_of::<BlurParameters>() as wgpu::BufferAddress,
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        let horizontal_buffer = create_buffer("Horizontal Blur Buffer");
+        let vertical_buffer = create_buffer("Vertical Blur Buffer");
+
+        let intermediate_view = pass::create_target(device, "Blur Intermediate Texture", width, height)
+            .create_view(&wgpu::TextureViewDescriptor::default());
+        let output_view = pass::create_target(device, "Blur Output Texture", width, height)
+            .create_view(&wgpu::TextureViewDescriptor::default());
+
+        let horizontal_bind_group = create_bind_group(device, &bind_group_layout, input, &sampler, &horizontal_buffer);
+        let vertical_bind_group = create_bind_group(device, &bind_group_layout, &intermediate_view, &sampler, &vertical_buffer);
+
+        SeparableBlur {
+            pipeline,
+            bind_group_layout,
+            sampler,
+            horizontal_buffer,
+            vertical_buffer,
+            horizontal_bind_group,
+            vertical_bind_group,
+            intermediate_view,
+            output_view,
+        }
+    }
+
+    pub fn output_view(&self) -> &wgpu::TextureView {
+        &self.output_view
+    }
+
+    // points the blur at a different texture of the same size
+    pub fn set_input(&mut self, device: &wgpu::Device, input: &wgpu::TextureView) {
+        self.horizontal_bind_group = create_bind_group(device, &self.bind_group_layout, input, &self.sampler, &self.horizontal_buffer);
+    }
+
+    pub fn encode(&self, encoder: &mut wgpu::CommandEncoder, queue: &wgpu::Queue, sigma: f32) {
+        self.encode_taps(encoder, queue, &gaussian_taps(sigma));
+    }
+
+    pub fn encode_box(&self, encoder: &mut wgpu::CommandEncoder, queue: &wgpu::Queue, radius: u32) {
+        self.encode_taps(encoder, queue, &box_taps(radius));
+    }
+
+    fn encode_taps(&self, encoder: &mut wgpu::CommandEncoder, queue: &wgpu::Queue, taps: &[[f32; 2]]) {
+        let mut params = BlurParameters {
+            direction: [1.0, 0.0],
+            tap_count: taps.len() as u32,
+            _padding: 0,
+            taps: [[0.0; 4]; MAX_TAPS],
+        };
+        for (i, [offset, weight]) in taps.iter().enumerate() {
+            params.taps[i] = [*offset, *weight, 0.0, 0.0];
+        }
+        queue.write_buffer(&self.horizontal_buffer, 0, bytemuck::cast_slice(&[params]));
+
+        params.direction = [0.0, 1.0];
+        queue.write_buffer(&self.vertical_buffer, 0, bytemuck::cast_slice(&[params]));
+
+        pass::run(encoder, "Blur Pass", &self.pipeline, &self.horizontal_bind_group, &[&self.intermediate_view]);
+        pass::run(encoder, "Blur Pass", &self.pipeline, &self.vertical_bind_group, &[&self.output_view]);
+    }
+}
+
+fn create_bind_group(
+    device: &wgpu::Device,
+    layout: &wgpu::BindGroupLayout,
+    input: &wgpu::TextureView,
+    sampler: &wgpu::Sampler,
+    buffer: &wgpu::Buffer,
+) -> wgpu::BindGroup {
+    device.create_bind_group(&wgpu::BindGroupDescriptor {
+        label: Some("Blur Bind Group"),
+        layout,
+        entries: &[
+            wgpu::BindGroupEntry {
+                binding: 0,
+                resource: wgpu::BindingResource::TextureView(input),
+            },
+            wgpu::BindGroupEntry {
+                binding: 1,
+                resource: wgpu::BindingResource::Sampler(sampler),
+            },
+            wgpu::BindGroupEntry {
+                binding: 2,
+                resource: buffer.as_entire_binding(),
+            },
+        ],
+    })
+}
@@ -0,0 +1,162 @@
+use iced_wgpu::wgpu;
+use crate::compute::{ComputeEffect, ComputePass, Domain};
+use crate::scene::Parameters;
+
+
+// layers of strokes the controls allow, each half as wide as the one before
+pub const MAX_LAYERS: u32 = 4;
+
+// the widths of strokes in pixels. the longest strokes of the widest brush
+// have to stay inside the halo of a tile, see tiles::HALO
+pub const MIN_STROKE_WIDTH: u32 = 4;
+pub const MAX_STROKE_WIDTH: u32 = 24;
+// stroke length in stroke widths
+pub const MAX_STROKE_LENGTH: f32 = 6.0;
+
+// u32 words per stroke: its color packed as rgba8 with alpha 0 for no stroke,
+// then its direction packed as two snorm16
+const STROKE_WORDS: u64 = 2;
+
+// the bundled brush, see BrushTexture::bristles
+const BRISTLES_LENGTH: u32 = 128;
+const BRISTLES_WIDTH: u32 = 32;
+
+
+// paints the image with strokes running along its edges, see brush_strokes.wgsl. the image
+// is split into a grid of cells per layer, as wide as the layer's strokes, and a pass per
+// layer places a stroke in every cell, turned across the cell's sobel gradients and colored
+// with its average. layers after the first only paint where a cell holds enough detail.
+// a pass per pixel then lays the layers over each other coarse to fine.
+// cells are anchored to the whole image, so a tiled image is painted without seams
+pub static BRUSH_STROKES: ComputeEffect = ComputeEffect {
+    label: "Brush Strokes",
+    source: include_str!("../shaders/brush_strokes.wgsl"),
+    passes: &[
+        ComputePass {
+            entry_point: "strokes_main",
+            workgroup_size: [8, 8],
+            // the finest layer has the most cells
+            domain: Domain::custom(|params, width, height| grid(params, layers(params) - 1, width, height)),
+            iterations: |params, _, _| layers(params),
+            writes: false,
+        },
+        ComputePass {
+            entry_point: "draw_main",
+            workgroup_size: [8, 8],
+            domain: Domain::pixels,
+            iterations: |_, _, _| 1,
+            writes: true,
+        },
+    ],
+    // every layer at the narrowest strokes, the cells at either end may be cut off
+    buffer_size: |width, height| {
+        MAX_LAYERS as u64 * (width / MIN_STROKE_WIDTH + 2) as u64 * (height / MIN_STROKE_WIDTH + 2) as u64 * STROKE_WORDS * 4
+    },
+    rounds: |_, _, _, _| 1,
+};
+
+pub fn layers(params: &Parameters) -> u32 {
+    params.stroke_layers.clamp(1, MAX_LAYERS)
+}
+
+// the width of the strokes of a layer, mirrors layer_width in brush_strokes.wgsl
+pub fn layer_width(params: &Parameters, layer: u32) -> u32 {
+    (params.stroke_width.clamp(MIN_STROKE_WIDTH, MAX_STROKE_WIDTH) >> layer).max(MIN_STROKE_WIDTH)
+}
+
+// columns and rows of a layer's cells over the tile of width x height at the tile's origin
+fn grid(params: &Parameters, layer: u32, width: u32, height: u32) -> (u32, u32) {
+    let cell = layer_width(params, layer);
+    let count = |origin: u32, length: u32| (origin + length.max(1) - 1) / cell - origin / cell + 1;
+    (count(params.tile_x, width), count(params.tile_y, height))
+}
+
+
+// how much paint one stroke leaves, with the stroke running left to right across the image
+pub struct BrushTexture {
+    image: image::GrayImage,
+}
+
+impl BrushTexture {
+    // path is an image file, empty for the bundled brush. dark opaque pixels leave paint,
+    // so brushes drawn black on white and black on transparent both work
+    pub fn new(path: &str) -> Result<BrushTexture, String> {
+        if path.is_empty() {
+            return Ok(BrushTexture { image: BrushTexture::bristles() });
+        }
+        let brush = image::open(path).map_err(|e| format!("could not read {path}: {e}"))?.to_luma_alpha8();
+        let image = image::GrayImage::from_fn(brush.width(), brush.height(), |x, y| {
+            let [luma, alpha] = brush.get_pixel(x, y).0;
+            image::Luma([((255 - luma as u32) * alpha as u32 / 255) as u8])
+        });
+        Ok(BrushTexture { image })
+    }
+
+    // a row of bristles of their own strength and length, thinning out
+    // towards the sides and the end of the stroke
+    fn bristles() -> image::GrayImage {
+        // the same bristles every time
+        let hash = |n: u32| {
+            let mut v = n.wrapping_mul(0x9e3779b9) ^ 0x85ebca6b;
+            v ^= v >> 16;
+            v = v.wrapping_mul(0x7feb352d);
+            v ^= v >> 15;
+            v = v.wrapping_mul(0x846ca68b);
+            v ^= v >> 16;
+            (v >> 8) as f32 / 16777216.0
+        };
+        let smoothstep = |edge0: f32, edge1: f32, x: f32| {
+            let t = ((x - edge0) / (edge1 - edge0)).clamp(0.0, 1.0);
+            t * t * (3.0 - 2.0 * t)
+        };
+        image::GrayImage::from_fn(BRISTLES_LENGTH, BRISTLES_WIDTH, |x, y| {
+            let strength = 0.55 + 0.45 * hash(3 * y);
+            let start = 0.08 * hash(3 * y + 1);
+            let end = 0.7 + 0.3 * hash(3 * y + 2);
+            let along = (x as f32 + 0.5) / BRISTLES_LENGTH as f32;
+            let across = (y as f32 + 0.5) / BRISTLES_WIDTH as f32 * 2.0 - 1.0;
+            let coverage = strength
+                * (1.0 - across.abs().powi(3))
+                * smoothstep(start, start + 0.06, along)
+                * (1.0 - smoothstep(end - 0.15, end, along));
+            image::Luma([(coverage * 255.0).round() as u8])
+        })
+    }
+
+    pub fn create_texture(&self, device: &wgpu::Device, queue: &wgpu::Queue) -> wgpu::Texture {
+        let size = wgpu::Extent3d {
+            width: self.image.width(),
+            height: self.image.height(),
+            depth_or_array_layers: 1,
+        };
+
+        let texture = device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("Brush Texture"),
+            size,
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: wgpu::TextureFormat::R8Unorm,
+            usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
+            view_formats: &[],
+        });
+
+        queue.write_texture(
+            wgpu::ImageCopyTexture {
+                texture: &texture,
+                mip_level: 0,
+                origin: wgpu::Origin3d::ZERO,
+                aspect: wgpu::TextureAspect::All,
+            },
+            self.image.as_raw(),
+            wgpu::ImageDataLayout {
+                offset: 0,
+                bytes_per_row: Some(self.image.width()),
+                rows_per_image: Some(self.image.height()),
+            },
+            size,
+        );
+
+        texture
+    }
+}
@@ -0,0 +1,210 @@
+use iced_wgpu::wgpu;
+
+
+const WORKGROUP_SIZE: u32 = 8;
+
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+struct CannyParameters {
+    low_threshold: f32,
+    high_threshold: f32,
+    _padding: [f32; 2],
+}
+
+
+fn create_storage_texture(device: &wgpu::Device, label: &str, format: wgpu::TextureFormat, width: u32, height: u32) -> wgpu::TextureView {
+    device.create_texture(&wgpu::TextureDescriptor {
+        label: Some(label),
+        size: wgpu::Extent3d {
+            width,
+            height,
+            depth_or_array_layers: 1
+        },
+        mip_level_count: 1,
+        sample_count: 1,
+        dimension: wgpu::TextureDimension::D2,
+        format,
+        usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::STORAGE_BINDING,
+        view_formats: &[],
+    }).create_view(&wgpu::TextureViewDescriptor::default())
+}
+
+// every canny pass reads one texture with textureLoad and writes one storage texture.
+// the storage binding number selects between the gradient and edge outputs in canny.wgsl
+fn create_bind_group_layout(device: &wgpu::Device, storage_binding: u32, format: wgpu::TextureFormat, with_parameters: bool) -> wgpu::BindGroupLayout {
+    let mut entries = vec![
+        wgpu::BindGroupLayoutEntry {
+            binding: 0,
+            visibility: wgpu::ShaderStages::COMPUTE,
+            ty: wgpu::BindingType::Texture {
+                multisampled: false,
+                view_dimension: wgpu::TextureViewDimension::D2,
+                sample_type: wgpu::TextureSampleType::Float { filterable: false },
+            },
+            count: None,
+        },
+        wgpu::BindGroupLayoutEntry {
+            binding: storage_binding,
+            visibility: wgpu::ShaderStages::COMPUTE,
+            ty: wgpu::BindingType::StorageTexture {
+                access: wgpu::StorageTextureAccess::WriteOnly,
+                format,
+                view_dimension: wgpu::TextureViewDimension::D2,
+            },
+            count: None,
+        },
+    ];
+
+    if with_parameters {
+        entries.push(wgpu::BindGroupLayoutEntry {
+            binding: 3,
+            visibility: wgpu::ShaderStages::COMPUTE,
+            ty: wgpu::BindingType::Buffer {
+                ty: wgpu::BufferBindingType::Uniform,
+                has_dynamic_offset: false,
+                min_binding_size: None
+            },
+            count: None,
+        });
+    }
+
+    device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+        label: Some("Canny Bind Group Layout"),
+        entries: &entries,
+    })
+}
+
+
+// canny edge detection on top of an already blurred input:
+// sobel gradients -> non-maximum suppression with double threshold -> hysteresis passes.
+// the edge texture holds 1.0 for edges and 0.5 for weak pixels that never connected to one
+pub struct Canny {
+    gradient_pipeline: wgpu::ComputePipeline,
+    suppress_pipeline: wgpu::ComputePipeline,
+    hysteresis_pipeline: wgpu::ComputePipeline,
+    gradient_bind_group: wgpu::BindGroup,
+    suppress_bind_group: wgpu::BindGroup,
+    hysteresis_bind_groups: [wgpu::BindGroup; 2],
+    parameters_buffer: wgpu::Buffer,
+    edge_view: wgpu::TextureView,
+    width: u32,
+    height: u32,
+}
+
+impl Canny {
+    pub fn new(device: &wgpu::Device, input: &wgpu::TextureView, width: u32, height: u32) -> Canny {
+        let module = device.create_shader_module(wgpu::include_wgsl!("../shaders/canny.wgsl"));
+
+        let gradient_layout = create_bind_group_layout(device, 1, wgpu::TextureFormat::Rgba32Float, false);
+        let suppress_layout = create_bind_group_layout(device, 2, wgpu::TextureFormat::R32Float, true);
+        let hysteresis_layout = create_bind_group_layout(device, 2, wgpu::TextureFormat::R32Float, false);
+
+        let create_pipeline = |layout: &wgpu::BindGroupLayout, entry_point| {
+            device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+                label: Some("Canny Pipeline"),
+                layout: Some(&device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+                    label: Some("Canny Pipeline Layout"),
+                    bind_group_layouts: &[layout],
+                    push_constant_ranges: &[],
+                })),
+                module: &module,
+                entry_point,
+            })
+        };
+        let gradient_pipeline = create_pipeline(&gradient_layout, "gradient_main");
+        let suppress_pipeline = create_pipeline(&suppress_layout, "suppress_main");
+        let hysteresis_pipeline = create_pipeline(&hysteresis_layout, "hysteresis_main");
+
+        let parameters_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Canny Parameters Buffer"),
+            size: std::mem::size_of::<CannyParameters>() as wgpu::BufferAddress,
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+
+        let gradient_view = create_storage_texture(device, "Canny Gradient Texture", wgpu::TextureFormat::Rgba32Float, width, height);
+        let edge_view = create_storage_texture(device, "Canny Edge Texture", wgpu::TextureFormat::R32Float, width, height);
+        let scratch_view = create_storage_texture(device, "Canny Scratch Texture", wgpu::TextureFormat::R32Float, width, height);
+
+        let create_bind_group = |layout: &wgpu::BindGroupLayout, input: &wgpu::TextureView, storage_binding: u32, output: &wgpu::TextureView, with_parameters: bool| {
+            let mut entries = vec![
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::TextureView(input),
+                },
+                wgpu::BindGroupEntry {
+                    binding: storage_binding,
+                    resource: wgpu::BindingResource::TextureView(output),
+                },
+            ];
+            if with_parameters {
+                entries.push(wgpu::BindGroupEntry {
+                    binding: 3,
+                    resource: parameters_buffer.as_entire_binding(),
+                });
+            }
+            device.create_bind_group(&wgpu::BindGroupDescriptor {
+                label: Some("Canny Bind Group"),
+                layout,
+                entries: &entries,
+            })
+        };
+
+        let gradient_bind_group = create_bind_group(&gradient_layout, input, 1, &gradient_view, false);
+        let suppress_bind_group = create_bind_group(&suppress_layout, &gradient_view, 2, &edge_view, true);
+        // hysteresis ping-pongs edge -> scratch -> edge
+        let hysteresis_bind_groups = [
+            create_bind_group(&hysteresis_layout, &edge_view, 2, &scratch_view, false),
+            create_bind_group(&hysteresis_layout, &scratch_view, 2, &edge_view, false),
+        ];
+
+        Canny {
+            gradient_pipeline,
+            suppress_pipeline,
+            hysteresis_pipeline,
+            gradient_bind_group,
+            suppress_bind_group,
+            hysteresis_bind_groups,
+            parameters_buffer,
+            edge_view,
+            width,
+            height,
+        }
+    }
+
+    pub fn edge_view(&self) -> &wgpu::TextureView {
+        &self.edge_view
+    }
+
+    pub fn encode(&self, encoder: &mut wgpu::CommandEncoder, queue: &wgpu::Queue, low_threshold: f32, high_threshold: f32, iterations: u32) {
+        queue.write_buffer(&self.parameters_buffer, 0, bytemuck::cast_slice(&[CannyParameters {
+            low_threshold,
+            high_threshold,
+            _padding: [0.0; 2],
+        }]));
+
+        let workgroups_x = self.width.div_ceil(WORKGROUP_SIZE);
+        let workgroups_y = self.height.div_ceil(WORKGROUP_SIZE);
+
+        let mut compute_pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
+            label: Some("Canny Pass"),
+            timestamp_writes: None,
+        });
+
+        compute_pass.set_pipeline(&self.gradient_pipeline);
+        compute_pass.set_bind_group(0, &self.gradient_bind_group, &[]);
+        compute_pass.dispatch_workgroups(workgroups_x, workgroups_y, 1);
+
+        compute_pass.set_pipeline(&self.suppress_pipeline);
+        compute_pass.set_bind_group(0, &self.suppress_bind_group, &[]);
+        compute_pass.dispatch_workgroups(workgroups_x, workgroups_y, 1);
+
+        // always an even number of steps so the result ends up back in the edge texture
+        compute_pass.set_pipeline(&self.hysteresis_pipeline);
+        for i in 0..(iterations + iterations % 2) {
+            compute_pass.set_bind_group(0, &self.hysteresis_bind_groups[(i % 2) as usize], &[]);
+            compute_pass.dispatch_workgroups(workgroups_x, workgroups_y, 1);
+        }
+    }
+}
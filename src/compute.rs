@@ -0,0 +1,339 @@
+use iced_wgpu::wgpu;
+use crate::pass;
+use crate::scene::{Parameters, PARAMETERS_WGSL};
+
+
+// dispatches one effect can record per render, iterations of every pass and round together.
+// each pass gets an equal share, so the last pass still runs and writes the effect texture,
+// and rounds are cut to what fits, see ComputeEffect::rounds
+const MAX_DISPATCHES: usize = 1024;
+
+
+// the default limits, with storage buffers as large as the adapter allows,
+// see ComputeEffect::buffer_size
+pub fn required_limits(adapter: &wgpu::Adapter) -> wgpu::Limits {
+    let limits = adapter.limits();
+    wgpu::Limits {
+        max_storage_buffer_binding_size: limits.max_storage_buffer_binding_size,
+        max_buffer_size: limits.max_buffer_size,
+        ..wgpu::Limits::default()
+    }
+}
+
+
+// what a single invocation of a compute pass covers
+#[allow(non_camel_case_types)]
+#[derive(Debug, Clone, Copy)]
+pub enum Domain {
+    // one per pixel
+    pixels,
+    // one per row or column, for effects that walk along them like sorting or error diffusion.
+    // none of the effects so far does
+    #[allow(dead_code)]
+    rows,
+    #[allow(dead_code)]
+    columns,
+    // x by y invocations worked out by the effect from the parameters and the stage size
+    custom(fn(&Parameters, u32, u32) -> (u32, u32)),
+}
+
+// one entry point of a compute effect, dispatched iterations times in a row
+pub struct ComputePass {
+    pub entry_point: &'static str,
+    // has to match the entry point's @workgroup_size, x then y
+    pub workgroup_size: [u32; 2],
+    pub domain: Domain,
+    // from the parameters and the stage size, at least one dispatch runs and at most
+    // MAX_DISPATCHES / passes
+    pub iterations: fn(&Parameters, u32, u32) -> u32,
+    // whether the entry point writes binding 2. passes that only work on the buffer leave
+    // it, the dispatches after them read what the last writing one wrote
+    pub writes: bool,
+}
+
+// an effect computed by compute passes in place of the fragment shader, see Shader::compute.
+// the passes run in order, rounds times over, and every dispatch reads what the last
+// writing one before it wrote.
+// the module's bindings, all in group 0:
+//   0 texture_2d<f32>        the stage input in the effect's color space
+//   1 texture_2d<f32>        what the previous dispatch wrote, the input for the first
+//   2 texture_storage_2d<rgba16float, write>   where this dispatch writes, every pixel of
+//                            it, for passes that write, see ComputePass::writes
+//   3 uniform Parameters     its struct is prepended to source, see PARAMETERS_WGSL
+//   4 uniform Dispatch       { pass_index, iteration, iterations, round }
+//   5 storage array<u32>, read_write   kept across dispatches, see buffer_size
+// and group 1 is the scene's asset bind group, the lut, the glyph atlas and the brush, see build_pipeline
+pub struct ComputeEffect {
+    pub label: &'static str,
+    pub source: &'static str,
+    pub passes: &'static [ComputePass],
+    // bytes of storage buffer for the stage size, 0 for none. capped to the device's
+    // binding limit, shaders check what they got with arrayLength
+    pub buffer_size: fn(u32, u32) -> u64,
+    // times the passes run in turn, from the parameters, the stage size and the bytes of
+    // storage buffer the effect got. for effects that work on more than the buffer holds
+    // at once a part at a time, 1 for the rest
+    pub rounds: fn(&Parameters, u32, u32, u64) -> u32,
+}
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+struct DispatchParameters {
+    pass_index: u32,
+    iteration: u32,
+    iterations: u32,
+    round: u32,
+}
+
+
+// the pipelines of one compute effect and the textures it ping-pongs between.
+// writing dispatches alternate between a scratch texture and the stage's effect texture,
+// arranged so the last one writes the effect texture the output pass reads
+pub struct ComputeEffectRunner {
+    effect: &'static ComputeEffect,
+    pipelines: Vec<wgpu::ComputePipeline>,
+    // reading the input into the effect texture or the scratch, then the scratch
+    // into the effect texture and the effect texture into the scratch
+    bind_groups: [wgpu::BindGroup; 4],
+    // one DispatchParameters per dispatch, each at its own aligned offset
+    dispatch_buffer: wgpu::Buffer,
+    stride: u32,
+    storage_buffer: wgpu::Buffer,
+    width: u32,
+    height: u32,
+}
+
+impl ComputeEffectRunner {
+    #[allow(clippy::too_many_arguments)]
+    pub fn new(
+        device: &wgpu::Device,
+        effect: &'static ComputeEffect,
+        input_view: &wgpu::TextureView,
+        effect_view: &wgpu::TextureView,
+        parameters_buffer: &wgpu::Buffer,
+        asset_bind_group_layout: &wgpu::BindGroupLayout,
+        width: u32,
+        height: u32,
+    ) -> ComputeEffectRunner {
+        let module = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some(effect.label),
+            source: wgpu::ShaderSource::Wgsl(format!("{PARAMETERS_WGSL}{}", effect.source).into()),
+        });
+
+        let texture_entry = |binding| wgpu::BindGroupLayoutEntry {
+            binding,
+            visibility: wgpu::ShaderStages::COMPUTE,
+            ty: wgpu::BindingType::Texture {
+                multisampled: false,
+                view_dimension: wgpu::TextureViewDimension::D2,
+                sample_type: wgpu::TextureSampleType::Float { filterable: false },
+            },
+            count: None,
+        };
+        let uniform_entry = |binding, has_dynamic_offset, size| wgpu::BindGroupLayoutEntry {
+            binding,
+            visibility: wgpu::ShaderStages::COMPUTE,
+            ty: wgpu::BindingType::Buffer {
+                ty: wgpu::BufferBindingType::Uniform,
+                has_dynamic_offset,
+                min_binding_size: wgpu::BufferSize::new(size as u64),
+            },
+            count: None,
+        };
+        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("Compute Effect Bind Group Layout"),
+            entries: &[
+                texture_entry(0),
+                texture_entry(1),
+                wgpu::BindGroupLayoutEntry {
+                    binding: 2,
+                    visibility: wgpu::ShaderStages::COMPUTE,
+                    ty: wgpu::BindingType::StorageTexture {
+                        access: wgpu::StorageTextureAccess::WriteOnly,
+                        format: pass::TARGET_FORMAT,
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                    },
+                    count: None,
+                },
+                uniform_entry(3, false, std::mem::size_of::<Parameters>()),
+                uniform_entry(4, true, std::mem::size_of::<DispatchParameters>()),
+                wgpu::BindGroupLayoutEntry {
+                    binding: 5,
+                    visibility: wgpu::ShaderStages::COMPUTE,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Storage { read_only: false },
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+            ],
+        });
+
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Compute Effect Pipeline Layout"),
+            bind_group_layouts: &[&bind_group_layout, asset_bind_group_layout],
+            push_constant_ranges: &[],
+        });
+        let pipelines = effect.passes.iter().map(|pass| device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+            label: Some(effect.label),
+            layout: Some(&pipeline_layout),
+            module: &module,
+            entry_point: pass.entry_point,
+        })).collect();
+
+        let stride = (std::mem::size_of::<DispatchParameters>() as u32).next_multiple_of(device.limits().min_uniform_buffer_offset_alignment);
+        let dispatch_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Compute Effect Dispatch Buffer"),
+            size: stride as u64 * MAX_DISPATCHES as u64,
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+
+        let limits = device.limits();
+        let buffer_size = (effect.buffer_size)(width, height)
+            .min(limits.max_storage_buffer_binding_size as u64)
+            .min(limits.max_buffer_size)
+            .max(4) / 4 * 4;
+        let storage_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Compute Effect Storage Buffer"),
+            size: buffer_size,
+            // read back for exports, see read_buffer
+            usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_SRC,
+            mapped_at_creation: false,
+        });
+
+        let scratch_view = pass::create_storage_target(device, "Compute Effect Scratch Texture", width, height)
+            .create_view(&wgpu::TextureViewDescriptor::default());
+        let create_bind_group = |previous: &wgpu::TextureView, output: &wgpu::TextureView| device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Compute Effect Bind Group"),
+            layout: &bind_group_layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::TextureView(input_view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::TextureView(previous),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: wgpu::BindingResource::TextureView(output),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 3,
+                    resource: parameters_buffer.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 4,
+                    resource: wgpu::BindingResource::Buffer(wgpu::BufferBinding {
+                        buffer: &dispatch_buffer,
+                        offset: 0,
+                        size: wgpu::BufferSize::new(std::mem::size_of::<DispatchParameters>() as u64),
+                    }),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 5,
+                    resource: storage_buffer.as_entire_binding(),
+                },
+            ],
+        });
+        let bind_groups = [
+            create_bind_group(input_view, effect_view),
+            create_bind_group(input_view, &scratch_view),
+            create_bind_group(&scratch_view, effect_view),
+            create_bind_group(effect_view, &scratch_view),
+        ];
+
+        ComputeEffectRunner {
+            effect,
+            pipelines,
+            bind_groups,
+            dispatch_buffer,
+            stride,
+            storage_buffer,
+            width,
+            height,
+        }
+    }
+
+    pub fn is_for(&self, effect: &'static ComputeEffect) -> bool {
+        std::ptr::eq(self.effect, effect)
+    }
+
+    // every dispatch of every pass, params are the ones written to the stage's parameters buffer.
+    // the dispatch parameters are written through the queue, so they are all written up front
+    pub fn encode(&self, encoder: &mut wgpu::CommandEncoder, queue: &wgpu::Queue, params: &Parameters, asset_bind_group: &wgpu::BindGroup) {
+        let share = (MAX_DISPATCHES / self.effect.passes.len()) as u32;
+        let iterations: Vec<u32> = self.effect.passes.iter()
+            .map(|pass| (pass.iterations)(params, self.width, self.height).clamp(1, share))
+            .collect();
+        let per_round = iterations.iter().sum::<u32>() as usize;
+        let rounds = (self.effect.rounds)(params, self.width, self.height, self.storage_buffer.size())
+            .clamp(1, (MAX_DISPATCHES / per_round) as u32);
+        let dispatches: Vec<DispatchParameters> = (0..rounds).flat_map(|round| {
+            iterations.iter().enumerate().flat_map(move |(i, &iterations)| {
+                (0..iterations).map(move |iteration| DispatchParameters { pass_index: i as u32, iteration, iterations, round })
+            })
+        }).collect();
+
+        let mut data = vec![0u8; dispatches.len() * self.stride as usize];
+        for (chunk, dispatch) in data.chunks_mut(self.stride as usize).zip(&dispatches) {
+            chunk[..std::mem::size_of::<DispatchParameters>()].copy_from_slice(bytemuck::bytes_of(dispatch));
+        }
+        queue.write_buffer(&self.dispatch_buffer, 0, &data);
+
+        let mut compute_pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
+            label: Some(self.effect.label),
+            timestamp_writes: None,
+        });
+        compute_pass.set_bind_group(1, asset_bind_group, &[]);
+        // writing dispatches from each one on, counted back from the last, which writes the effect texture.
+        // dispatches that don't write bind what the next writing one does
+        let mut writes_left = vec![0; dispatches.len() + 1];
+        for (k, dispatch) in dispatches.iter().enumerate().rev() {
+            writes_left[k] = writes_left[k + 1] + self.effect.passes[dispatch.pass_index as usize].writes as usize;
+        }
+        for (k, dispatch) in dispatches.iter().enumerate() {
+            let pass = &self.effect.passes[dispatch.pass_index as usize];
+            let written = writes_left[k] < writes_left[0];
+            let writes_effect = writes_left[k] % 2 == 1;
+            let bind_group = match (written, writes_effect) {
+                (false, true) => 0,
+                (false, false) => 1,
+                (true, true) => 2,
+                (true, false) => 3,
+            };
+            let (x, y) = match pass.domain {
+                Domain::pixels => (self.width, self.height),
+                Domain::rows => (self.height, 1),
+                Domain::columns => (self.width, 1),
+                Domain::custom(size) => size(params, self.width, self.height),
+            };
+            compute_pass.set_pipeline(&self.pipelines[dispatch.pass_index as usize]);
+            compute_pass.set_bind_group(0, &self.bind_groups[bind_group], &[k as u32 * self.stride]);
+            compute_pass.dispatch_workgroups(x.div_ceil(pass.workgroup_size[0]), y.div_ceil(pass.workgroup_size[1]), 1);
+        }
+    }
+    // what the effect left in its storage buffer after the last render
+    pub fn read_buffer(&self, device: &wgpu::Device, queue: &wgpu::Queue) -> Vec<u32> {
+        let size = self.storage_buffer.size();
+        let staging = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Compute Effect Readback Buffer"),
+            size,
+            usage: wgpu::BufferUsages::MAP_READ | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor { label: Some("Compute Effect Readback Encoder") });
+        encoder.copy_buffer_to_buffer(&self.storage_buffer, 0, &staging, 0, size);
+        queue.submit(Some(encoder.finish()));
+
+        let slice = staging.slice(..);
+        slice.map_async(wgpu::MapMode::Read, |_| {});
+        device.poll(wgpu::Maintain::Wait);
+        let data = bytemuck::cast_slice(&slice.get_mapped_range()).to_vec();
+        staging.unmap();
+        data
+    }
+}
@@ -1,113 +1,1156 @@
 
-use std::path::Component;
-
-use iced_wgpu::core::Font;
+use std::time::{Duration, Instant};
 
 use iced_wgpu::Renderer;
-use iced_widget::{button, column, combo_box, component, container, pick_list, row, slider, text, text_input};
+use iced_widget::{button, checkbox, column, combo_box, container, pick_list, row, text, text_input};
 use iced_winit::core::alignment;
-use iced_winit::core::{Color, Element, Length};
+use iced_winit::core::{Color, Element, Length, Rectangle};
+use iced_winit::core::widget::Id;
+use iced_winit::core::widget::operation::{Focusable, Operation, Outcome};
 use iced_winit::runtime::{Command, Program};
 use iced_widget::Theme;
-use iced_aw::{number_input, style::NumberInputStyles, SelectionList, style::SelectionListStyles};
-use iced_wgpu::wgpu::{self, util::DeviceExt, ShaderModuleDescriptor};
-use image::codecs::png;
-use image::imageops::replace;
+use iced_aw::number_input;
 // use iced::{window, Element};
-use image::Rgba;
 use crate::scene::Parameters;
-use rfd;
-use iced_winit::winit;
+use crate::compute::ComputeEffect;
+use crate::ascii::{self, ASCII, DEFAULT_RAMP};
+use crate::pixel_sort::PIXEL_SORT;
+use crate::brush_strokes::{self, BRUSH_STROKES};
+use crate::superpixels::{self, SUPERPIXELS};
+use crate::timing::Timings;
+use crate::blur;
+use crate::smoothing;
+
+
+
+
+
+// struct ImageLocation {
+//     location: String,
+//     name: String,
+// }
+
+#[allow(non_camel_case_types)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Shader {
+    #[default]
+    none,
+    invert,
+    gaussian_blur,
+    quantization,
+    sobel_edge_detection,
+    difference_of_gaussians_DoG,
+    flow_based_XDoG,
+    edge_direction,
+    bayer_dither,
+    chromatic_aberration,
+    lut,
+    canny_edge_detection,
+    bilateral_filter,
+    guided_filter,
+    tone_mapping,
+    pixel_sort,
+    ascii,
+    halftone,
+    crt,
+    film_grain,
+    noise,
+    brush_strokes,
+    superpixels,
+}
+
+impl Shader {
+    const ALL: [Shader; 23] = [
+        Shader::none,
+        Shader::invert,
+        Shader::gaussian_blur,
+        Shader::quantization,
+        Shader::sobel_edge_detection,
+        Shader::difference_of_gaussians_DoG,
+        Shader::flow_based_XDoG,
+        Shader::edge_direction,
+        Shader::bayer_dither,
+        Shader::chromatic_aberration,
+        Shader::lut,
+        Shader::canny_edge_detection,
+        Shader::bilateral_filter,
+        Shader::guided_filter,
+        Shader::tone_mapping,
+        Shader::pixel_sort,
+        Shader::ascii,
+        Shader::halftone,
+        Shader::crt,
+        Shader::film_grain,
+        Shader::noise,
+        Shader::brush_strokes,
+        Shader::superpixels,
+    ];
+}
+
+impl Shader {
+    pub fn get_index(&self) -> u32 {
+        match self {
+            Shader::none => 100,
+            Shader::invert => 0,
+            Shader::gaussian_blur => 1,
+            Shader::quantization => 2,
+            Shader::sobel_edge_detection => 3,
+            Shader::difference_of_gaussians_DoG => 4,
+            Shader::flow_based_XDoG => 5,
+            Shader::edge_direction => 6,
+            Shader::bayer_dither => 7,
+            Shader::chromatic_aberration => 8,
+            Shader::lut => 9,
+            Shader::canny_edge_detection => 10,
+            Shader::bilateral_filter => 11,
+            Shader::guided_filter => 12,
+            Shader::tone_mapping => 13,
+            // computed, see compute
+            Shader::pixel_sort => 14,
+            Shader::ascii => 15,
+            Shader::halftone => 16,
+            Shader::crt => 17,
+            Shader::film_grain => 18,
+            Shader::noise => 19,
+            Shader::brush_strokes => 20,
+            Shader::superpixels => 21,
+        }
+    }
+
+    // by the name shown in the effect list, for the command line
+    pub fn from_name(name: &str) -> Option<Shader> {
+        Shader::ALL.into_iter().find(|shader| shader.to_string().eq_ignore_ascii_case(name))
+    }
+
+    // effects whose output depends only on the color of the same pixel,
+    // which is what a .cube file can represent
+    pub fn is_pointwise(&self) -> bool {
+        match self {
+            // tone mapping only while auto exposure is off, see can_export_cube
+            Shader::none | Shader::invert | Shader::quantization | Shader::lut | Shader::tone_mapping => true,
+            _ => false,
+        }
+    }
+
+    // the smoothing that runs ahead of the effect. the standalone smoothing effects
+    // always run their filter, the effects that accept a prefilter run the selected one
+    pub fn smoothing(&self, prefilter: Prefilter) -> Prefilter {
+        match self {
+            Shader::bilateral_filter => Prefilter::bilateral,
+            Shader::guided_filter => Prefilter::guided,
+            Shader::quantization | Shader::difference_of_gaussians_DoG | Shader::flow_based_XDoG => prefilter,
+            _ => Prefilter::none,
+        }
+    }
+
+    // the textures the effect reads besides its source, see Inputs.
+    // none of the effects so far does
+    pub fn inputs(&self) -> Inputs {
+        Inputs::NONE
+    }
+
+    // effects computed by compute passes in place of the fragment shader
+    pub fn compute(&self) -> Option<&'static ComputeEffect> {
+        match self {
+            Shader::pixel_sort => Some(&PIXEL_SORT),
+            Shader::ascii => Some(&ASCII),
+            Shader::brush_strokes => Some(&BRUSH_STROKES),
+            Shader::superpixels => Some(&SUPERPIXELS),
+            _ => None,
+        }
+    }
+
+    // the color space the effect's math is written for, the stage converts its input
+    // into this space and the result back. None runs in the working space
+    pub fn color_space(&self) -> Option<ColorSpace> {
+        match self {
+            // thresholds, palettes and dither patterns are tuned on display encoded values
+            Shader::invert | Shader::quantization | Shader::sobel_edge_detection => Some(ColorSpace::display),
+            Shader::difference_of_gaussians_DoG | Shader::flow_based_XDoG | Shader::edge_direction => Some(ColorSpace::display),
+            Shader::bayer_dither | Shader::canny_edge_detection | Shader::bilateral_filter => Some(ColorSpace::display),
+            Shader::pixel_sort | Shader::ascii | Shader::halftone | Shader::crt | Shader::brush_strokes => Some(ColorSpace::display),
+            // superpixels convert display encoded colors to cielab themselves
+            Shader::superpixels => Some(ColorSpace::display),
+            // grain is as visible in the shadows as in the highlights on display encoded values
+            Shader::film_grain | Shader::noise => Some(ColorSpace::display),
+            // .cube files map display encoded colors
+            Shader::lut => Some(ColorSpace::display),
+            // averaging light is only physically right on linear values
+            Shader::gaussian_blur => Some(ColorSpace::linear),
+            // operators take scene linear values and return display linear ones
+            Shader::tone_mapping => Some(ColorSpace::linear),
+            Shader::none | Shader::chromatic_aberration | Shader::guided_filter => None,
+        }
+    }
+
+    pub fn get_parameters<'a>(&self, controls: &'a Controls) -> iced_widget::Container<'a, Message, Theme, Renderer> {
+        match self {
+            Shader::none => container(column![]),
+            Shader::invert => container(column![]),
+            Shader::gaussian_blur => container(column![
+                row![number_input(controls.sigma1, 10.0, move |v| {Message::Sigma1Changed(v)}).step(0.1),text("sigma"),].width(500).spacing(10),
+            ]),
+            Shader::quantization => container(column![
+                prefilter_parameters(controls),
+                vector_parameters(controls),
+            ]),
+            Shader::sobel_edge_detection => container(column![]),
+            Shader::difference_of_gaussians_DoG => container(column![
+                row![number_input(controls.sigma1, 10.0, move |v| {Message::Sigma1Changed(v)}).step(0.1),text("sigma"),].width(500).spacing(10),
+                row![number_input(controls.tau, 0.3, move |v| {Message::TauChanged(v)}).step(0.01),text("tau"),].width(500).spacing(10),
+                prefilter_parameters(controls),
+                vector_parameters(controls),
+            ]),
+            Shader::flow_based_XDoG => container(column![
+                row![number_input(controls.sigma1, 10.0, move |v| {Message::Sigma1Changed(v)}).step(0.1),text("sigma"),].width(500).spacing(10),
+                row![number_input(controls.tau, 0.3, move |v| {Message::TauChanged(v)}).step(0.01),text("tau"),].width(500).spacing(10),
+                row![number_input(controls.gfact, 10.0, move |v| {Message::GFactChanged(v)}).step(0.5),text("gamma"),].width(500).spacing(10),
+                row![number_input(controls.num_gvf_iterations, 30, move |v| {Message::IsFactChanged(v)}).step(1),text("iterations"),].width(500).spacing(10),
+                prefilter_parameters(controls),
+                vector_parameters(controls),
+            ]),
+            Shader::edge_direction => container(column![]),
+            Shader::bayer_dither => container(column![
+                row![number_input(controls.tau, 10.0, move |v| {Message::TauChanged(v)}).step(0.25),text("tau"),].width(500).spacing(10),
+                row![number_input(controls.colors, 128.0, move |v| {Message::ColorsChanged(v)}).step(1.0),text("colors"),].width(500).spacing(10),
+            ]),
+            Shader::chromatic_aberration => container(column![
+                row![number_input(controls.intensity, 10.0, move |v| {Message::IntensityChange(v)}).step(0.001),text("intensity"),number_input(controls.aberration_falloff, 8.0, move |v| {Message::AberrationFalloffChanged(v)}).step(0.1),text("falloff"),].width(500).spacing(10),
+                row![number_input(controls.spectral_samples, 32, move |v| {Message::SpectralSamplesChanged(v)}).step(1),text("wavelengths"),].width(500).spacing(10),
+                row![number_input(controls.distortion_k1, 1.0, move |v| {Message::DistortionK1Changed(v)}).step(0.01),text("k1"),number_input(controls.distortion_k2, 1.0, move |v| {Message::DistortionK2Changed(v)}).step(0.01),text("k2 distortion"),].width(500).spacing(10),
+                row![number_input(controls.lens_center[0], 1.0, move |v| {Message::LensCenterChanged(0, v)}).step(0.01),number_input(controls.lens_center[1], 1.0, move |v| {Message::LensCenterChanged(1, v)}).step(0.01),text("center"),].width(500).spacing(10),
+            ]),
+            Shader::lut => container(column![
+                row![button(if controls.selected_lut.is_empty() { "load lut" } else { controls.selected_lut.split('/').next_back().unwrap() }).on_press(Message::LutChanger())].width(500).spacing(10),
+                row![number_input(controls.lut_intensity, 1.0, move |v| {Message::LutIntensityChanged(v)}).step(0.05),text("intensity"),].width(500).spacing(10),
+            ]),
+            Shader::canny_edge_detection => container(column![
+                row![number_input(controls.sigma1, 10.0, move |v| {Message::Sigma1Changed(v)}).step(0.1),text("sigma"),].width(500).spacing(10),
+                row![number_input(controls.low_threshold, 2.0, move |v| {Message::LowThresholdChanged(v)}).step(0.01),text("low threshold"),].width(500).spacing(10),
+                row![number_input(controls.high_threshold, 2.0, move |v| {Message::HighThresholdChanged(v)}).step(0.01),text("high threshold"),].width(500).spacing(10),
+                row![number_input(controls.num_gvf_iterations, 30, move |v| {Message::IsFactChanged(v)}).step(1),text("hysteresis passes"),].width(500).spacing(10),
+                row![checkbox("overlay on image", controls.canny_overlay).on_toggle(Message::CannyOverlayToggled)].width(500).spacing(10),
+                vector_parameters(controls),
+            ]),
+            Shader::bilateral_filter => container(column![
+                Prefilter::bilateral.get_parameters(controls),
+            ]),
+            Shader::guided_filter => container(column![
+                Prefilter::guided.get_parameters(controls),
+            ]),
+            Shader::tone_mapping => container(column![
+                row![pick_list(&ToneOperator::ALL[..],Some(controls.tone_operator),Message::ToneOperatorSelected,),].width(500).spacing(10),
+                row![number_input(controls.tone_exposure, 10.0, move |v| {Message::ToneExposureChanged(v)}).step(0.1),text("exposure"),].width(500).spacing(10),
+                row![number_input(controls.white_point, 64.0, move |v| {Message::WhitePointChanged(v)}).step(0.25),text("white point"),].width(500).spacing(10),
+                row![number_input(controls.contrast, 3.0, move |v| {Message::ContrastChanged(v)}).step(0.05),text("contrast"),].width(500).spacing(10),
+                row![checkbox("auto exposure", controls.auto_exposure).on_toggle(Message::AutoExposureToggled)].width(500).spacing(10),
+            ]),
+            Shader::pixel_sort => container(column![
+                row![number_input(controls.sort_angle, 360.0, move |v| {Message::SortAngleChanged(v)}).step(1.0),text("angle, 0 along rows and 90 down columns"),].width(500).spacing(10),
+                row![pick_list(&SortKey::ALL[..],Some(controls.sort_span),Message::SortSpanSelected,),text("spans where it is from"),].width(500).spacing(10),
+                row![number_input(controls.sort_low, 1.0, move |v| {Message::SortLowChanged(v)}).step(0.01),text("to"),number_input(controls.sort_high, 1.0, move |v| {Message::SortHighChanged(v)}).step(0.01),].width(500).spacing(10),
+                row![pick_list(&SortKey::ALL[..],Some(controls.sort_key),Message::SortKeySelected,),text("sorted by"),checkbox("descending", controls.sort_descending).on_toggle(Message::SortDescendingToggled),].width(500).spacing(10),
+            ]),
+            Shader::ascii => container(column![
+                row![button(if controls.glyph_font.is_empty() { "load font" } else { controls.glyph_font.split('/').next_back().unwrap() }).on_press(Message::GlyphFontChanger()),button("bundled font").on_press_maybe((!controls.glyph_font.is_empty()).then_some(Message::GlyphFontReset())),].width(500).spacing(10),
+                row![text_input(DEFAULT_RAMP, &controls.glyph_ramp).on_input(Message::GlyphRampChanged),text("ramp, dark to light"),].width(500).spacing(10),
+                row![number_input(controls.glyph_cell, ascii::MAX_CELL_WIDTH, move |v| {Message::GlyphCellChanged(v)}).step(1),text("cell width, cells are twice as tall"),].width(500).spacing(10),
+                row![pick_list(&GlyphColor::ALL[..],Some(controls.glyph_color),Message::GlyphColorSelected,),text("glyph color"),].width(500).spacing(10),
+                row![checkbox("edges", controls.glyph_edges).on_toggle(Message::GlyphEdgesToggled),number_input(controls.glyph_edge_threshold, 4.0, move |v| {Message::GlyphEdgeThresholdChanged(v)}).step(0.05),text("edge threshold"),].width(500).spacing(10),
+                row![checkbox("export text with the image", controls.export_text).on_toggle(Message::ExportTextToggled)].width(500).spacing(10),
+            ]),
+            Shader::halftone => container(column![
+                row![pick_list(&HalftoneMode::ALL[..],Some(controls.halftone_mode),Message::HalftoneModeSelected,),pick_list(&DotShape::ALL[..],Some(controls.dot_shape),Message::DotShapeSelected,),].width(500).spacing(10),
+                row![number_input(controls.halftone_lpi, 600.0, move |v| {Message::HalftoneLpiChanged(v)}).step(5.0),text("lpi at"),number_input(controls.halftone_dpi, 4800.0, move |v| {Message::HalftoneDpiChanged(v)}).step(50.0),text("dpi"),].width(500).spacing(10),
+                if controls.halftone_mode == HalftoneMode::cmyk {
+                    row![
+                        number_input(controls.screen_angles[0], 180.0, move |v| {Message::ScreenAngleChanged(0, v)}).step(7.5),text("c"),
+                        number_input(controls.screen_angles[1], 180.0, move |v| {Message::ScreenAngleChanged(1, v)}).step(7.5),text("m"),
+                        number_input(controls.screen_angles[2], 180.0, move |v| {Message::ScreenAngleChanged(2, v)}).step(7.5),text("y"),
+                        number_input(controls.screen_angles[3], 180.0, move |v| {Message::ScreenAngleChanged(3, v)}).step(7.5),text("k"),
+                    ].width(500).spacing(10)
+                } else {
+                    row![number_input(controls.screen_angles[3], 180.0, move |v| {Message::ScreenAngleChanged(3, v)}).step(7.5),text("screen angle"),].width(500).spacing(10)
+                },
+                row![number_input(controls.dot_gain, 0.5, move |v| {Message::DotGainChanged(v)}).step(0.01),text("dot gain at 50%"),].width(500).spacing(10),
+            ]),
+            Shader::crt => container(column![
+                row![pick_list(&MaskType::ALL[..],Some(controls.crt_mask),Message::MaskTypeSelected,),number_input(controls.mask_strength, 1.0, move |v| {Message::MaskStrengthChanged(v)}).step(0.05),text("mask strength"),].width(500).spacing(10),
+                row![number_input(controls.scanline_size, 32.0, move |v| {Message::ScanlineSizeChanged(v)}).step(0.5),text("line height"),number_input(controls.scanline_strength, 1.0, move |v| {Message::ScanlineStrengthChanged(v)}).step(0.05),text("scanline strength"),].width(500).spacing(10),
+                row![checkbox("interlaced", controls.crt_interlace).on_toggle(Message::InterlaceToggled),pick_list(&ConsolePalette::ALL[..],Some(controls.crt_palette),Message::ConsolePaletteSelected,),].width(500).spacing(10),
+                row![number_input(controls.crt_curvature, 0.5, move |v| {Message::CurvatureChanged(v)}).step(0.01),text("curvature"),].width(500).spacing(10),
+                row![number_input(controls.crt_bloom, 2.0, move |v| {Message::BloomChanged(v)}).step(0.05),text("bloom"),number_input(controls.crt_vignette, 2.0, move |v| {Message::VignetteChanged(v)}).step(0.05),text("vignette"),].width(500).spacing(10),
+            ]),
+            Shader::film_grain => container(column![
+                row![number_input(controls.grain_intensity, 1.0, move |v| {Message::GrainIntensityChanged(v)}).step(0.01),text("intensity"),number_input(controls.grain_size, 16.0, move |v| {Message::GrainSizeChanged(v)}).step(0.1),text("size"),].width(500).spacing(10),
+                row![number_input(controls.grain_chroma, 1.0, move |v| {Message::GrainChromaChanged(v)}).step(0.05),text("chroma"),number_input(controls.grain_response, 8.0, move |v| {Message::GrainResponseChanged(v)}).step(0.1),text("midtone response"),].width(500).spacing(10),
+                row![number_input(controls.grain_seed, u32::MAX, move |v| {Message::GrainSeedChanged(v)}).step(1),text("seed"),checkbox("animate", controls.animate_grain).on_toggle(Message::AnimateGrainToggled),].width(500).spacing(10),
+            ]),
+            Shader::noise => container(column![
+                row![pick_list(&NoiseType::ALL[..],Some(controls.noise_type),Message::NoiseTypeSelected,),].width(500).spacing(10),
+                row![number_input(controls.noise_intensity, 1.0, move |v| {Message::NoiseIntensityChanged(v)}).step(0.01),text("intensity"),number_input(controls.noise_scale, 512.0, move |v| {Message::NoiseScaleChanged(v)}).step(1.0),text("scale"),].width(500).spacing(10),
+                row![number_input(controls.noise_seed, u32::MAX, move |v| {Message::NoiseSeedChanged(v)}).step(1),text("seed"),checkbox("animate", controls.animate_grain).on_toggle(Message::AnimateGrainToggled),].width(500).spacing(10),
+            ]),
+            Shader::brush_strokes => container(column![
+                row![number_input(controls.stroke_width, brush_strokes::MAX_STROKE_WIDTH, move |v| {Message::StrokeWidthChanged(v)}).step(1),text("stroke width"),number_input(controls.stroke_length, brush_strokes::MAX_STROKE_LENGTH, move |v| {Message::StrokeLengthChanged(v)}).step(0.5),text("length, in widths"),].width(500).spacing(10),
+                row![number_input(controls.stroke_layers, brush_strokes::MAX_LAYERS, move |v| {Message::StrokeLayersChanged(v)}).step(1),text("layers, each half as wide"),number_input(controls.stroke_detail, 1.0, move |v| {Message::StrokeDetailChanged(v)}).step(0.01),text("detail"),].width(500).spacing(10),
+                row![checkbox("brush texture", controls.stroke_texture).on_toggle(Message::StrokeTextureToggled),button(if controls.stroke_brush.is_empty() { "load brush" } else { controls.stroke_brush.split('/').next_back().unwrap() }).on_press(Message::StrokeBrushChanger()),button("bundled brush").on_press_maybe((!controls.stroke_brush.is_empty()).then_some(Message::StrokeBrushReset())),].width(500).spacing(10),
+            ]),
+            Shader::superpixels => container(column![
+                row![number_input(controls.superpixel_count, superpixels::MAX_REGIONS, move |v| {Message::SuperpixelCountChanged(v)}).step(50),text("regions"),number_input(controls.superpixel_compactness, 40.0, move |v| {Message::SuperpixelCompactnessChanged(v)}).step(1.0),text("compactness"),].width(500).spacing(10),
+                row![number_input(controls.superpixel_iterations, superpixels::MAX_ITERATIONS, move |v| {Message::SuperpixelIterationsChanged(v)}).step(1),text("iterations"),checkbox("boundaries", controls.superpixel_boundaries).on_toggle(Message::SuperpixelBoundariesToggled),].width(500).spacing(10),
+            ]),
+        }
+    }
+}
+
+// curves the tone mapping effect fits hdr values into display range with
+#[allow(non_camel_case_types)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ToneOperator {
+    #[default]
+    aces,
+    reinhard,
+    agx,
+    hable,
+}
+
+impl ToneOperator {
+    const ALL: [ToneOperator; 4] = [
+        ToneOperator::aces,
+        ToneOperator::reinhard,
+        ToneOperator::agx,
+        ToneOperator::hable,
+    ];
+
+    pub fn get_index(&self) -> u32 {
+        match self {
+            ToneOperator::aces => 0,
+            ToneOperator::reinhard => 1,
+            ToneOperator::agx => 2,
+            ToneOperator::hable => 3,
+        }
+    }
+}
+
+impl std::fmt::Display for ToneOperator {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}",
+            match self {
+                ToneOperator::aces => "aces filmic",
+                ToneOperator::reinhard => "reinhard extended",
+                ToneOperator::agx => "agx",
+                ToneOperator::hable => "hable",
+            }
+        )
+    }
+}
+
+// what pixel sorting thresholds its spans on and sorts them by
+#[allow(non_camel_case_types)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum SortKey {
+    #[default]
+    luminance,
+    hue,
+    saturation,
+}
+
+impl SortKey {
+    const ALL: [SortKey; 3] = [
+        SortKey::luminance,
+        SortKey::hue,
+        SortKey::saturation,
+    ];
+
+    pub fn get_index(&self) -> u32 {
+        match self {
+            SortKey::luminance => 0,
+            SortKey::hue => 1,
+            SortKey::saturation => 2,
+        }
+    }
+}
+
+impl std::fmt::Display for SortKey {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}",
+            match self {
+                SortKey::luminance => "luminance",
+                SortKey::hue => "hue",
+                SortKey::saturation => "saturation",
+            }
+        )
+    }
+}
+
+// what the ascii effect draws its glyphs in, on black
+#[allow(non_camel_case_types)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum GlyphColor {
+    #[default]
+    mono,
+    cell,
+}
+
+impl GlyphColor {
+    const ALL: [GlyphColor; 2] = [
+        GlyphColor::mono,
+        GlyphColor::cell,
+    ];
+
+    pub fn get_index(&self) -> u32 {
+        match self {
+            GlyphColor::mono => 0,
+            GlyphColor::cell => 1,
+        }
+    }
+}
+
+impl std::fmt::Display for GlyphColor {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}",
+            match self {
+                GlyphColor::mono => "white",
+                GlyphColor::cell => "cell color",
+            }
+        )
+    }
+}
+
+// how the halftone effect screens the image. am varies the size of dots on a regular screen,
+// fm places dots of one size by a dither pattern
+#[allow(non_camel_case_types)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum HalftoneMode {
+    #[default]
+    cmyk,
+    am,
+    fm,
+}
+
+impl HalftoneMode {
+    const ALL: [HalftoneMode; 3] = [
+        HalftoneMode::cmyk,
+        HalftoneMode::am,
+        HalftoneMode::fm,
+    ];
+
+    pub fn get_index(&self) -> u32 {
+        match self {
+            HalftoneMode::cmyk => 0,
+            HalftoneMode::am => 1,
+            HalftoneMode::fm => 2,
+        }
+    }
+}
+
+impl std::fmt::Display for HalftoneMode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}",
+            match self {
+                HalftoneMode::cmyk => "cmyk",
+                HalftoneMode::am => "single color am",
+                HalftoneMode::fm => "single color fm",
+            }
+        )
+    }
+}
+
+// the shape of halftone dots as they grow, see spot in fragment.wgsl
+#[allow(non_camel_case_types)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum DotShape {
+    #[default]
+    round,
+    line,
+    ellipse,
+    square,
+}
+
+impl DotShape {
+    const ALL: [DotShape; 4] = [
+        DotShape::round,
+        DotShape::line,
+        DotShape::ellipse,
+        DotShape::square,
+    ];
+
+    pub fn get_index(&self) -> u32 {
+        match self {
+            DotShape::round => 0,
+            DotShape::line => 1,
+            DotShape::ellipse => 2,
+            DotShape::square => 3,
+        }
+    }
+}
+
+impl std::fmt::Display for DotShape {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} dots",
+            match self {
+                DotShape::round => "round",
+                DotShape::line => "line",
+                DotShape::ellipse => "ellipse",
+                DotShape::square => "square",
+            }
+        )
+    }
+}
+
+// the phosphors of a crt, see crt_mask in fragment.wgsl
+#[allow(non_camel_case_types)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum MaskType {
+    none,
+    #[default]
+    aperture_grille,
+    shadow_mask,
+    slot_mask,
+}
+
+impl MaskType {
+    const ALL: [MaskType; 4] = [
+        MaskType::none,
+        MaskType::aperture_grille,
+        MaskType::shadow_mask,
+        MaskType::slot_mask,
+    ];
+
+    pub fn get_index(&self) -> u32 {
+        match self {
+            MaskType::none => 0,
+            MaskType::aperture_grille => 1,
+            MaskType::shadow_mask => 2,
+            MaskType::slot_mask => 3,
+        }
+    }
+}
+
+impl std::fmt::Display for MaskType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}",
+            match self {
+                MaskType::none => "no mask",
+                MaskType::aperture_grille => "aperture grille",
+                MaskType::shadow_mask => "shadow mask",
+                MaskType::slot_mask => "slot mask",
+            }
+        )
+    }
+}
+
+// the colors a console could show, the crt picks the closest one for every pixel
+// like quantization does, see nearest_color in fragment.wgsl
+#[allow(non_camel_case_types)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ConsolePalette {
+    #[default]
+    none,
+    game_boy,
+    cga,
+    nes,
+}
+
+impl ConsolePalette {
+    const ALL: [ConsolePalette; 4] = [
+        ConsolePalette::none,
+        ConsolePalette::game_boy,
+        ConsolePalette::cga,
+        ConsolePalette::nes,
+    ];
+
+    pub fn get_index(&self) -> u32 {
+        match self {
+            ConsolePalette::none => 0,
+            ConsolePalette::game_boy => 1,
+            ConsolePalette::cga => 2,
+            ConsolePalette::nes => 3,
+        }
+    }
+}
+
+impl std::fmt::Display for ConsolePalette {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}",
+            match self {
+                ConsolePalette::none => "full color",
+                ConsolePalette::game_boy => "game boy",
+                ConsolePalette::cga => "cga",
+                ConsolePalette::nes => "nes",
+            }
+        )
+    }
+}
+
+// how an image exported as svg is drawn, see vector.rs
+#[allow(non_camel_case_types)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum VectorPaths {
+    #[default]
+    fill,
+    stroke,
+}
+
+impl VectorPaths {
+    const ALL: [VectorPaths; 2] = [
+        VectorPaths::fill,
+        VectorPaths::stroke,
+    ];
+}
+
+impl std::fmt::Display for VectorPaths {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}",
+            match self {
+                VectorPaths::fill => "filled regions",
+                VectorPaths::stroke => "stroked outlines",
+            }
+        )
+    }
+}
+
+// what the noise effect adds to the image, see noise in fragment.wgsl
+#[allow(non_camel_case_types)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum NoiseType {
+    #[default]
+    white,
+    value,
+    perlin,
+    blue,
+}
+
+impl NoiseType {
+    const ALL: [NoiseType; 4] = [
+        NoiseType::white,
+        NoiseType::value,
+        NoiseType::perlin,
+        NoiseType::blue,
+    ];
+
+    pub fn get_index(&self) -> u32 {
+        match self {
+            NoiseType::white => 0,
+            NoiseType::value => 1,
+            NoiseType::perlin => 2,
+            NoiseType::blue => 3,
+        }
+    }
+}
+
+impl std::fmt::Display for NoiseType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} noise",
+            match self {
+                NoiseType::white => "white",
+                NoiseType::value => "value",
+                NoiseType::perlin => "perlin",
+                NoiseType::blue => "blue",
+            }
+        )
+    }
+}
+
+// edge preserving smoothing that can run ahead of another effect
+#[allow(non_camel_case_types)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Prefilter {
+    #[default]
+    none,
+    bilateral,
+    guided,
+}
+
+impl Prefilter {
+    const ALL: [Prefilter; 3] = [
+        Prefilter::none,
+        Prefilter::bilateral,
+        Prefilter::guided,
+    ];
+}
+
+impl Prefilter {
+    pub fn get_index(&self) -> u32 {
+        match self {
+            Prefilter::none => 0,
+            Prefilter::bilateral => 1,
+            Prefilter::guided => 2,
+        }
+    }
+
+    pub fn get_parameters<'a>(&self, controls: &'a Controls) -> iced_widget::Column<'a, Message, Theme, Renderer> {
+        match self {
+            Prefilter::none => column![],
+            Prefilter::bilateral => column![
+                row![number_input(controls.sigma_space, 16.0, move |v| {Message::SigmaSpaceChanged(v)}).step(0.25),text("spatial sigma"),].width(500).spacing(10),
+                row![number_input(controls.sigma_range, 1.0, move |v| {Message::SigmaRangeChanged(v)}).step(0.01),text("range sigma"),].width(500).spacing(10),
+            ],
+            Prefilter::guided => column![
+                row![number_input(controls.guided_radius, smoothing::MAX_GUIDED_RADIUS as i32, move |v| {Message::GuidedRadiusChanged(v)}).step(1),text("radius"),].width(500).spacing(10),
+                row![number_input(controls.guided_epsilon, 1.0, move |v| {Message::GuidedEpsilonChanged(v)}).step(0.005),text("epsilon"),].width(500).spacing(10),
+            ],
+        }
+    }
+}
+
+impl std::fmt::Display for Prefilter {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}",
+            match self {
+                Prefilter::none => "no prefilter",
+                Prefilter::bilateral => "bilateral prefilter",
+                Prefilter::guided => "guided prefilter",
+            }
+        )
+    }
+}
+
+// textures an effect stage can read as its source
+#[allow(non_camel_case_types)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Input {
+    #[default]
+    original,
+    previous_stage,
+    second_image,
+}
+
+impl Input {
+    const ALL: [Input; 3] = [
+        Input::original,
+        Input::previous_stage,
+        Input::second_image,
+    ];
+
+    // the base stage is the first one, so it has no previous stage to read
+    const BASE: [Input; 2] = [
+        Input::original,
+        Input::second_image,
+    ];
+}
+
+// what an effect reads besides its source, bound for every fragment effect as
+// originalTexture, previousTexture, secondTexture and maskTexture in fragment.wgsl.
+// the stage renders again when an image it declares changes
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Inputs {
+    pub images: &'static [Input],
+    // the stage's mask as painted, read even while masking is off
+    pub mask: bool,
+}
+
+impl Inputs {
+    pub const NONE: Inputs = Inputs { images: &[], mask: false };
+
+    pub fn reads(&self, input: Input) -> bool {
+        self.images.contains(&input)
+    }
+}
+
+impl std::fmt::Display for Input {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}",
+            match self {
+                Input::original => "original image",
+                Input::previous_stage => "base stage",
+                Input::second_image => "second image",
+            }
+        )
+    }
+}
+
+// the space stages hand to each other and blend modes are computed in.
+// linear is linear light with srgb primaries, display is srgb encoded
+#[allow(non_camel_case_types)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ColorSpace {
+    #[default]
+    linear,
+    display,
+}
+
+impl ColorSpace {
+    const ALL: [ColorSpace; 2] = [
+        ColorSpace::linear,
+        ColorSpace::display,
+    ];
+
+    pub fn get_index(&self) -> u32 {
+        match self {
+            ColorSpace::linear => 0,
+            ColorSpace::display => 1,
+        }
+    }
+}
+
+impl std::fmt::Display for ColorSpace {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}",
+            match self {
+                ColorSpace::linear => "linear working space",
+                ColorSpace::display => "display working space",
+            }
+        )
+    }
+}
+
+// how the effect stage is composited over the base stage
+#[allow(non_camel_case_types)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum BlendMode {
+    #[default]
+    normal,
+    multiply,
+    screen,
+    overlay,
+    soft_light,
+    difference,
+    darken,
+    lighten,
+    add,
+}
+
+impl BlendMode {
+    const ALL: [BlendMode; 9] = [
+        BlendMode::normal,
+        BlendMode::multiply,
+        BlendMode::screen,
+        BlendMode::overlay,
+        BlendMode::soft_light,
+        BlendMode::difference,
+        BlendMode::darken,
+        BlendMode::lighten,
+        BlendMode::add,
+    ];
+}
+
+impl BlendMode {
+    pub fn get_index(&self) -> u32 {
+        match self {
+            BlendMode::normal => 0,
+            BlendMode::multiply => 1,
+            BlendMode::screen => 2,
+            BlendMode::overlay => 3,
+            BlendMode::soft_light => 4,
+            BlendMode::difference => 5,
+            BlendMode::darken => 6,
+            BlendMode::lighten => 7,
+            BlendMode::add => 8,
+        }
+    }
+}
+
+impl std::fmt::Display for BlendMode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}",
+            match self {
+                BlendMode::normal => "normal",
+                BlendMode::multiply => "multiply",
+                BlendMode::screen => "screen",
+                BlendMode::overlay => "overlay",
+                BlendMode::soft_light => "soft light",
+                BlendMode::difference => "difference",
+                BlendMode::darken => "darken",
+                BlendMode::lighten => "lighten",
+                BlendMode::add => "add",
+            }
+        )
+    }
+}
+
+// how the viewer fills in pixels when zoomed in past 1:1. zoomed out it always
+// filters across the mip chain
+#[allow(non_camel_case_types)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Magnification {
+    // hard texel edges, for pixel art
+    nearest,
+    #[default]
+    linear,
+    lanczos,
+}
+
+impl Magnification {
+    const ALL: [Magnification; 3] = [
+        Magnification::nearest,
+        Magnification::linear,
+        Magnification::lanczos,
+    ];
+}
+
+impl Magnification {
+    pub fn get_index(&self) -> u32 {
+        match self {
+            Magnification::nearest => 0,
+            Magnification::linear => 1,
+            Magnification::lanczos => 2,
+        }
+    }
+}
+
+impl std::fmt::Display for Magnification {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}",
+            match self {
+                Magnification::nearest => "nearest",
+                Magnification::linear => "linear",
+                Magnification::lanczos => "lanczos",
+            }
+        )
+    }
+}
+
+// which stage's mask the mask controls edit
+#[allow(non_camel_case_types)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum MaskLayer {
+    base,
+    #[default]
+    effect,
+}
+
+impl MaskLayer {
+    const ALL: [MaskLayer; 2] = [
+        MaskLayer::base,
+        MaskLayer::effect,
+    ];
+
+    pub fn get_index(&self) -> usize {
+        match self {
+            MaskLayer::base => 0,
+            MaskLayer::effect => 1,
+        }
+    }
+}
+
+impl std::fmt::Display for MaskLayer {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}",
+            match self {
+                MaskLayer::base => "base mask",
+                MaskLayer::effect => "effect mask",
+            }
+        )
+    }
+}
 
+#[allow(non_camel_case_types)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Brush {
+    #[default]
+    off,
+    paint,
+    erase,
+}
 
+impl Brush {
+    const ALL: [Brush; 3] = [
+        Brush::off,
+        Brush::paint,
+        Brush::erase,
+    ];
+}
 
+impl std::fmt::Display for Brush {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}",
+            match self {
+                Brush::off => "brush off",
+                Brush::paint => "paint",
+                Brush::erase => "erase",
+            }
+        )
+    }
+}
 
+const MAX_RECENT_IMAGES: usize = 8;
 
-use win_screenshot;
+const TOAST_DURATION: Duration = Duration::from_secs(5);
 
+// a message shown over the controls for a few seconds, errors or what an export wrote
+pub struct Toast {
+    text: String,
+    expires: Instant,
+    error: bool,
+}
 
-// struct ImageLocation {
-//     location: String,
-//     name: String,
-// }
+// images opened recently, most recent first, kept between runs in the user's config directory
+fn recent_images_file() -> Option<std::path::PathBuf> {
+    dirs::config_dir().map(|dir| dir.join("rts").join("recent_images.txt"))
+}
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
-pub enum Shader {
-    #[default]
-    none,
-    invert,
-    gaussian_blur,
-    quantization,
-    sobel_edge_detection,
-    difference_of_gaussians_DoG,
-    flow_based_XDoG,
-    edge_direction,
-    bayer_dither,
-    chromatic_aberration
+fn load_recent_images() -> Vec<String> {
+    recent_images_file()
+        .and_then(|file| std::fs::read_to_string(file).ok())
+        .map(|recent| recent.lines().filter(|line| !line.is_empty()).take(MAX_RECENT_IMAGES).map(String::from).collect())
+        .unwrap_or_default()
 }
 
-impl Shader {
-    const ALL: [Shader; 10] = [
-        Shader::none,
-        Shader::invert,
-        Shader::gaussian_blur,
-        Shader::quantization,
-        Shader::sobel_edge_detection,
-        Shader::difference_of_gaussians_DoG,
-        Shader::flow_based_XDoG,
-        Shader::edge_direction,
-        Shader::bayer_dither,
-        Shader::chromatic_aberration,
-    ];
+// an image on the clipboard is written to the cache directory and opened from there like any
+// other file, copied text is opened as a path
+fn paste_image() -> Result<String, String> {
+    let mut clipboard = arboard::Clipboard::new().map_err(|error| error.to_string())?;
+    if let Ok(pasted) = clipboard.get_image() {
+        let image = image::RgbaImage::from_raw(pasted.width as u32, pasted.height as u32, pasted.bytes.into_owned())
+            .ok_or("the clipboard image is incomplete")?;
+        let directory = dirs::cache_dir().unwrap_or_else(std::env::temp_dir).join("rts");
+        std::fs::create_dir_all(&directory).map_err(|error| error.to_string())?;
+        let seconds = std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).map_or(0, |time| time.as_secs());
+        let path = directory.join(format!("pasted-{seconds}.png"));
+        image.save(&path).map_err(|error| error.to_string())?;
+        return Ok(str::replace(&path.to_string_lossy(), '\\', "/"));
+    }
+    clipboard.get_text().ok().as_deref().and_then(image_path).ok_or(String::from("the clipboard holds neither an image nor a path to an image file"))
 }
 
-impl Shader {
-    pub fn get_index(&self) -> u32 {
-        match self {
-            Shader::none => 100,
-            Shader::invert => 0,
-            Shader::gaussian_blur => 1,
-            Shader::quantization => 2,
-            Shader::sobel_edge_detection => 3,
-            Shader::difference_of_gaussians_DoG => 4,
-            Shader::flow_based_XDoG => 5,
-            Shader::edge_direction => 6,
-            Shader::bayer_dither => 7,
-            Shader::chromatic_aberration => 8,
-        }
+// ctrl+v pastes an image unless a text input has focus and takes the paste itself,
+// run over the controls' widgets before Message::PasteImage is sent
+#[derive(Default)]
+pub struct PasteUnlessEditing {
+    editing: bool,
+}
+
+impl Operation<Message> for PasteUnlessEditing {
+    fn container(&mut self, _id: Option<&Id>, _bounds: Rectangle, operate_on_children: &mut dyn FnMut(&mut dyn Operation<Message>)) {
+        operate_on_children(self);
     }
 
-    pub fn get_parameters<'a>(&self, controls: &Controls) -> iced_widget::Container<'a, Message, Theme, Renderer> {
-        match self {
-            Shader::none => container(column![]),
-            Shader::invert => container(column![]),
-            Shader::gaussian_blur => container(column![
-                row![number_input(controls.sigma1, 10.0, move |v| {Message::Sigma1Changed(v)}).step(0.1),text("sigma"),].width(500).spacing(10),
-            ]),
-            Shader::quantization => container(column![]),
-            Shader::sobel_edge_detection => container(column![]),
-            Shader::difference_of_gaussians_DoG => container(column![
-                row![number_input(controls.sigma1, 10.0, move |v| {Message::Sigma1Changed(v)}).step(0.1),text("sigma"),].width(500).spacing(10),
-                row![number_input(controls.tau, 0.3, move |v| {Message::TauChanged(v)}).step(0.01),text("tau"),].width(500).spacing(10),
-            ]),
-            Shader::flow_based_XDoG => container(column![
-                row![number_input(controls.sigma1, 10.0, move |v| {Message::Sigma1Changed(v)}).step(0.1),text("sigma"),].width(500).spacing(10),
-                row![number_input(controls.tau, 0.3, move |v| {Message::TauChanged(v)}).step(0.01),text("tau"),].width(500).spacing(10),
-                row![number_input(controls.gfact, 10.0, move |v| {Message::GFactChanged(v)}).step(0.5),text("gamma"),].width(500).spacing(10),
-                row![number_input(controls.num_gvf_iterations, 30, move |v| {Message::IsFactChanged(v)}).step(1),text("iterations"),].width(500).spacing(10)
-            ]),
-            Shader::edge_direction => container(column![]),
-            Shader::bayer_dither => container(column![
-                row![number_input(controls.tau, 10.0, move |v| {Message::TauChanged(v)}).step(0.25),text("tau"),].width(500).spacing(10),
-                row![number_input(controls.colors, 128.0, move |v| {Message::ColorsChanged(v)}).step(1.0),text("colors"),].width(500).spacing(10),
-            ]),
-            Shader::chromatic_aberration => container(column![
-                row![number_input(controls.intensity, 10.0, move |v| {Message::IntensityChange(v)}).step(0.001),text("intensity"),].width(500).spacing(10),
-            ]),
+    fn focusable(&mut self, state: &mut dyn Focusable, _id: Option<&Id>) {
+        self.editing |= state.is_focused();
+    }
+
+    fn finish(&self) -> Outcome<Message> {
+        match self.editing {
+            true => Outcome::None,
+            false => Outcome::Some(Message::PasteImage()),
         }
     }
 }
 
+// dropped files and clipboard text can be a plain path, a file:// uri or a quoted path
+pub fn image_path(text: &str) -> Option<String> {
+    let line = text.lines().next()?.trim().trim_matches('"');
+    let path = line.strip_prefix("file://").unwrap_or(line);
+    // file:///C:/... keeps a slash in front of the drive letter
+    let path = if path.starts_with('/') && path.get(2..3) == Some(":") { &path[1..] } else { path };
+    let path = str::replace(path, '\\', "/").replace("%20", " ");
+    std::path::Path::new(&path).is_file().then_some(path)
+}
+
+// mask edits that need the scene, picked up after the update like export_cube
+#[allow(non_camel_case_types)]
+#[derive(Debug, Clone, PartialEq)]
+pub enum MaskCommand {
+    load(String),
+    luminance,
+    hue,
+    invert,
+    clear,
+    fill,
+    // regions of the superpixels effect whose average luminance is in range
+    superpixels,
+}
+
+fn mask_parameters<'a>(controls: &'a Controls) -> iced_widget::Column<'a, Message, Theme, Renderer> {
+    let layer = controls.mask_layer.get_index();
+
+    let mut mask_controls = column![
+        row![pick_list(&MaskLayer::ALL[..], Some(controls.mask_layer), Message::MaskLayerSelected),checkbox("enabled", controls.mask_enabled[layer]).on_toggle(Message::MaskToggled),].width(500).spacing(10),
+    ]
+    .spacing(2);
+
+    if controls.mask_enabled[layer] {
+        mask_controls = mask_controls.push(column![
+            row![number_input(controls.mask_feather[layer], 20.0, move |v| {Message::MaskFeatherChanged(v)}).step(0.5),text("feather"),].width(500).spacing(10),
+            row![pick_list(&Brush::ALL[..], Some(controls.brush), Message::BrushSelected),number_input(controls.brush_size, 500.0, move |v| {Message::BrushSizeChanged(v)}).step(1.0),text("size"),number_input(controls.brush_hardness, 1.0, move |v| {Message::BrushHardnessChanged(v)}).step(0.05),text("hardness"),].width(500).spacing(10),
+            row![button("load mask").on_press(Message::MaskLoader()),button("invert").on_press(Message::MaskCommand(MaskCommand::invert)),button("clear").on_press(Message::MaskCommand(MaskCommand::clear)),button("fill").on_press(Message::MaskCommand(MaskCommand::fill)),].width(500).spacing(2),
+            row![number_input(controls.mask_low, 1.0, move |v| {Message::MaskLowChanged(v)}).step(0.05),text("low"),number_input(controls.mask_high, 1.0, move |v| {Message::MaskHighChanged(v)}).step(0.05),text("high"),button("luminance mask").on_press(Message::MaskCommand(MaskCommand::luminance)),].width(500).spacing(10),
+            row![number_input(controls.mask_hue, 360.0, move |v| {Message::MaskHueChanged(v)}).step(5.0),text("hue"),number_input(controls.mask_hue_width, 360.0, move |v| {Message::MaskHueWidthChanged(v)}).step(5.0),text("width"),button("hue mask").on_press(Message::MaskCommand(MaskCommand::hue)),].width(500).spacing(10),
+            row![number_input(controls.mask_softness, 1.0, move |v| {Message::MaskSoftnessChanged(v)}).step(0.01),text("range softness"),button("superpixel mask").on_press(Message::MaskCommand(MaskCommand::superpixels)),].width(500).spacing(10),
+        ].spacing(2));
+    }
+
+    mask_controls
+}
+
+fn prefilter_parameters<'a>(controls: &'a Controls) -> iced_widget::Column<'a, Message, Theme, Renderer> {
+    column![
+        controls.prefilter.get_parameters(controls),
+        row![pick_list(&Prefilter::ALL[..], Some(controls.prefilter), Message::PrefilterSelected)].width(200).spacing(10),
+    ]
+}
+
+// how the effects drawing flat colors are traced when the image is exported as svg
+fn vector_parameters<'a>(controls: &'a Controls) -> iced_widget::Column<'a, Message, Theme, Renderer> {
+    column![
+        row![pick_list(&VectorPaths::ALL[..], Some(controls.vector_paths), Message::VectorPathsSelected),text("when exported as svg"),].width(500).spacing(10),
+        row![number_input(controls.vector_tolerance, 8.0, move |v| {Message::VectorToleranceChanged(v)}).step(0.25),text("tolerance"),number_input(controls.vector_speckle, 100.0, move |v| {Message::VectorSpeckleChanged(v)}).step(1.0),text("speckle"),].width(500).spacing(10),
+        row![number_input(controls.vector_smoothing, 1.34, move |v| {Message::VectorSmoothingChanged(v)}).step(0.05),text("smoothing, 0 keeps every corner"),].width(500).spacing(10),
+    ]
+}
+
+// gpu time per pass, frame time and the resolution it was measured at
+fn timings_overlay<'a>(controls: &'a Controls) -> Element<'a, Message, Theme, Renderer> {
+    let Some(timings) = &controls.timings else {
+        return column![].into();
+    };
+
+    let mut lines = vec![
+        format!("{}x{}, stages {}x{}, {} tiles", timings.width, timings.height, timings.stage_width, timings.stage_height, timings.tiles),
+        format!("frame {:.2} ms, cpu {:.2} ms", timings.frame, timings.cpu),
+    ];
+    if timings.passes.is_empty() {
+        lines.push(String::from("no gpu timestamps on this adapter"));
+    } else {
+        lines.push(format!("gpu {:.2} ms", timings.gpu_total()));
+        lines.extend(timings.passes.iter().map(|(label, ms)| format!("  {label} {ms:.2} ms")));
+    }
+
+    container(column(lines.into_iter().map(|line| text(line).size(14).into())))
+        .padding(8)
+        .width(500)
+        .style(iced_widget::container::Appearance {
+            background: Some(Color::from_rgba(0.0, 0.0, 0.0, 0.6).into()),
+            text_color: Some(Color::WHITE),
+            ..Default::default()
+        })
+        .into()
+}
+
 impl std::fmt::Display for Shader {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         write!(
@@ -123,7 +1166,20 @@ impl std::fmt::Display for Shader {
                 Shader::flow_based_XDoG => "flow based XDoG",
                 Shader::edge_direction => "edge direction",
                 Shader::bayer_dither => "bayer dither",
-                Shader::chromatic_aberration => "chromatic aberration"
+                Shader::chromatic_aberration => "chromatic aberration",
+                Shader::lut => "3D LUT",
+                Shader::canny_edge_detection => "canny edge detection",
+                Shader::bilateral_filter => "bilateral filter",
+                Shader::guided_filter => "guided filter",
+                Shader::tone_mapping => "tone mapping",
+                Shader::pixel_sort => "pixel sort",
+                Shader::ascii => "ascii",
+                Shader::halftone => "halftone",
+                Shader::crt => "crt",
+                Shader::film_grain => "film grain",
+                Shader::noise => "noise",
+                Shader::brush_strokes => "brush strokes",
+                Shader::superpixels => "superpixels",
             }
         )
     }
@@ -133,6 +1189,7 @@ impl std::fmt::Display for Shader {
 pub struct Controls {
     pub background_color: Color,
     pub input: String,
+    #[allow(dead_code)]
     pub shaders: combo_box::State<Shader>,
     pub selected_shader: Option<Shader>,
     pub selected_image: String,
@@ -146,11 +1203,144 @@ pub struct Controls {
     pub enable_xdog: u32,
     pub colors: f32,
     pub intensity: f32,
+    // where the lens's axis meets the image, as a share of its width and height
+    pub lens_center: [f32; 2],
+    // how quickly fringes grow away from the center, as the power of the distance
+    pub aberration_falloff: f32,
+    // brown-conrady radial coefficients, positive for barrel and negative for pincushion distortion
+    pub distortion_k1: f32,
+    pub distortion_k2: f32,
+    // wavelengths sampled across the visible spectrum, 3 for separate red, green and blue
+    pub spectral_samples: u32,
+    // renders with the same seed draw the same grain
+    pub grain_seed: u32,
+    pub grain_intensity: f32,
+    // the size of the clumps of grain in pixels
+    pub grain_size: f32,
+    // 0 for grain in brightness only, 1 for grain in every channel on its own
+    pub grain_chroma: f32,
+    // how much more the midtones grain than the shadows and highlights
+    pub grain_response: f32,
+    pub noise_type: NoiseType,
+    pub noise_seed: u32,
+    // the size of noise's features in pixels
+    pub noise_scale: f32,
+    pub noise_intensity: f32,
+    // grain and noise change every frame, exports keep them still
+    pub animate_grain: bool,
+    // the width of the strokes of the first layer in pixels
+    pub stroke_width: u32,
+    pub stroke_layers: u32,
+    // the length of strokes in stroke widths
+    pub stroke_length: f32,
+    // how far the colors of a cell have to spread for the finer layers to paint it
+    pub stroke_detail: f32,
+    pub stroke_texture: bool,
+    // a brush image with the stroke running left to right, empty for the bundled bristles
+    pub stroke_brush: String,
+    // about how many superpixels cover the image
+    pub superpixel_count: u32,
+    // how much more superpixels keep to a compact shape than follow colors
+    pub superpixel_compactness: f32,
+    pub superpixel_iterations: u32,
+    pub superpixel_boundaries: bool,
+    pub selected_lut: String,
+    pub lut_intensity: f32,
+    pub export_cube: Option<String>,
+    pub low_threshold: f32,
+    pub high_threshold: f32,
+    pub canny_overlay: bool,
+    pub prefilter: Prefilter,
+    pub sigma_space: f32,
+    pub sigma_range: f32,
+    pub guided_radius: i32,
+    pub guided_epsilon: f32,
+    pub base_shader: Shader,
+    pub base_input: Input,
+    pub effect_input: Input,
+    pub second_image: String,
+    pub blend_mode: BlendMode,
+    pub opacity: f32,
+    pub color_space: ColorSpace,
+    pub recent_images: Vec<String>,
+    // image the scene is decoding in the background, the previous one stays on screen
+    pub loading_image: Option<String>,
+    pub toasts: Vec<Toast>,
+    pub tone_operator: ToneOperator,
+    pub tone_exposure: f32,
+    pub white_point: f32,
+    pub contrast: f32,
+    pub auto_exposure: bool,
+    pub sort_angle: f32,
+    // pixels whose sort_span value lies within sort_low..=sort_high form the sorted spans
+    pub sort_span: SortKey,
+    pub sort_low: f32,
+    pub sort_high: f32,
+    pub sort_key: SortKey,
+    pub sort_descending: bool,
+    // a font file, empty for the bundled fira mono
+    pub glyph_font: String,
+    pub glyph_ramp: String,
+    // the width of a cell in pixels
+    pub glyph_cell: u32,
+    pub glyph_color: GlyphColor,
+    pub glyph_edges: bool,
+    pub glyph_edge_threshold: f32,
+    // whether exporting an image also writes the ascii effect's glyphs as text
+    pub export_text: bool,
+    pub halftone_mode: HalftoneMode,
+    pub dot_shape: DotShape,
+    // screen frequency in lines per inch, at the resolution the image is printed at.
+    // exported at its own size the image is the print, a pixel per printer dot
+    pub halftone_lpi: f32,
+    pub halftone_dpi: f32,
+    // degrees of the cyan, magenta, yellow and black screens, black for single color
+    pub screen_angles: [f32; 4],
+    pub dot_gain: f32,
+    pub crt_mask: MaskType,
+    pub crt_palette: ConsolePalette,
+    pub crt_interlace: bool,
+    // how far the corners of the screen bulge out, as a share of its size
+    pub crt_curvature: f32,
+    // the height of a line in pixels of the image, pixels of the crt's picture are as wide
+    pub scanline_size: f32,
+    pub scanline_strength: f32,
+    pub mask_strength: f32,
+    pub crt_bloom: f32,
+    pub crt_vignette: f32,
+    // display only, exports keep the composite as is
+    pub view_exposure: f32,
+    pub compress_highlights: bool,
+    pub magnification: Magnification,
+    // the overlay redraws continuously while it is shown to keep the times current
+    pub show_timings: bool,
+    pub timings: Option<Timings>,
+    pub export_image: Option<String>,
+    // tracing an image exported as svg, in the crt's console palette or the colors of quantization.
+    // tolerance and speckle are in pixels, smoothing is potrace's alphamax
+    pub vector_paths: VectorPaths,
+    pub vector_tolerance: f32,
+    pub vector_speckle: f32,
+    pub vector_smoothing: f32,
+    pub mask_layer: MaskLayer,
+    pub mask_enabled: [bool; 2],
+    pub mask_feather: [f32; 2],
+    pub brush: Brush,
+    pub brush_size: f32,
+    pub brush_hardness: f32,
+    pub mask_low: f32,
+    pub mask_high: f32,
+    pub mask_hue: f32,
+    pub mask_hue_width: f32,
+    pub mask_softness: f32,
+    pub mask_command: Option<MaskCommand>,
 }
 
 #[derive(Debug, Clone)]
 pub enum Message {
+    #[allow(dead_code)]
     BackgroundColorChanged(Color),
+    #[allow(dead_code)]
     InputChanged(String),
     Sigma1Changed(f32),
     TauChanged(f32),
@@ -160,8 +1350,123 @@ pub enum Message {
     ShaderSelected(Shader),
     ImageChanger(),
     TakeScreenshot(),
+    #[allow(dead_code)]
     ToggleUI(),
     IntensityChange(f32),
+    AberrationFalloffChanged(f32),
+    SpectralSamplesChanged(u32),
+    DistortionK1Changed(f32),
+    DistortionK2Changed(f32),
+    LensCenterChanged(usize, f32),
+    GrainSeedChanged(u32),
+    GrainIntensityChanged(f32),
+    GrainSizeChanged(f32),
+    GrainChromaChanged(f32),
+    GrainResponseChanged(f32),
+    NoiseTypeSelected(NoiseType),
+    NoiseSeedChanged(u32),
+    NoiseScaleChanged(f32),
+    NoiseIntensityChanged(f32),
+    AnimateGrainToggled(bool),
+    StrokeWidthChanged(u32),
+    StrokeLayersChanged(u32),
+    StrokeLengthChanged(f32),
+    StrokeDetailChanged(f32),
+    StrokeTextureToggled(bool),
+    StrokeBrushChanger(),
+    StrokeBrushReset(),
+    SuperpixelCountChanged(u32),
+    SuperpixelCompactnessChanged(f32),
+    SuperpixelIterationsChanged(u32),
+    SuperpixelBoundariesToggled(bool),
+    LutChanger(),
+    LutIntensityChanged(f32),
+    ExportCube(),
+    CubeExported(),
+    LowThresholdChanged(f32),
+    HighThresholdChanged(f32),
+    CannyOverlayToggled(bool),
+    PrefilterSelected(Prefilter),
+    SigmaSpaceChanged(f32),
+    SigmaRangeChanged(f32),
+    GuidedRadiusChanged(i32),
+    GuidedEpsilonChanged(f32),
+    BaseShaderSelected(Shader),
+    BaseInputSelected(Input),
+    EffectInputSelected(Input),
+    SecondImageChanger(),
+    BlendModeSelected(BlendMode),
+    ColorSpaceSelected(ColorSpace),
+    OpenImage(String),
+    ImageLoading(String),
+    ImageLoaded(String),
+    ImageFailed(String, String),
+    SecondImageFailed(String),
+    ShowError(String),
+    ShowInfo(String),
+    PasteImage(),
+    DismissToast(usize),
+    ToastsExpired(),
+    ToneOperatorSelected(ToneOperator),
+    ToneExposureChanged(f32),
+    WhitePointChanged(f32),
+    ContrastChanged(f32),
+    AutoExposureToggled(bool),
+    SortAngleChanged(f32),
+    SortSpanSelected(SortKey),
+    SortLowChanged(f32),
+    SortHighChanged(f32),
+    SortKeySelected(SortKey),
+    SortDescendingToggled(bool),
+    GlyphFontChanger(),
+    GlyphFontReset(),
+    GlyphRampChanged(String),
+    GlyphCellChanged(u32),
+    GlyphColorSelected(GlyphColor),
+    GlyphEdgesToggled(bool),
+    GlyphEdgeThresholdChanged(f32),
+    ExportTextToggled(bool),
+    HalftoneModeSelected(HalftoneMode),
+    DotShapeSelected(DotShape),
+    HalftoneLpiChanged(f32),
+    HalftoneDpiChanged(f32),
+    ScreenAngleChanged(usize, f32),
+    DotGainChanged(f32),
+    MaskTypeSelected(MaskType),
+    MaskStrengthChanged(f32),
+    ConsolePaletteSelected(ConsolePalette),
+    InterlaceToggled(bool),
+    CurvatureChanged(f32),
+    ScanlineSizeChanged(f32),
+    ScanlineStrengthChanged(f32),
+    BloomChanged(f32),
+    VignetteChanged(f32),
+    ViewExposureChanged(f32),
+    CompressHighlightsToggled(bool),
+    MagnificationSelected(Magnification),
+    TimingsToggled(bool),
+    TimingsMeasured(Timings),
+    ExportImage(),
+    ImageExported(),
+    VectorPathsSelected(VectorPaths),
+    VectorToleranceChanged(f32),
+    VectorSpeckleChanged(f32),
+    VectorSmoothingChanged(f32),
+    OpacityChanged(f32),
+    MaskLayerSelected(MaskLayer),
+    MaskToggled(bool),
+    MaskFeatherChanged(f32),
+    BrushSelected(Brush),
+    BrushSizeChanged(f32),
+    BrushHardnessChanged(f32),
+    MaskLowChanged(f32),
+    MaskHighChanged(f32),
+    MaskHueChanged(f32),
+    MaskHueWidthChanged(f32),
+    MaskSoftnessChanged(f32),
+    MaskLoader(),
+    MaskCommand(MaskCommand),
+    MaskApplied(),
 }
 
 impl Controls {
@@ -172,7 +1477,7 @@ impl Controls {
         //     shaders.push(format!("{i}"))
         // }
 
-        return Controls {
+        Controls {
             background_color: Color::BLACK,
             input: String::default(),
             shaders: combo_box::State::new(Shader::ALL.to_vec()),
@@ -188,9 +1493,111 @@ impl Controls {
             enable_xdog: 1,
             colors: 32.0,
             intensity: 0.005,
+            lens_center: [0.5, 0.5],
+            aberration_falloff: 1.0,
+            distortion_k1: 0.0,
+            distortion_k2: 0.0,
+            spectral_samples: 7,
+            grain_seed: 0,
+            grain_intensity: 0.1,
+            grain_size: 1.5,
+            grain_chroma: 0.2,
+            grain_response: 1.0,
+            noise_type: NoiseType::white,
+            noise_seed: 0,
+            noise_scale: 1.0,
+            noise_intensity: 0.1,
+            animate_grain: false,
+            stroke_width: 16,
+            stroke_layers: 3,
+            stroke_length: 3.0,
+            stroke_detail: 0.05,
+            stroke_texture: false,
+            stroke_brush: String::new(),
+            superpixel_count: 400,
+            superpixel_compactness: 10.0,
+            superpixel_iterations: 10,
+            superpixel_boundaries: false,
+            selected_lut: String::new(),
+            lut_intensity: 1.0,
+            export_cube: None,
+            low_threshold: 0.1,
+            high_threshold: 0.3,
+            canny_overlay: false,
+            prefilter: Prefilter::none,
+            sigma_space: 3.0,
+            sigma_range: 0.1,
+            guided_radius: 4,
+            guided_epsilon: 0.01,
+            base_shader: Shader::none,
+            base_input: Input::original,
+            effect_input: Input::original,
+            second_image: String::new(),
+            blend_mode: BlendMode::normal,
+            color_space: ColorSpace::linear,
+            recent_images: load_recent_images(),
+            loading_image: None,
+            toasts: Vec::new(),
+            tone_operator: ToneOperator::aces,
+            tone_exposure: 0.0,
+            white_point: 4.0,
+            contrast: 1.0,
+            auto_exposure: false,
+            sort_angle: 0.0,
+            sort_span: SortKey::luminance,
+            sort_low: 0.25,
+            sort_high: 0.8,
+            sort_key: SortKey::luminance,
+            sort_descending: false,
+            glyph_font: String::new(),
+            glyph_ramp: String::from(DEFAULT_RAMP),
+            glyph_cell: 8,
+            glyph_color: GlyphColor::mono,
+            glyph_edges: false,
+            glyph_edge_threshold: 0.5,
+            export_text: false,
+            halftone_mode: HalftoneMode::cmyk,
+            dot_shape: DotShape::round,
+            halftone_lpi: 40.0,
+            halftone_dpi: 300.0,
+            screen_angles: [15.0, 75.0, 0.0, 45.0],
+            dot_gain: 0.0,
+            crt_mask: MaskType::aperture_grille,
+            crt_palette: ConsolePalette::none,
+            crt_interlace: false,
+            crt_curvature: 0.1,
+            scanline_size: 4.0,
+            scanline_strength: 0.7,
+            mask_strength: 0.3,
+            crt_bloom: 0.2,
+            crt_vignette: 0.3,
+            view_exposure: 0.0,
+            compress_highlights: false,
+            magnification: Magnification::linear,
+            show_timings: false,
+            timings: None,
+            export_image: None,
+            vector_paths: VectorPaths::fill,
+            vector_tolerance: 1.0,
+            vector_speckle: 2.0,
+            vector_smoothing: 1.0,
+            opacity: 1.0,
+            mask_layer: MaskLayer::effect,
+            mask_enabled: [false; 2],
+            mask_feather: [2.0; 2],
+            brush: Brush::off,
+            brush_size: 40.0,
+            brush_hardness: 0.5,
+            mask_low: 0.5,
+            mask_high: 1.0,
+            mask_hue: 30.0,
+            mask_hue_width: 60.0,
+            mask_softness: 0.1,
+            mask_command: None,
         }
     }
 
+    #[allow(dead_code)]
     pub fn update_did_change(&mut self, v: bool) {
         self.did_change = v; 
     }
@@ -200,7 +1607,7 @@ impl Controls {
     }
 
     pub fn params(&self) -> Parameters {
-        return Parameters {
+        Parameters {
             sigma1: self.sigma1,
             tau: self.tau,
             gfact: self.gfact,
@@ -210,8 +1617,122 @@ impl Controls {
             colors: self.colors,
             shader_index: self.selected_shader.unwrap().get_index(),
             intensity: self.intensity,
+            lut_intensity: self.lut_intensity,
+            low_threshold: self.low_threshold,
+            high_threshold: self.high_threshold,
+            canny_overlay: self.canny_overlay as u32,
+            sigma_space: self.sigma_space,
+            sigma_range: self.sigma_range,
+            guided_radius: self.guided_radius,
+            guided_epsilon: self.guided_epsilon,
+            prefilter: self.prefilter.get_index(),
+            blend_mode: self.blend_mode.get_index(),
+            opacity: self.opacity,
+            tone_operator: self.tone_operator.get_index(),
+            tone_exposure: self.tone_exposure,
+            white_point: self.white_point,
+            contrast: self.contrast,
+            auto_exposure: self.auto_exposure as u32,
+            sort_angle: self.sort_angle,
+            sort_span: self.sort_span.get_index(),
+            sort_low: self.sort_low,
+            sort_high: self.sort_high,
+            sort_key: self.sort_key.get_index(),
+            sort_descending: self.sort_descending as u32,
+            glyph_cell: self.glyph_cell,
+            glyph_count: ascii::ramp(&self.glyph_ramp).len() as u32,
+            glyph_color: self.glyph_color.get_index(),
+            glyph_edges: self.glyph_edges as u32,
+            glyph_edge_threshold: self.glyph_edge_threshold,
+            // set per tile, see render_stages
+            tile_x: 0,
+            tile_y: 0,
+            halftone_mode: self.halftone_mode.get_index(),
+            dot_shape: self.dot_shape.get_index(),
+            halftone_cell: self.halftone_dpi / self.halftone_lpi.max(1.0),
+            screen_cyan: self.screen_angles[0],
+            screen_magenta: self.screen_angles[1],
+            screen_yellow: self.screen_angles[2],
+            screen_black: self.screen_angles[3],
+            dot_gain: self.dot_gain,
+            // set per tile too
+            image_width: 0,
+            image_height: 0,
+            crt_mask: self.crt_mask.get_index(),
+            crt_palette: self.crt_palette.get_index(),
+            crt_interlace: self.crt_interlace as u32,
+            crt_curvature: self.crt_curvature,
+            scanline_size: self.scanline_size,
+            scanline_strength: self.scanline_strength,
+            mask_strength: self.mask_strength,
+            crt_bloom: self.crt_bloom,
+            crt_vignette: self.crt_vignette,
+            lens_center_x: self.lens_center[0],
+            lens_center_y: self.lens_center[1],
+            aberration_falloff: self.aberration_falloff,
+            distortion_k1: self.distortion_k1,
+            distortion_k2: self.distortion_k2,
+            spectral_samples: self.spectral_samples,
+            // set by the scene while grain animates
+            frame: 0,
+            grain_seed: self.grain_seed,
+            grain_intensity: self.grain_intensity,
+            grain_size: self.grain_size,
+            grain_chroma: self.grain_chroma,
+            grain_response: self.grain_response,
+            noise_type: self.noise_type.get_index(),
+            noise_seed: self.noise_seed,
+            noise_scale: self.noise_scale,
+            noise_intensity: self.noise_intensity,
+            stroke_width: self.stroke_width,
+            stroke_layers: self.stroke_layers,
+            stroke_length: self.stroke_length,
+            stroke_detail: self.stroke_detail,
+            stroke_texture: self.stroke_texture as u32,
+            superpixel_count: self.superpixel_count,
+            superpixel_compactness: self.superpixel_compactness,
+            superpixel_iterations: self.superpixel_iterations,
+            superpixel_boundaries: self.superpixel_boundaries as u32,
         }
     }
+
+    fn show_error(&mut self, text: String) {
+        self.toasts.push(Toast { text, expires: Instant::now() + TOAST_DURATION, error: true });
+    }
+
+    fn show_info(&mut self, text: String) {
+        self.toasts.push(Toast { text, expires: Instant::now() + TOAST_DURATION, error: false });
+    }
+
+    // whether animated grain or noise is on screen, the window redraws continuously while it is
+    pub fn animating(&self) -> bool {
+        let animated = |shader: Shader| matches!(shader, Shader::film_grain | Shader::noise);
+        self.animate_grain && (animated(self.base_shader) || self.selected_shader.is_some_and(animated))
+    }
+
+    // when the event loop has to wake up to clear a toast
+    pub fn next_toast_expiry(&self) -> Option<Instant> {
+        self.toasts.iter().map(|toast| toast.expires).min()
+    }
+
+    fn save_recent_images(&self) {
+        // losing the list is not worth interrupting anyone over
+        if let Some(file) = recent_images_file() {
+            let _ = file.parent().map(std::fs::create_dir_all);
+            let _ = std::fs::write(file, self.recent_images.join("\n"));
+        }
+    }
+
+    // a .cube can only hold the result when every stage is a color transform of the original
+    pub fn can_export_cube(&self) -> bool {
+        self.selected_shader.is_some_and(|shader| shader.is_pointwise())
+            && self.base_shader.is_pointwise()
+            && self.base_input != Input::second_image
+            && self.effect_input != Input::second_image
+            && !self.mask_enabled.contains(&true)
+            // auto exposure depends on the whole image
+            && !(self.auto_exposure && (self.base_shader == Shader::tone_mapping || self.selected_shader == Some(Shader::tone_mapping)))
+    }
 }
 
 impl Program for Controls {
@@ -231,7 +1752,7 @@ impl Program for Controls {
                 self.selected_shader = Some(shader);
             }
             Message::Sigma1Changed(v) => {
-                self.sigma1 = v;
+                self.sigma1 = v.min(blur::MAX_SIGMA);
             }
             Message::TauChanged(v) => {
                 self.tau = v;
@@ -243,10 +1764,10 @@ impl Program for Controls {
                 self.num_gvf_iterations = v;
             }
             Message::ImageChanger() => {
-                println!("IMAGE CHANGER");
-                let dialog = rfd::FileDialog::new().pick_file().unwrap().into_os_string().into_string().unwrap();
-                self.selected_image = str::replace(&dialog, '\\', "/");
-                self.did_change = true;
+                if let Some(path) = rfd::FileDialog::new().pick_file() {
+                    self.selected_image = str::replace(&path.to_string_lossy(), '\\', "/");
+                    self.did_change = true;
+                }
             }
             Message::TakeScreenshot() => {
 
@@ -260,9 +1781,9 @@ impl Program for Controls {
                 
                 let window_buffer = win_screenshot::capture::capture_window(win_screenshot::utils::find_window("real time shaders").unwrap());
                 match window_buffer {
-                    Ok(RgbBuf) => {
-                        image::save_buffer("./render.png", &RgbBuf.pixels, RgbBuf.width, RgbBuf.height, image::ColorType::Rgba8);
-                    } Err(Error) => {
+                    Ok(rgb_buf) => {
+                        let _ = image::save_buffer("./render.png", &rgb_buf.pixels, rgb_buf.width, rgb_buf.height, image::ColorType::Rgba8);
+                    } Err(_) => {
                         println!("could not get window")
                     }
                 }
@@ -276,36 +1797,458 @@ impl Program for Controls {
             Message::IntensityChange(v) => {
                 self.intensity = v;
             }
+            Message::AberrationFalloffChanged(v) => {
+                self.aberration_falloff = v;
+            }
+            Message::SpectralSamplesChanged(v) => {
+                self.spectral_samples = v.clamp(3, 32);
+            }
+            Message::DistortionK1Changed(v) => {
+                self.distortion_k1 = v;
+            }
+            Message::DistortionK2Changed(v) => {
+                self.distortion_k2 = v;
+            }
+            Message::LensCenterChanged(i, v) => {
+                self.lens_center[i] = v;
+            }
+            Message::GrainSeedChanged(v) => {
+                self.grain_seed = v;
+            }
+            Message::GrainIntensityChanged(v) => {
+                self.grain_intensity = v;
+            }
+            Message::GrainSizeChanged(v) => {
+                self.grain_size = v.max(0.1);
+            }
+            Message::GrainChromaChanged(v) => {
+                self.grain_chroma = v;
+            }
+            Message::GrainResponseChanged(v) => {
+                self.grain_response = v;
+            }
+            Message::NoiseTypeSelected(kind) => {
+                self.noise_type = kind;
+            }
+            Message::NoiseSeedChanged(v) => {
+                self.noise_seed = v;
+            }
+            Message::NoiseScaleChanged(v) => {
+                self.noise_scale = v.max(0.1);
+            }
+            Message::NoiseIntensityChanged(v) => {
+                self.noise_intensity = v;
+            }
+            Message::AnimateGrainToggled(v) => {
+                self.animate_grain = v;
+            }
+            Message::StrokeWidthChanged(v) => {
+                self.stroke_width = v.clamp(brush_strokes::MIN_STROKE_WIDTH, brush_strokes::MAX_STROKE_WIDTH);
+            }
+            Message::StrokeLayersChanged(v) => {
+                self.stroke_layers = v.clamp(1, brush_strokes::MAX_LAYERS);
+            }
+            Message::StrokeLengthChanged(v) => {
+                self.stroke_length = v.clamp(1.0, brush_strokes::MAX_STROKE_LENGTH);
+            }
+            Message::StrokeDetailChanged(v) => {
+                self.stroke_detail = v;
+            }
+            Message::StrokeTextureToggled(v) => {
+                self.stroke_texture = v;
+            }
+            Message::StrokeBrushChanger() => {
+                if let Some(path) = rfd::FileDialog::new()
+                    .add_filter("brush", &["png", "jpg", "jpeg", "tif", "tiff"])
+                    .pick_file()
+                {
+                    self.stroke_brush = str::replace(&path.to_string_lossy(), '\\', "/");
+                    self.stroke_texture = true;
+                }
+            }
+            Message::StrokeBrushReset() => {
+                self.stroke_brush = String::new();
+            }
+            Message::SuperpixelCountChanged(v) => {
+                self.superpixel_count = v.clamp(1, superpixels::MAX_REGIONS);
+            }
+            Message::SuperpixelCompactnessChanged(v) => {
+                self.superpixel_compactness = v.max(0.0);
+            }
+            Message::SuperpixelIterationsChanged(v) => {
+                self.superpixel_iterations = v.clamp(1, superpixels::MAX_ITERATIONS);
+            }
+            Message::SuperpixelBoundariesToggled(v) => {
+                self.superpixel_boundaries = v;
+            }
+            Message::LutChanger() => {
+                if let Some(path) = rfd::FileDialog::new()
+                    .add_filter("lut", &["cube", "png", "tif", "tiff"])
+                    .pick_file()
+                {
+                    self.selected_lut = str::replace(&path.to_string_lossy(), '\\', "/");
+                }
+            }
+            Message::LutIntensityChanged(v) => {
+                self.lut_intensity = v;
+            }
+            Message::ExportCube() => {
+                self.export_cube = rfd::FileDialog::new()
+                    .add_filter("cube", &["cube"])
+                    .set_file_name("effect.cube")
+                    .save_file()
+                    .map(|path| str::replace(&path.to_string_lossy(), '\\', "/"));
+            }
+            Message::CubeExported() => {
+                self.export_cube = None;
+            }
+            Message::LowThresholdChanged(v) => {
+                self.low_threshold = v.min(self.high_threshold);
+            }
+            Message::HighThresholdChanged(v) => {
+                self.high_threshold = v.max(self.low_threshold);
+            }
+            Message::CannyOverlayToggled(v) => {
+                self.canny_overlay = v;
+            }
+            Message::PrefilterSelected(prefilter) => {
+                self.prefilter = prefilter;
+            }
+            Message::SigmaSpaceChanged(v) => {
+                self.sigma_space = v;
+            }
+            Message::SigmaRangeChanged(v) => {
+                self.sigma_range = v;
+            }
+            Message::GuidedRadiusChanged(v) => {
+                self.guided_radius = v;
+            }
+            Message::GuidedEpsilonChanged(v) => {
+                self.guided_epsilon = v;
+            }
+            Message::BaseShaderSelected(shader) => {
+                self.base_shader = shader;
+            }
+            Message::BaseInputSelected(input) => {
+                self.base_input = input;
+            }
+            Message::EffectInputSelected(input) => {
+                self.effect_input = input;
+            }
+            Message::SecondImageChanger() => {
+                if let Some(path) = rfd::FileDialog::new().pick_file() {
+                    self.second_image = str::replace(&path.to_string_lossy(), '\\', "/");
+                }
+            }
+            Message::BlendModeSelected(mode) => {
+                self.blend_mode = mode;
+            }
+            Message::OpacityChanged(v) => {
+                self.opacity = v;
+            }
+            Message::ColorSpaceSelected(space) => {
+                self.color_space = space;
+            }
+            Message::OpenImage(path) => {
+                self.selected_image = path;
+                self.did_change = true;
+            }
+            Message::ImageLoading(path) => {
+                self.loading_image = Some(path);
+            }
+            Message::ImageLoaded(path) => {
+                self.loading_image = None;
+                self.recent_images.retain(|recent| *recent != path);
+                self.recent_images.insert(0, path);
+                self.recent_images.truncate(MAX_RECENT_IMAGES);
+                self.save_recent_images();
+            }
+            Message::ImageFailed(path, error) => {
+                self.loading_image = None;
+                self.show_error(format!("could not open {}: {error}", path.split('/').next_back().unwrap()));
+                self.recent_images.retain(|recent| *recent != path);
+                self.save_recent_images();
+                // the scene still shows the last image that loaded
+                if let Some(shown) = self.recent_images.first() {
+                    self.selected_image = shown.clone();
+                }
+            }
+            Message::SecondImageFailed(error) => {
+                self.show_error(format!("could not open second image: {error}"));
+                self.second_image = String::new();
+            }
+            Message::ShowError(error) => {
+                self.show_error(error);
+            }
+            Message::ShowInfo(info) => {
+                self.show_info(info);
+            }
+            Message::PasteImage() => match paste_image() {
+                Ok(path) => {
+                    self.selected_image = path;
+                    self.did_change = true;
+                }
+                Err(error) => self.show_error(error),
+            },
+            Message::DismissToast(index) => {
+                if index < self.toasts.len() {
+                    self.toasts.remove(index);
+                }
+            }
+            Message::ToastsExpired() => {
+                let now = Instant::now();
+                self.toasts.retain(|toast| toast.expires > now);
+            }
+            Message::ToneOperatorSelected(operator) => {
+                self.tone_operator = operator;
+            }
+            Message::ToneExposureChanged(v) => {
+                self.tone_exposure = v;
+            }
+            Message::WhitePointChanged(v) => {
+                self.white_point = v.max(0.1);
+            }
+            Message::ContrastChanged(v) => {
+                self.contrast = v.max(0.1);
+            }
+            Message::AutoExposureToggled(v) => {
+                self.auto_exposure = v;
+            }
+            Message::SortAngleChanged(v) => {
+                self.sort_angle = v;
+            }
+            Message::SortSpanSelected(key) => {
+                self.sort_span = key;
+            }
+            Message::SortLowChanged(v) => {
+                self.sort_low = v;
+            }
+            Message::SortHighChanged(v) => {
+                self.sort_high = v;
+            }
+            Message::SortKeySelected(key) => {
+                self.sort_key = key;
+            }
+            Message::SortDescendingToggled(v) => {
+                self.sort_descending = v;
+            }
+            Message::GlyphFontChanger() => {
+                if let Some(path) = rfd::FileDialog::new()
+                    .add_filter("font", &["ttf", "otf"])
+                    .pick_file()
+                {
+                    self.glyph_font = str::replace(&path.to_string_lossy(), '\\', "/");
+                }
+            }
+            Message::GlyphFontReset() => {
+                self.glyph_font = String::new();
+            }
+            Message::GlyphRampChanged(ramp) => {
+                self.glyph_ramp = ramp;
+            }
+            Message::GlyphCellChanged(v) => {
+                self.glyph_cell = v.clamp(ascii::MIN_CELL_WIDTH, ascii::MAX_CELL_WIDTH);
+            }
+            Message::GlyphColorSelected(color) => {
+                self.glyph_color = color;
+            }
+            Message::GlyphEdgesToggled(v) => {
+                self.glyph_edges = v;
+            }
+            Message::GlyphEdgeThresholdChanged(v) => {
+                self.glyph_edge_threshold = v;
+            }
+            Message::ExportTextToggled(v) => {
+                self.export_text = v;
+            }
+            Message::HalftoneModeSelected(mode) => {
+                self.halftone_mode = mode;
+            }
+            Message::DotShapeSelected(shape) => {
+                self.dot_shape = shape;
+            }
+            Message::HalftoneLpiChanged(v) => {
+                self.halftone_lpi = v.max(1.0);
+            }
+            Message::HalftoneDpiChanged(v) => {
+                self.halftone_dpi = v.max(1.0);
+            }
+            Message::ScreenAngleChanged(i, v) => {
+                self.screen_angles[i] = v;
+            }
+            Message::DotGainChanged(v) => {
+                self.dot_gain = v;
+            }
+            Message::MaskTypeSelected(mask) => {
+                self.crt_mask = mask;
+            }
+            Message::MaskStrengthChanged(v) => {
+                self.mask_strength = v;
+            }
+            Message::ConsolePaletteSelected(palette) => {
+                self.crt_palette = palette;
+            }
+            Message::InterlaceToggled(v) => {
+                self.crt_interlace = v;
+            }
+            Message::CurvatureChanged(v) => {
+                self.crt_curvature = v;
+            }
+            // the bloom blurs at twice the line height
+            Message::ScanlineSizeChanged(v) => {
+                self.scanline_size = v.clamp(1.0, blur::MAX_SIGMA / 2.0);
+            }
+            Message::ScanlineStrengthChanged(v) => {
+                self.scanline_strength = v;
+            }
+            Message::BloomChanged(v) => {
+                self.crt_bloom = v;
+            }
+            Message::VignetteChanged(v) => {
+                self.crt_vignette = v;
+            }
+            Message::ViewExposureChanged(v) => {
+                self.view_exposure = v;
+            }
+            Message::CompressHighlightsToggled(v) => {
+                self.compress_highlights = v;
+            }
+            Message::MagnificationSelected(magnification) => {
+                self.magnification = magnification;
+            }
+            Message::TimingsToggled(v) => {
+                self.show_timings = v;
+                self.timings = None;
+            }
+            Message::TimingsMeasured(timings) => {
+                self.timings = self.show_timings.then_some(timings);
+            }
+            Message::ExportImage() => {
+                self.export_image = rfd::FileDialog::new()
+                    .add_filter("png (16 bit)", &["png"])
+                    .add_filter("tiff (16 bit)", &["tif", "tiff"])
+                    .add_filter("openexr (float)", &["exr"])
+                    .add_filter("svg (traced)", &["svg"])
+                    .set_file_name("render.png")
+                    .save_file()
+                    .map(|path| str::replace(&path.to_string_lossy(), '\\', "/"));
+            }
+            Message::ImageExported() => {
+                self.export_image = None;
+            }
+            Message::VectorPathsSelected(paths) => {
+                self.vector_paths = paths;
+            }
+            Message::VectorToleranceChanged(v) => {
+                self.vector_tolerance = v.max(0.0);
+            }
+            Message::VectorSpeckleChanged(v) => {
+                self.vector_speckle = v.max(0.0);
+            }
+            // past 4/3 every vertex is smoothed already
+            Message::VectorSmoothingChanged(v) => {
+                self.vector_smoothing = v.clamp(0.0, 4.0 / 3.0);
+            }
+            Message::MaskLayerSelected(layer) => {
+                self.mask_layer = layer;
+            }
+            Message::MaskToggled(v) => {
+                self.mask_enabled[self.mask_layer.get_index()] = v;
+            }
+            Message::MaskFeatherChanged(v) => {
+                self.mask_feather[self.mask_layer.get_index()] = v.min(blur::MAX_SIGMA);
+            }
+            Message::BrushSelected(brush) => {
+                self.brush = brush;
+            }
+            Message::BrushSizeChanged(v) => {
+                self.brush_size = v;
+            }
+            Message::BrushHardnessChanged(v) => {
+                self.brush_hardness = v;
+            }
+            Message::MaskLowChanged(v) => {
+                self.mask_low = v.min(self.mask_high);
+            }
+            Message::MaskHighChanged(v) => {
+                self.mask_high = v.max(self.mask_low);
+            }
+            Message::MaskHueChanged(v) => {
+                self.mask_hue = v;
+            }
+            Message::MaskHueWidthChanged(v) => {
+                self.mask_hue_width = v;
+            }
+            Message::MaskSoftnessChanged(v) => {
+                self.mask_softness = v;
+            }
+            Message::MaskLoader() => {
+                if let Some(path) = rfd::FileDialog::new().pick_file() {
+                    self.mask_command = Some(MaskCommand::load(str::replace(&path.to_string_lossy(), '\\', "/")));
+                }
+            }
+            Message::MaskCommand(command) => {
+                self.mask_command = Some(command);
+            }
+            Message::MaskApplied() => {
+                self.mask_command = None;
+            }
         }
 
         Command::none()
     }
 
-    fn view(&self) -> Element<Message, Theme, Renderer> {
-        let background_color = self.background_color;
-        let sigma1 = self.sigma1;
-        let tau = self.tau;
-        let gfact = self.gfact;
-        let epsilon = self.epsilon;
-        let num_gvf_iterations = self.num_gvf_iterations;
-        let enable_xdog = self.enable_xdog;
+    fn view(&self) -> Element<'_, Message, Theme, Renderer> {
         let selected_shader = self.selected_shader;
         // let selected_image = self.selected_image;
 
+        let composite_controls = column![
+            row![pick_list(&Shader::ALL[..],Some(self.base_shader),Message::BaseShaderSelected,),pick_list(&Input::BASE[..],Some(self.base_input),Message::BaseInputSelected,),text("base"),].width(500).spacing(10),
+            row![pick_list(&BlendMode::ALL[..],Some(self.blend_mode),Message::BlendModeSelected,),number_input(self.opacity, 1.0, move |v| {Message::OpacityChanged(v)}).step(0.05),text("opacity"),].width(500).spacing(10),
+            row![pick_list(&Input::ALL[..],Some(self.effect_input),Message::EffectInputSelected,),text("effect input"),].width(500).spacing(10),
+            row![pick_list(&ColorSpace::ALL[..],Some(self.color_space),Message::ColorSpaceSelected,),].width(500).spacing(10),
+            row![number_input(self.view_exposure, 10.0, move |v| {Message::ViewExposureChanged(v)}).step(0.1),text("view exposure"),checkbox("compress highlights", self.compress_highlights).on_toggle(Message::CompressHighlightsToggled),].width(500).spacing(10),
+            row![pick_list(&Magnification::ALL[..],Some(self.magnification),Message::MagnificationSelected,),text("zoomed in past 1:1"),checkbox("timings", self.show_timings).on_toggle(Message::TimingsToggled),].width(500).spacing(10),
+        ]
+        .width(500)
+        .spacing(2);
+
         let shader_controls = column![
+            composite_controls,
+            mask_parameters(self),
             selected_shader.unwrap().get_parameters(self),
             row![pick_list(&Shader::ALL[..],self.selected_shader,Message::ShaderSelected,)].width(200).spacing(10),
         ]
         .width(500)
         .spacing(2);
-        let c = self.selected_image.split('/').last().unwrap();
+        let c = match &self.loading_image {
+            Some(path) => format!("loading {}...", path.split('/').next_back().unwrap()),
+            None => self.selected_image.split('/').next_back().unwrap().to_string(),
+        };
+
+        let second = if self.second_image.is_empty() { "load second image" } else { self.second_image.split('/').next_back().unwrap() };
 
-        let image_loader = row![button("save").on_press(Message::TakeScreenshot()),button(c).on_press(Message::ImageChanger())].width(500).spacing(2);
+        let export_cube = button("export .cube").on_press_maybe(self.can_export_cube().then_some(Message::ExportCube()));
+        let image_loader = row![button("save").on_press(Message::TakeScreenshot()),button(text(c)).on_press(Message::ImageChanger()),button(second).on_press(Message::SecondImageChanger()),button("export image").on_press(Message::ExportImage()),export_cube].width(500).spacing(2);
+        let recent_images = row![pick_list(&self.recent_images[..], None::<String>, Message::OpenImage).placeholder("recent images"),text("drop an image or paste (ctrl+v) one or its path to open it"),].width(500).spacing(10);
+
+        let toasts = column(self.toasts.iter().enumerate().map(|(i, toast)| {
+            container(row![text(&toast.text).width(Length::Fill),button("x").on_press(Message::DismissToast(i)),].spacing(10))
+                .padding(8)
+                .style(iced_widget::container::Appearance {
+                    background: Some(if toast.error { Color::from_rgb(0.5, 0.1, 0.1) } else { Color::from_rgb(0.1, 0.3, 0.15) }.into()),
+                    text_color: Some(Color::WHITE),
+                    ..Default::default()
+                })
+                .into()
+        }))
+        .width(500)
+        .spacing(4);
         // ,button("toggle ui").on_press(Message::ToggleUI())
         if self.show_ui {
             container(
                 column![
-                container(column![image_loader].spacing(10))
+                container(column![image_loader, recent_images, toasts, timings_overlay(self)].spacing(10))
                     .padding(10)
                     .height(Length::Fill)
                     .align_y(alignment::Vertical::Top),
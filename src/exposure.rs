@@ -0,0 +1,207 @@
+use iced_wgpu::wgpu;
+use crate::tiles::Region;
+
+
+const WORKGROUP_SIZE: u32 = 16;
+const HISTOGRAM_BINS: u64 = 256;
+
+// log2 luminance range the histogram covers, 1/1024 to 64
+const MIN_LOG_LUMINANCE: f32 = -10.0;
+const LOG_LUMINANCE_RANGE: f32 = 16.0;
+
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+struct ExposureParameters {
+    min_log_luminance: f32,
+    log_luminance_range: f32,
+    _padding: [f32; 2],
+    // pixels of the input counted into the histogram
+    region: [u32; 4],
+}
+
+
+// average scene luminance of a linear input for auto exposure:
+// a log luminance histogram built per workgroup tile -> a single workgroup reducing it to its mean.
+// the result stays on the gpu in a 1x1 texture the tone mapping effect reads.
+// the histogram keeps counting until it is averaged, so an image split into
+// tiles (see TileGrid) is measured as a whole by counting each tile's core in turn
+pub struct AutoExposure {
+    histogram_pipeline: wgpu::ComputePipeline,
+    average_pipeline: wgpu::ComputePipeline,
+    bind_group: wgpu::BindGroup,
+    parameters_buffer: wgpu::Buffer,
+    average_view: wgpu::TextureView,
+    width: u32,
+    height: u32,
+}
+
+impl AutoExposure {
+    pub fn new(device: &wgpu::Device, input: &wgpu::TextureView, width: u32, height: u32) -> AutoExposure {
+        let module = device.create_shader_module(wgpu::include_wgsl!("../shaders/exposure.wgsl"));
+
+        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("Exposure Bind Group Layout"),
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::COMPUTE,
+                    ty: wgpu::BindingType::Texture {
+                        multisampled: false,
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        sample_type: wgpu::TextureSampleType::Float { filterable: false },
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStages::COMPUTE,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Storage { read_only: false },
+                        has_dynamic_offset: false,
+                        min_binding_size: None
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 2,
+                    visibility: wgpu::ShaderStages::COMPUTE,
+                    ty: wgpu::BindingType::StorageTexture {
+                        access: wgpu::StorageTextureAccess::WriteOnly,
+                        format: wgpu::TextureFormat::R32Float,
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 3,
+                    visibility: wgpu::ShaderStages::COMPUTE,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None
+                    },
+                    count: None,
+                },
+            ],
+        });
+
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Exposure Pipeline Layout"),
+            bind_group_layouts: &[&bind_group_layout],
+            push_constant_ranges: &[],
+        });
+        let create_pipeline = |entry_point| {
+            device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+                label: Some("Exposure Pipeline"),
+                layout: Some(&pipeline_layout),
+                module: &module,
+                entry_point,
+            })
+        };
+        let histogram_pipeline = create_pipeline("histogram_main");
+        let average_pipeline = create_pipeline("average_main");
+
+        // starts zeroed, average_main clears it again after reading
+        let histogram_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Exposure Histogram Buffer"),
+            size: HISTOGRAM_BINS * 4,
+            usage: wgpu::BufferUsages::STORAGE,
+            mapped_at_creation: false,
+        });
+
+        let parameters_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Exposure Parameters Buffer"),
+            size: std::mem::size_of::<ExposureParameters>() as wgpu::BufferAddress,
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+
+        let average_view = device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("Exposure Average Texture"),
+            size: wgpu::Extent3d {
+                width: 1,
+                height: 1,
+                depth_or_array_layers: 1
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: wgpu::TextureFormat::R32Float,
+            usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::STORAGE_BINDING,
+            view_formats: &[],
+        }).create_view(&wgpu::TextureViewDescriptor::default());
+
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Exposure Bind Group"),
+            layout: &bind_group_layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::TextureView(input),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: histogram_buffer.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: wgpu::BindingResource::TextureView(&average_view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 3,
+                    resource: parameters_buffer.as_entire_binding(),
+                },
+            ],
+        });
+
+        AutoExposure {
+            histogram_pipeline,
+            average_pipeline,
+            bind_group,
+            parameters_buffer,
+            average_view,
+            width,
+            height,
+        }
+    }
+
+    pub fn average_view(&self) -> &wgpu::TextureView {
+        &self.average_view
+    }
+
+    pub fn encode(&self, encoder: &mut wgpu::CommandEncoder, queue: &wgpu::Queue) {
+        self.encode_histogram(encoder, queue, Region { x: 0, y: 0, width: self.width, height: self.height });
+        self.encode_average(encoder);
+    }
+
+    // counts region of the input. the parameters are written through the queue,
+    // so each region needs its own submission
+    pub fn encode_histogram(&self, encoder: &mut wgpu::CommandEncoder, queue: &wgpu::Queue, region: Region) {
+        queue.write_buffer(&self.parameters_buffer, 0, bytemuck::cast_slice(&[ExposureParameters {
+            min_log_luminance: MIN_LOG_LUMINANCE,
+            log_luminance_range: LOG_LUMINANCE_RANGE,
+            _padding: [0.0; 2],
+            region: [region.x, region.y, region.width, region.height],
+        }]));
+
+        let mut compute_pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
+            label: Some("Exposure Histogram Pass"),
+            timestamp_writes: None,
+        });
+        compute_pass.set_bind_group(0, &self.bind_group, &[]);
+        compute_pass.set_pipeline(&self.histogram_pipeline);
+        compute_pass.dispatch_workgroups(region.width.div_ceil(WORKGROUP_SIZE), region.height.div_ceil(WORKGROUP_SIZE), 1);
+    }
+
+    // reduces everything counted since the last average
+    pub fn encode_average(&self, encoder: &mut wgpu::CommandEncoder) {
+        let mut compute_pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
+            label: Some("Exposure Average Pass"),
+            timestamp_writes: None,
+        });
+        compute_pass.set_bind_group(0, &self.bind_group, &[]);
+        compute_pass.set_pipeline(&self.average_pipeline);
+        compute_pass.dispatch_workgroups(1, 1, 1);
+    }
+}
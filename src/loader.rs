@@ -0,0 +1,79 @@
+use std::sync::{mpsc, Arc};
+use iced_wgpu::wgpu;
+use image::RgbaImage;
+use crate::source::{self, Source};
+
+
+// which input a finished load replaces
+#[allow(non_camel_case_types)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Slot {
+    image,
+    second_image,
+}
+
+pub struct Decoded {
+    pub source: Source,
+    // 8 bit display encoded copy for generating masks
+    pub preview: RgbaImage,
+}
+
+pub struct Loaded {
+    pub slot: Slot,
+    pub path: String,
+    pub result: Result<Decoded, String>,
+}
+
+
+// decodes images and uploads their textures on worker threads so the render loop
+// keeps drawing the current image. finished loads wait in a channel until the scene
+// picks them up, wake tells the event loop to redraw once one is ready
+pub struct Loader {
+    device: Arc<wgpu::Device>,
+    queue: Arc<wgpu::Queue>,
+    sender: mpsc::Sender<Loaded>,
+    receiver: mpsc::Receiver<Loaded>,
+    wake: Arc<dyn Fn() + Send + Sync>,
+}
+
+impl Loader {
+    pub fn new(device: Arc<wgpu::Device>, queue: Arc<wgpu::Queue>, wake: impl Fn() + Send + Sync + 'static) -> Loader {
+        let (sender, receiver) = mpsc::channel();
+        Loader {
+            device,
+            queue,
+            sender,
+            receiver,
+            wake: Arc::new(wake),
+        }
+    }
+
+    // size resamples the image, the second image follows the size of the first
+    pub fn load(&self, slot: Slot, path: String, size: Option<(u32, u32)>) {
+        let device = self.device.clone();
+        let queue = self.queue.clone();
+        let sender = self.sender.clone();
+        let wake = self.wake.clone();
+
+        std::thread::spawn(move || {
+            let result = image::open(&path).map_err(|error| error.to_string()).map(|image| {
+                let image = match size {
+                    Some((width, height)) => source::resize(&image, width, height),
+                    None => image,
+                };
+                Decoded {
+                    source: Source::new(&device, &queue, &image),
+                    preview: source::preview(&image),
+                }
+            });
+            // the receiver only goes away when the app closes
+            if sender.send(Loaded { slot, path, result }).is_ok() {
+                wake();
+            }
+        });
+    }
+
+    pub fn try_recv(&self) -> Option<Loaded> {
+        self.receiver.try_recv().ok()
+    }
+}
@@ -0,0 +1,259 @@
+use iced_wgpu::wgpu;
+use half::f16;
+
+
+// 3D color lookup table
+// entries are stored red-fastest, then green, then blue (the .cube order)
+#[derive(Clone, Debug)]
+pub struct Lut {
+    pub title: String,
+    pub size: u32,
+    pub data: Vec<[f32; 3]>,
+}
+
+impl Lut {
+    pub fn identity(size: u32) -> Lut {
+        let mut data = Vec::with_capacity((size * size * size) as usize);
+        let scale = (size - 1) as f32;
+
+        for b in 0..size {
+            for g in 0..size {
+                for r in 0..size {
+                    data.push([r as f32 / scale, g as f32 / scale, b as f32 / scale]);
+                }
+            }
+        }
+
+        Lut {
+            title: String::from("identity"),
+            size,
+            data,
+        }
+    }
+
+    // opens either a .cube file or a hald clut image (anything the image crate reads)
+    pub fn open(path: &str) -> Result<Lut, String> {
+        if path.to_lowercase().ends_with(".cube") {
+            let text = std::fs::read_to_string(path).map_err(|e| format!("could not read {path}: {e}"))?;
+            Lut::parse_cube(&text)
+        } else {
+            let img = image::open(path).map_err(|e| format!("could not open {path}: {e}"))?;
+            Lut::from_hald(&img.to_rgb32f())
+        }
+    }
+
+    pub fn parse_cube(text: &str) -> Result<Lut, String> {
+        let mut title = String::new();
+        let mut size: Option<u32> = None;
+        let mut data: Vec<[f32; 3]> = Vec::new();
+
+        for (number, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let mut words = line.split_whitespace();
+            let keyword = words.next().unwrap();
+
+            match keyword {
+                "TITLE" => {
+                    title = line["TITLE".len()..].trim().trim_matches('"').to_string();
+                }
+                "LUT_3D_SIZE" => {
+                    let n = words.next()
+                        .and_then(|w| w.parse::<u32>().ok())
+                        .ok_or(format!("line {}: invalid LUT_3D_SIZE", number + 1))?;
+                    if !(2..=256).contains(&n) {
+                        return Err(format!("line {}: LUT_3D_SIZE {n} is out of range (2..=256)", number + 1));
+                    }
+                    size = Some(n);
+                }
+                "LUT_1D_SIZE" => {
+                    return Err(String::from("1D LUTs are not supported"));
+                }
+                "DOMAIN_MIN" | "DOMAIN_MAX" | "LUT_3D_INPUT_RANGE" => {
+                    let expected: &[f32] = match keyword {
+                        "DOMAIN_MIN" => &[0.0, 0.0, 0.0],
+                        "DOMAIN_MAX" => &[1.0, 1.0, 1.0],
+                        _ => &[0.0, 1.0],
+                    };
+                    let values: Vec<f32> = words.filter_map(|w| w.parse::<f32>().ok()).collect();
+                    if values != expected {
+                        return Err(format!("line {}: only a 0..1 input domain is supported", number + 1));
+                    }
+                }
+                _ => {
+                    let values: Vec<f32> = line
+                        .split_whitespace()
+                        .map(|w| w.parse::<f32>())
+                        .collect::<Result<_, _>>()
+                        .map_err(|_| format!("line {}: unknown keyword or bad entry \"{line}\"", number + 1))?;
+                    if values.len() != 3 {
+                        return Err(format!("line {}: expected 3 values, found {}", number + 1, values.len()));
+                    }
+                    data.push([values[0], values[1], values[2]]);
+                }
+            }
+        }
+
+        let size = size.ok_or(String::from("missing LUT_3D_SIZE"))?;
+        let expected = (size * size * size) as usize;
+        if data.len() != expected {
+            return Err(format!("expected {expected} entries for size {size}, found {}", data.len()));
+        }
+
+        Ok(Lut { title, size, data })
+    }
+
+    // a hald clut of level L is an L^3 x L^3 image holding an L^2 sized cube,
+    // with pixels in the same red-fastest order as a .cube file
+    pub fn from_hald(img: &image::Rgb32FImage) -> Result<Lut, String> {
+        let (width, height) = img.dimensions();
+        let level = (width as f32).cbrt().round() as u32;
+
+        if width != height || level * level * level != width || level < 2 {
+            return Err(format!("{width}x{height} is not a valid hald clut size"));
+        }
+
+        let data = img.pixels().map(|p| p.0).collect();
+
+        Ok(Lut {
+            title: String::from("hald clut"),
+            size: level * level,
+            data,
+        })
+    }
+
+    pub fn to_cube(&self) -> String {
+        let mut out = String::new();
+
+        out.push_str(&format!("TITLE \"{}\"\n", self.title));
+        out.push_str("# exported by rts\n");
+        out.push_str(&format!("LUT_3D_SIZE {}\n", self.size));
+        out.push_str("DOMAIN_MIN 0.0 0.0 0.0\n");
+        out.push_str("DOMAIN_MAX 1.0 1.0 1.0\n");
+
+        for [r, g, b] in &self.data {
+            out.push_str(&format!("{r:.6} {g:.6} {b:.6}\n"));
+        }
+
+        out
+    }
+
+    pub fn save_cube(&self, path: &str) -> Result<(), String> {
+        std::fs::write(path, self.to_cube()).map_err(|e| format!("could not write {path}: {e}"))
+    }
+
+    // uploads the table as an rgba16float 3D texture so it can be sampled trilinearly
+    pub fn create_texture(&self, device: &wgpu::Device, queue: &wgpu::Queue) -> wgpu::Texture {
+        let size = wgpu::Extent3d {
+            width: self.size,
+            height: self.size,
+            depth_or_array_layers: self.size,
+        };
+
+        let texture = device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("LUT Texture"),
+            size,
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D3,
+            format: wgpu::TextureFormat::Rgba16Float,
+            usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
+            view_formats: &[],
+        });
+
+        let texels: Vec<u16> = self.data
+            .iter()
+            .flat_map(|[r, g, b]| [*r, *g, *b, 1.0])
+            .map(|v| f16::from_f32(v).to_bits())
+            .collect();
+
+        queue.write_texture(
+            wgpu::ImageCopyTexture {
+                texture: &texture,
+                mip_level: 0,
+                origin: wgpu::Origin3d::ZERO,
+                aspect: wgpu::TextureAspect::All,
+            },
+            bytemuck::cast_slice(&texels),
+            wgpu::ImageDataLayout {
+                offset: 0,
+                bytes_per_row: Some(8 * self.size),
+                rows_per_image: Some(self.size),
+            },
+            size,
+        );
+
+        texture
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Lut;
+
+    // a size 2 table with a comment and the optional header lines around it
+    const SIZE_2: &str = "# made by hand\nTITLE \"test\"\nLUT_3D_SIZE 2\nDOMAIN_MIN 0 0 0\nDOMAIN_MAX 1 1 1\n\n\
+        0 0 0\n1 0 0\n0 1 0\n1 1 0\n# halfway through\n0 0 1\n1 0 1\n0 1 1\n1 1 1\n";
+
+    #[test]
+    fn parses_entries_red_fastest() {
+        let lut = Lut::parse_cube(SIZE_2).unwrap();
+        assert_eq!(lut.title, "test");
+        assert_eq!(lut.size, 2);
+        assert_eq!(lut.data, Lut::identity(2).data);
+    }
+
+    #[test]
+    fn survives_its_own_export() {
+        let lut = Lut::identity(5);
+        let parsed = Lut::parse_cube(&lut.to_cube()).unwrap();
+        assert_eq!(parsed.size, 5);
+        for (a, b) in lut.data.iter().zip(&parsed.data) {
+            assert!((0..3).all(|c| (a[c] - b[c]).abs() < 1e-6));
+        }
+    }
+
+    #[test]
+    fn bounds_the_size() {
+        let with_size = |size: &str| SIZE_2.replace("LUT_3D_SIZE 2", &format!("LUT_3D_SIZE {size}"));
+        assert!(Lut::parse_cube(&with_size("1")).unwrap_err().contains("out of range"));
+        assert!(Lut::parse_cube(&with_size("257")).unwrap_err().contains("out of range"));
+        assert!(Lut::parse_cube(&with_size("two")).unwrap_err().contains("invalid LUT_3D_SIZE"));
+        assert!(Lut::parse_cube(&SIZE_2.replace("LUT_3D_SIZE 2\n", "")).unwrap_err().contains("missing LUT_3D_SIZE"));
+        assert!(Lut::parse_cube("LUT_1D_SIZE 16\n").unwrap_err().contains("1D"));
+    }
+
+    #[test]
+    fn only_takes_the_unit_domain() {
+        assert!(Lut::parse_cube(&SIZE_2.replace("DOMAIN_MIN 0 0 0", "DOMAIN_MIN -1 0 0")).is_err());
+        assert!(Lut::parse_cube(&SIZE_2.replace("DOMAIN_MAX 1 1 1", "DOMAIN_MAX 1 1 2")).is_err());
+        assert!(Lut::parse_cube(&SIZE_2.replace("DOMAIN_MAX 1 1 1", "LUT_3D_INPUT_RANGE 0 1")).is_ok());
+        assert!(Lut::parse_cube(&SIZE_2.replace("DOMAIN_MAX 1 1 1", "LUT_3D_INPUT_RANGE 0 4")).is_err());
+    }
+
+    #[test]
+    fn counts_the_entries() {
+        let without_last = SIZE_2.strip_suffix("1 1 1\n").unwrap();
+        assert!(Lut::parse_cube(without_last).unwrap_err().contains("expected 8 entries for size 2, found 7"));
+        assert!(Lut::parse_cube(&format!("{SIZE_2}0.5 0.5 0.5\n")).unwrap_err().contains("found 9"));
+        assert!(Lut::parse_cube(&format!("{without_last}1 1 1 1\n")).unwrap_err().contains("expected 3 values, found 4"));
+        assert!(Lut::parse_cube(&format!("{without_last}UNKNOWN 1\n")).unwrap_err().contains("unknown keyword"));
+    }
+
+    #[test]
+    fn reads_hald_cluts() {
+        // level 2 is an 8x8 image holding a size 4 table
+        let identity = Lut::identity(4);
+        let image = image::Rgb32FImage::from_fn(8, 8, |x, y| image::Rgb(identity.data[(y * 8 + x) as usize]));
+        let lut = Lut::from_hald(&image).unwrap();
+        assert_eq!(lut.size, 4);
+        assert_eq!(lut.data, identity.data);
+
+        assert!(Lut::from_hald(&image::Rgb32FImage::new(8, 9)).is_err());
+        assert!(Lut::from_hald(&image::Rgb32FImage::new(10, 10)).is_err());
+        assert!(Lut::from_hald(&image::Rgb32FImage::new(1, 1)).is_err());
+    }
+}
@@ -1,23 +1,38 @@
+mod ascii;
+mod benchmark;
+mod blue_noise;
+mod blur;
+mod brush_strokes;
+mod canny;
+mod compute;
 mod controls;
+mod exposure;
+mod loader;
+mod lut;
+mod mask;
+mod mipmap;
+mod pass;
+mod pixel_sort;
 mod scene;
+mod smoothing;
+mod source;
+mod stage;
+mod stitch;
+mod superpixels;
+mod tiles;
+mod timing;
+mod vector;
 
 // use iced::{window}
 
 use controls::Controls;
-use iced_wgpu::wgpu::core::device;
-use iced_wgpu::wgpu::core::id::DeviceId;
+use controls::Message;
 // use iced_winit::winit::event::KeyEvent;
 
-use iced_wgpu::wgpu::core::validation::check_texture_format;
-use iced_wgpu::wgpu::hal::empty::Encoder;
-use iced_wgpu::wgpu::ImageCopyTextureBase;
-use iced_wgpu::wgpu::Texture;
-use iced_winit::winit::event;
 use iced_winit::winit::platform::modifier_supplement::KeyEventExtModifierSupplement;
-use image::RgbaImage;
 // use iced_winit::winit::platform::modifier_supplement::KeyEventExtModifierSupplement;
+use loader::Loader;
 use scene::Scene;
-use scene::Parameters;
 
 use iced_wgpu::graphics::Viewport;
 use iced_wgpu::{wgpu, Backend, Renderer, Settings};
@@ -31,16 +46,19 @@ use iced_winit::runtime::program;
 use iced_winit::runtime::Debug;
 use iced_winit::winit;
 use iced_winit::Clipboard;
+use iced_winit::core::widget::Operation;
 use iced_widget::Theme;
-use winit::dpi::{LogicalSize, PhysicalPosition};
+use winit::dpi::PhysicalPosition;
 // use winit::event::WindowEvent::KeyboardInput;
 use winit::{
-    event::{Event, ElementState, WindowEvent, MouseScrollDelta, MouseButton },
+    event::{Event, ElementState, WindowEvent, MouseScrollDelta, MouseButton, StartCause },
     event_loop::{ControlFlow, EventLoop},
-    keyboard::ModifiersState,
+    keyboard::{Key, ModifiersState},
 };
-use std::sync::Arc;
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
 
+#[allow(dead_code)]
 #[repr(C)]
 #[derive(Copy, Clone, Debug)]
 struct Vertex {
@@ -63,7 +81,7 @@ const ZOOM_SPEED: f32 = 0.1;
 
 
 
-fn handle_zoom(delta: &MouseScrollDelta, zoom_level: &mut f32, pan_offset: &[f32; 2]) {
+fn handle_zoom(delta: &MouseScrollDelta, zoom_level: &mut f32, _pan_offset: &[f32; 2]) {
     match delta {
         MouseScrollDelta::LineDelta(_, y) => {
             *zoom_level += y * ZOOM_SPEED;
@@ -109,6 +127,12 @@ pub fn main() -> Result<(), Box<dyn std::error::Error>> {
     #[cfg(not(target_arch = "wasm32"))]
     tracing_subscriber::fmt::init();
 
+    // rts --benchmark renders headless and prints timings instead of opening a window
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    if args.first().map(String::as_str) == Some("--benchmark") {
+        return benchmark::run(&args[1..]);
+    }
+
     // Initialize winit
     let event_loop = EventLoop::new()?;
 
@@ -151,7 +175,7 @@ pub fn main() -> Result<(), Box<dyn std::error::Error>> {
             .expect("Create adapter");
 
             let adapter_features = adapter.features();
-            let needed_limits = wgpu::Limits::default();
+            let needed_limits = compute::required_limits(&adapter);
 
             let capabilities = surface.get_capabilities(&adapter);
 
@@ -160,7 +184,7 @@ pub fn main() -> Result<(), Box<dyn std::error::Error>> {
                     &wgpu::DeviceDescriptor {
                         label: None,
                         required_features: adapter_features
-                            & wgpu::Features::default(),
+                            & (wgpu::Features::default() | timing::FEATURES),
                         required_limits: needed_limits,
                     },
                     None,
@@ -184,6 +208,10 @@ pub fn main() -> Result<(), Box<dyn std::error::Error>> {
 
         println!("gpu: {}", adapter.get_info().name);
 
+    // shared with the image loader threads
+    let device = Arc::new(device);
+    let queue = Arc::new(queue);
+
     surface.configure(
         &device,
         &wgpu::SurfaceConfiguration {
@@ -199,11 +227,15 @@ pub fn main() -> Result<(), Box<dyn std::error::Error>> {
     );
 
     let mut resized = false;
-    let mut save_screen = false;
 
     // Initialize scene and GUI controls
-    let mut controls = Controls::new();
-    let mut scene = Scene::new(&device, format, &queue, controls.selected_shader);
+    let controls = Controls::new();
+    // a finished image load wakes the event loop with a user event
+    let proxy = Mutex::new(event_loop.create_proxy());
+    let loader = Loader::new(device.clone(), queue.clone(), move || {
+        let _ = proxy.lock().unwrap().send_event(());
+    });
+    let mut scene = Scene::new(&device, format, &queue, controls.selected_shader, loader);
 
     // Initialize iced
     let mut debug = Debug::new();
@@ -222,13 +254,18 @@ pub fn main() -> Result<(), Box<dyn std::error::Error>> {
 
     let mut zoom_level: f32 = 1.0;
     let mut pan_offset = [0.0, 0.0];
+    // last texel painted while the brush is held down
+    let mut paint_position: Option<(f32, f32)> = None;
     let mut pan_state = PanState {
         is_panning: false,
         prev_mouse_pos: PhysicalPosition::new(0.0, 0.0),
     };
 
     let mut current_mouse_position = PhysicalPosition::new(0.0, 0.0);
+    // start of the last frame, for the frame time in the timings overlay
+    let mut last_frame = Instant::now();
 
+    #[allow(dead_code)]
     fn padded_bytes_per_row(width: u32) -> usize {
         let bytes_per_row = width as usize * 4;
         let padding = (256 - bytes_per_row % 256) % 256;
@@ -290,9 +327,20 @@ pub fn main() -> Result<(), Box<dyn std::error::Error>> {
     event_loop.run(move |event, window_target| {
         // You should change this if you want to render continuously
         window_target.set_control_flow(ControlFlow::Wait);
+        // wake up in time to clear toasts
+        if let Some(expiry) = state.program().next_toast_expiry() {
+            window_target.set_control_flow(ControlFlow::WaitUntil(expiry));
+        }
 
 
         match event {
+            Event::NewEvents(StartCause::ResumeTimeReached { .. }) => {
+                state.queue_message(Message::ToastsExpired());
+            }
+            // the scene picks up the loaded image when it prepares the next frame
+            Event::UserEvent(()) => {
+                window.request_redraw();
+            }
             Event::WindowEvent {
                 event: WindowEvent::RedrawRequested,
                 ..
@@ -324,6 +372,7 @@ pub fn main() -> Result<(), Box<dyn std::error::Error>> {
 
                 match surface.get_current_texture() {
                     Ok(frame) => {
+                        let frame_start = Instant::now();
                         let mut encoder = device.create_command_encoder(
                             &wgpu::CommandEncoderDescriptor { label: None },
                         );
@@ -336,6 +385,13 @@ pub fn main() -> Result<(), Box<dyn std::error::Error>> {
 
                         // let texture: Texture;
 
+                        let messages = scene.prepare(
+                            &mut encoder,
+                            &queue,
+                            &device,
+                            program,
+                        );
+
                         {
                             // We clear the frame
                             let mut render_pass = Scene::clear(
@@ -352,8 +408,6 @@ pub fn main() -> Result<(), Box<dyn std::error::Error>> {
                             let window_position = window.inner_position().unwrap();
                             // let params: Parameters = controls.params();
 
-                            // println!("{}", program.did_change);
-
                             // Draw the scene
                             scene.draw(
                                 &mut render_pass,
@@ -366,8 +420,6 @@ pub fn main() -> Result<(), Box<dyn std::error::Error>> {
                                 window_size.height,
                                 &pan_offset,
                                 &zoom_level,
-                                bytemuck::cast_slice(&[program.params()]),
-                                &program.selected_image,
                             )
 
                             
@@ -376,6 +428,7 @@ pub fn main() -> Result<(), Box<dyn std::error::Error>> {
 
 
                         }
+                        scene.finish_timing(&mut encoder);
                         
 
                         // And then iced on top
@@ -398,9 +451,29 @@ pub fn main() -> Result<(), Box<dyn std::error::Error>> {
                     
                 
                         queue.submit(Some(encoder.finish()));
+                        scene.timings_submitted();
+                        let cpu_time = frame_start.elapsed();
                         
                         frame.present();
 
+                        for message in messages {
+                            state.queue_message(message);
+                        }
+
+                        if state.program().show_timings {
+                            // with timestamps the overlay follows as each frame's times are read back
+                            let passes = if scene.gpu_timing_supported() { scene.poll_timings(&device, false) } else { Some(Vec::new()) };
+                            if let Some(passes) = passes {
+                                let frame_time = frame_start.duration_since(last_frame);
+                                state.queue_message(Message::TimingsMeasured(scene.timings(passes, frame_time.as_secs_f32() * 1000.0, cpu_time.as_secs_f32() * 1000.0)));
+                            }
+                            window.request_redraw();
+                        }
+                        if state.program().animating() {
+                            window.request_redraw();
+                        }
+                        last_frame = frame_start;
+
                         // Update the mouse cursor
                        window.set_cursor_icon(
                             iced_winit::conversion::mouse_interaction(
@@ -427,14 +500,29 @@ pub fn main() -> Result<(), Box<dyn std::error::Error>> {
                     WindowEvent::MouseWheel { delta, .. } => {
                         handle_zoom(&delta, &mut zoom_level, &pan_offset);
                     }
-                    WindowEvent::MouseInput { state, button, .. } => {
-                        match (state, button) {
+                    WindowEvent::MouseInput { state: button_state, button, .. } => {
+                        match (button_state, button) {
                             (ElementState::Pressed, MouseButton::Left) => {
-                                pan_state.is_panning = true;
-                                pan_state.prev_mouse_pos = current_mouse_position;
+                                let program = state.program();
+                                // the brush replaces panning while it is selected, unless the press lands on the ui
+                                if program.brush != controls::Brush::off && state.mouse_interaction() == mouse::Interaction::Idle {
+                                    let window_size = window.inner_size();
+                                    let texel = scene.cursor_to_texel(
+                                        (current_mouse_position.x as f32, current_mouse_position.y as f32),
+                                        (window_size.width as f32, window_size.height as f32),
+                                        &pan_offset,
+                                        &zoom_level,
+                                    );
+                                    scene.paint_mask(program, texel, texel);
+                                    paint_position = Some(texel);
+                                } else {
+                                    pan_state.is_panning = true;
+                                    pan_state.prev_mouse_pos = current_mouse_position;
+                                }
                             }
                             (ElementState::Released, MouseButton::Left) => {
                                 pan_state.is_panning = false;
+                                paint_position = None;
                             }
                             _ => {}
                         }
@@ -442,9 +530,21 @@ pub fn main() -> Result<(), Box<dyn std::error::Error>> {
                     WindowEvent::CursorMoved { position, .. } => {
                         cursor_position = Some(position);
                         current_mouse_position = position;
+
+                        if let Some(from) = paint_position {
+                            let window_size = window.inner_size();
+                            let texel = scene.cursor_to_texel(
+                                (position.x as f32, position.y as f32),
+                                (window_size.width as f32, window_size.height as f32),
+                                &pan_offset,
+                                &zoom_level,
+                            );
+                            scene.paint_mask(state.program(), from, texel);
+                            paint_position = Some(texel);
+                        }
             
                         if pan_state.is_panning {
-                            handle_pan(&position, &mut pan_state.prev_mouse_pos, &mut zoom_level, &mut pan_offset);
+                            handle_pan(&position, &mut pan_state.prev_mouse_pos, &zoom_level, &mut pan_offset);
                             // window.request_redraw();
                         }
                     }
@@ -457,13 +557,19 @@ pub fn main() -> Result<(), Box<dyn std::error::Error>> {
                     WindowEvent::CloseRequested => {
                         window_target.exit();
                     }
-                    WindowEvent::KeyboardInput { device_id, ref event, is_synthetic } => {
-                        println!("{:?}", event.text);
-                        if event.text.as_slice() == &[" "] {
-                            print!("SPACE!!");
+                    WindowEvent::KeyboardInput { ref event, .. } => {
+                        if event.text.as_slice() == [" "] {
                             zoom_level = 1.0;
                             pan_offset = [0.0, 0.0];
                         } 
+                        // a copied image, or a copied path or file:// uri, opens the image
+                        if event.state == ElementState::Pressed && modifiers.control_key() && event.key_without_modifiers().as_ref() == Key::Character("v") {
+                            let paste: Box<dyn Operation<Message>> = Box::new(controls::PasteUnlessEditing::default());
+                            state.operate(&mut renderer, std::iter::once(paste), viewport.logical_size(), &mut debug);
+                        }
+                    }
+                    WindowEvent::DroppedFile(ref path) => {
+                        state.queue_message(Message::OpenImage(str::replace(&path.to_string_lossy(), '\\', "/")));
                     }
                     _ => {}
                 }
@@ -501,6 +607,29 @@ pub fn main() -> Result<(), Box<dyn std::error::Error>> {
                 &mut debug,
             );
 
+            if let Some(path) = state.program().export_cube.clone() {
+                match scene.export_cube(&device, &queue, state.program(), 33, &path) {
+                    Ok(()) => state.queue_message(Message::ShowInfo(format!("exported {path}"))),
+                    Err(error) => state.queue_message(Message::ShowError(format!("could not export lut: {error}"))),
+                }
+                state.queue_message(Message::CubeExported());
+            }
+
+            if let Some(path) = state.program().export_image.clone() {
+                match scene.export_image(&device, &queue, state.program(), &path) {
+                    Ok(()) => state.queue_message(Message::ShowInfo(format!("exported {path}"))),
+                    Err(error) => state.queue_message(Message::ShowError(format!("could not export image: {error}"))),
+                }
+                state.queue_message(Message::ImageExported());
+            }
+
+            if let Some(command) = state.program().mask_command.clone() {
+                if let Err(error) = scene.apply_mask_command(&device, &queue, state.program(), &command) {
+                    state.queue_message(Message::ShowError(format!("could not update mask: {error}")));
+                }
+                state.queue_message(Message::MaskApplied());
+            }
+
             // and request a redraw
             window.request_redraw();
         }
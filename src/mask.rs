@@ -0,0 +1,256 @@
+use iced_wgpu::wgpu;
+use image::{GrayImage, Luma, RgbaImage};
+use crate::blur::SeparableBlur;
+use crate::tiles::{Region, TileGrid};
+
+
+// a grayscale layer gating where a stage's effect shows, 255 = effect, 0 = source.
+// edits happen on the cpu copy and the touched rows are uploaded before the next frame
+pub struct Mask {
+    image: GrayImage,
+    // one texture per tile of the image, covering the tile's region
+    tiles: Vec<(Region, wgpu::TextureView, wgpu::Texture)>,
+    // feathering, the gate pass reads the blurred mask
+    blur: SeparableBlur,
+    // tile the blur reads from
+    selected: usize,
+    // first and last row changed since the last upload
+    dirty: Option<(u32, u32)>,
+}
+
+impl Mask {
+    pub fn new(device: &wgpu::Device, grid: &TileGrid) -> Mask {
+        let tiles: Vec<_> = grid.tiles.iter().map(|tile| {
+            let texture = device.create_texture(&wgpu::TextureDescriptor {
+                label: Some("Mask Texture"),
+                size: wgpu::Extent3d {
+                    width: tile.region.width,
+                    height: tile.region.height,
+                    depth_or_array_layers: 1
+                },
+                mip_level_count: 1,
+                sample_count: 1,
+                dimension: wgpu::TextureDimension::D2,
+                format: wgpu::TextureFormat::R8Unorm,
+                usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
+                view_formats: &[],
+            });
+            (tile.region, texture.create_view(&wgpu::TextureViewDescriptor::default()), texture)
+        }).collect();
+        let (tile_width, tile_height) = grid.tile_size();
+        let blur = SeparableBlur::new(device, &tiles[0].1, tile_width, tile_height);
+
+        Mask {
+            image: GrayImage::from_pixel(grid.width, grid.height, Luma([255])),
+            tiles,
+            blur,
+            selected: 0,
+            dirty: Some((0, grid.height - 1)),
+        }
+    }
+
+    // points the feathering at the texture of another tile
+    pub fn select_tile(&mut self, device: &wgpu::Device, tile: usize) {
+        if tile != self.selected {
+            self.selected = tile;
+            self.blur.set_input(device, &self.tiles[tile].1);
+        }
+    }
+
+    pub fn view(&self) -> &wgpu::TextureView {
+        self.blur.output_view()
+    }
+
+    // the selected tile as painted, before feathering
+    pub fn painted_view(&self) -> &wgpu::TextureView {
+        &self.tiles[self.selected].1
+    }
+
+    fn mark_dirty(&mut self, first: u32, last: u32) {
+        self.dirty = Some(match self.dirty {
+            Some((a, b)) => (a.min(first), b.max(last)),
+            None => (first, last),
+        });
+    }
+
+    fn mark_all_dirty(&mut self) {
+        self.mark_dirty(0, self.image.height() - 1);
+    }
+
+    // soft round brush. value 1.0 paints the effect in, 0.0 erases it.
+    // hardness is the fraction of the radius drawn at full strength
+    pub fn stamp(&mut self, x: f32, y: f32, radius: f32, hardness: f32, value: f32) {
+        let (width, height) = self.image.dimensions();
+        let radius = radius.max(0.5);
+        let inner = radius * hardness.clamp(0.0, 0.99);
+
+        let x0 = (x - radius).floor().max(0.0) as u32;
+        let y0 = (y - radius).floor().max(0.0) as u32;
+        let x1 = ((x + radius).ceil() as i64).min(width as i64 - 1);
+        let y1 = ((y + radius).ceil() as i64).min(height as i64 - 1);
+        if x1 < x0 as i64 || y1 < y0 as i64 {
+            return;
+        }
+
+        for py in y0..=y1 as u32 {
+            for px in x0..=x1 as u32 {
+                let distance = ((px as f32 + 0.5 - x).powi(2) + (py as f32 + 0.5 - y).powi(2)).sqrt();
+                if distance >= radius {
+                    continue;
+                }
+                let strength = 1.0 - ((distance - inner) / (radius - inner)).clamp(0.0, 1.0);
+                let pixel = self.image.get_pixel_mut(px, py);
+                let current = pixel[0] as f32 / 255.0;
+                pixel[0] = ((current + (value - current) * strength) * 255.0).round() as u8;
+            }
+        }
+
+        self.mark_dirty(y0, y1 as u32);
+    }
+
+    // stamps along the segment so fast cursor moves still leave a continuous line
+    pub fn stroke(&mut self, from: (f32, f32), to: (f32, f32), radius: f32, hardness: f32, value: f32) {
+        let length = ((to.0 - from.0).powi(2) + (to.1 - from.1).powi(2)).sqrt();
+        let steps = (length / (radius * 0.25).max(0.5)).ceil().max(1.0) as u32;
+        for i in 1..=steps {
+            let t = i as f32 / steps as f32;
+            self.stamp(from.0 + (to.0 - from.0) * t, from.1 + (to.1 - from.1) * t, radius, hardness, value);
+        }
+    }
+
+    pub fn fill(&mut self, value: u8) {
+        self.image.pixels_mut().for_each(|pixel| pixel[0] = value);
+        self.mark_all_dirty();
+    }
+
+    pub fn invert(&mut self) {
+        self.image.pixels_mut().for_each(|pixel| pixel[0] = 255 - pixel[0]);
+        self.mark_all_dirty();
+    }
+
+    pub fn load(&mut self, path: &str) -> Result<(), String> {
+        let img = image::open(path).map_err(|error| error.to_string())?;
+        let (width, height) = self.image.dimensions();
+        self.image = image::imageops::resize(&img.to_luma8(), width, height, image::imageops::FilterType::Triangle);
+        self.mark_all_dirty();
+        Ok(())
+    }
+
+    // selects pixels whose luminance falls between low and high,
+    // fading out over softness on either side
+    #[allow(clippy::wrong_self_convention)]
+    pub fn from_luminance(&mut self, source: &RgbaImage, low: f32, high: f32, softness: f32) {
+        let softness = softness.max(1e-3);
+        for (mask, pixel) in self.image.pixels_mut().zip(source.pixels()) {
+            mask[0] = in_range(luminance(pixel), low, high, softness);
+        }
+        self.mark_all_dirty();
+    }
+
+    // selects whole regions whose average luminance falls between low and high. labels
+    // holds the region of every pixel, small indices from the superpixels effect,
+    // pixels labelled u32::MAX are in none and left out
+    #[allow(clippy::wrong_self_convention)]
+    pub fn from_regions(&mut self, source: &RgbaImage, labels: &[u32], low: f32, high: f32, softness: f32) {
+        let softness = softness.max(1e-3);
+        // luminance sum and pixel count of every region
+        let mut regions: Vec<(f32, u32)> = Vec::new();
+        for (&label, pixel) in labels.iter().zip(source.pixels()).filter(|(&label, _)| label != u32::MAX) {
+            if regions.len() <= label as usize {
+                regions.resize(label as usize + 1, (0.0, 0));
+            }
+            regions[label as usize].0 += luminance(pixel);
+            regions[label as usize].1 += 1;
+        }
+        for (mask, &label) in self.image.pixels_mut().zip(labels) {
+            mask[0] = match regions.get(label as usize) {
+                Some(&(sum, count)) => in_range(sum / count as f32, low, high, softness),
+                None => 0,
+            };
+        }
+        self.mark_all_dirty();
+    }
+
+    // selects saturated pixels whose hue is within width / 2 degrees of hue,
+    // fading out over softness degrees
+    #[allow(clippy::wrong_self_convention)]
+    pub fn from_hue(&mut self, source: &RgbaImage, hue: f32, width: f32, softness: f32) {
+        let softness = softness.max(1e-3);
+        for (mask, pixel) in self.image.pixels_mut().zip(source.pixels()) {
+            let [r, g, b, _] = pixel.0.map(|c| c as f32 / 255.0);
+            let (max, min) = (r.max(g).max(b), r.min(g).min(b));
+            let chroma = max - min;
+
+            let pixel_hue = if chroma < 1e-5 {
+                0.0
+            } else if max == r {
+                60.0 * ((g - b) / chroma).rem_euclid(6.0)
+            } else if max == g {
+                60.0 * ((b - r) / chroma + 2.0)
+            } else {
+                60.0 * ((r - g) / chroma + 4.0)
+            };
+
+            let distance = (pixel_hue - hue).rem_euclid(360.0);
+            let distance = distance.min(360.0 - distance);
+            let inside = ((width * 0.5 - distance) / softness + 1.0).clamp(0.0, 1.0);
+            // grays have no meaningful hue
+            let saturation = (chroma / 0.1).clamp(0.0, 1.0);
+            mask[0] = (inside * saturation * 255.0).round() as u8;
+        }
+        self.mark_all_dirty();
+    }
+
+    // whether anything changed since the last upload
+    pub fn upload(&mut self, queue: &wgpu::Queue) -> bool {
+        let Some((first, last)) = self.dirty.take() else {
+            return false;
+        };
+        let width = self.image.width();
+
+        // the changed rows that fall in each tile's region
+        for (region, _, texture) in &self.tiles {
+            let (first, last) = (first.max(region.y), last.min(region.y + region.height - 1));
+            if first > last {
+                continue;
+            }
+            let rows = last - first + 1;
+
+            queue.write_texture(
+                wgpu::ImageCopyTexture {
+                    texture,
+                    mip_level: 0,
+                    origin: wgpu::Origin3d { x: 0, y: first - region.y, z: 0 },
+                    aspect: wgpu::TextureAspect::All
+                },
+                self.image.as_raw(),
+                wgpu::ImageDataLayout {
+                    offset: (first * width + region.x) as u64,
+                    bytes_per_row: Some(width),
+                    rows_per_image: Some(rows),
+                },
+                wgpu::Extent3d {
+                    width: region.width,
+                    height: rows,
+                    depth_or_array_layers: 1
+                },
+            );
+        }
+        true
+    }
+
+    pub fn encode(&self, encoder: &mut wgpu::CommandEncoder, queue: &wgpu::Queue, feather: f32) {
+        self.blur.encode(encoder, queue, feather);
+    }
+}
+
+fn luminance(pixel: &image::Rgba<u8>) -> f32 {
+    let [r, g, b, _] = pixel.0.map(|c| c as f32 / 255.0);
+    0.2126 * r + 0.7152 * g + 0.0722 * b
+}
+
+// the mask value of a luminance, full between low and high and fading out over softness on either side
+fn in_range(luminance: f32, low: f32, high: f32, softness: f32) -> u8 {
+    let inside = ((luminance - low) / softness + 1.0).min((high - luminance) / softness + 1.0);
+    (inside.clamp(0.0, 1.0) * 255.0).round() as u8
+}
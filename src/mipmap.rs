@@ -0,0 +1,73 @@
+use iced_wgpu::wgpu;
+use crate::pass;
+
+
+// fills the levels of a mipmapped target from its first, each level downsampled from
+// the one above it. what the viewer minifies is rendered every frame, so the chain is
+// encoded again after each render. loaded images are filled once, after upload
+pub struct Mipmaps {
+    pipeline: wgpu::RenderPipeline,
+    // bind_groups[i] reads level i and renders into level_views[i]
+    bind_groups: Vec<wgpu::BindGroup>,
+    level_views: Vec<wgpu::TextureView>,
+}
+
+impl Mipmaps {
+    pub fn new(device: &wgpu::Device, texture: &wgpu::Texture) -> Mipmaps {
+        let module = device.create_shader_module(wgpu::include_wgsl!("../shaders/mipmap.wgsl"));
+
+        // 32 bit float images can't be filtered, their texels are averaged by hand
+        let format = texture.format();
+        let filterable = format.sample_type(None, None) == Some(wgpu::TextureSampleType::Float { filterable: true });
+        let (entries, entry_point) = match filterable {
+            true => (vec![pass::texture_entry(0), pass::sampler_entry(1)], "frag_main"),
+            false => (vec![pass::load_texture_entry(0)], "load_main"),
+        };
+        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("Mipmap Bind Group Layout"),
+            entries: &entries,
+        });
+
+        let pipeline = pass::create_format_pipeline(device, "Mipmap Pipeline", &module, entry_point, &bind_group_layout, format, 1);
+
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            address_mode_u: wgpu::AddressMode::ClampToEdge,
+            address_mode_v: wgpu::AddressMode::ClampToEdge,
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            mipmap_filter: wgpu::FilterMode::Nearest,
+            ..Default::default()
+        });
+
+        let views: Vec<_> = (0..texture.mip_level_count()).map(|level| pass::level_view(texture, level)).collect();
+        let bind_groups = views.iter().take(views.len() - 1).map(|view| {
+            let mut entries = vec![wgpu::BindGroupEntry {
+                binding: 0,
+                resource: wgpu::BindingResource::TextureView(view),
+            }];
+            if filterable {
+                entries.push(wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::Sampler(&sampler),
+                });
+            }
+            device.create_bind_group(&wgpu::BindGroupDescriptor {
+                label: Some("Mipmap Bind Group"),
+                layout: &bind_group_layout,
+                entries: &entries,
+            })
+        }).collect();
+
+        Mipmaps {
+            pipeline,
+            bind_groups,
+            level_views: views.into_iter().skip(1).collect(),
+        }
+    }
+
+    pub fn encode(&self, encoder: &mut wgpu::CommandEncoder) {
+        for (bind_group, view) in self.bind_groups.iter().zip(&self.level_views) {
+            pass::run(encoder, "Mipmap Pass", &self.pipeline, bind_group, &[view]);
+        }
+    }
+}
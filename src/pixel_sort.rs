@@ -0,0 +1,372 @@
+use crate::compute::{ComputeEffect, ComputePass, Domain};
+#[cfg(test)]
+use crate::scene::Parameters;
+
+
+// u32 words per sorted element: its span start above its key, then its position on the line
+const ELEMENT_WORDS: u64 = 2;
+
+
+// sorts the spans of pixels that pass a threshold along straight lines, see pixel_sort.wgsl.
+// a pass per line finds the spans, a bitonic network sorts every line by span start, key
+// and position, which leaves pixels outside the spans in place and sorts each span where
+// it is, and a pass per pixel gathers the sorted pixels. lines that don't fit the storage
+// buffer at once are sorted in rounds, as many as it holds each. a tiled image is sorted tile by tile
+pub static PIXEL_SORT: ComputeEffect = ComputeEffect {
+    label: "Pixel Sort",
+    source: include_str!("../shaders/pixel_sort.wgsl"),
+    passes: &[
+        ComputePass {
+            entry_point: "spans_main",
+            workgroup_size: [64, 1],
+            domain: Domain::custom(|params, width, height| (Lines::new(params.sort_angle, width, height).count, 1)),
+            iterations: |_, _, _| 1,
+            writes: false,
+        },
+        ComputePass {
+            entry_point: "sort_main",
+            workgroup_size: [64, 1],
+            domain: Domain::custom(|params, width, height| {
+                let lines = Lines::new(params.sort_angle, width, height);
+                (lines.padded / 2, lines.count)
+            }),
+            iterations: |params, width, height| bitonic_steps(Lines::new(params.sort_angle, width, height).padded),
+            writes: false,
+        },
+        ComputePass {
+            entry_point: "write_main",
+            workgroup_size: [8, 8],
+            domain: Domain::pixels,
+            iterations: |_, _, _| 1,
+            writes: true,
+        },
+    ],
+    // the most lines any angle needs, each padded to a power of two
+    buffer_size: |width, height| (width + height) as u64 * width.max(height).next_power_of_two() as u64 * ELEMENT_WORDS * 4,
+    rounds: |params, width, height, bytes| {
+        let lines = Lines::new(params.sort_angle, width, height);
+        lines.count.div_ceil(lines.stored(bytes).max(1))
+    },
+};
+
+// compare and swap steps sorting padded elements, merges of size 2, 4, .. padded
+// take one step per halving
+fn bitonic_steps(padded: u32) -> u32 {
+    let stages = padded.trailing_zeros();
+    stages * (stages + 1) / 2
+}
+
+
+// the lines pixels are sorted along, straight at the angle. they step one pixel along the
+// axis closer to the angle at a time, so every pixel lies on exactly one line and
+// nothing is resampled. 0 degrees runs along rows, 90 down columns.
+// mirrors lines_for in pixel_sort.wgsl. only the reference sort in the tests walks the lines
+#[cfg_attr(not(test), allow(dead_code))]
+#[derive(Debug, Clone, Copy)]
+pub struct Lines {
+    // whether lines run closer to the x axis than to the y axis
+    horizontal: bool,
+    // whether positions on a line count down that axis
+    reversed: bool,
+    // offset across per pixel along
+    slope: f32,
+    // largest offset any line reaches, lines are indexed so the first starts at 0
+    offset: i32,
+    // pixels along a line, some of them outside the image for slanted lines
+    length: u32,
+    // length rounded up to a power of two for the bitonic sort
+    padded: u32,
+    count: u32,
+}
+
+impl Lines {
+    pub fn new(angle: f32, width: u32, height: u32) -> Lines {
+        let radians = angle.to_radians();
+        let (sin, cos) = (radians.sin(), radians.cos());
+        let horizontal = cos.abs() >= sin.abs();
+        let (length, across, slope, reversed) = match horizontal {
+            true => (width, height, sin / cos, cos < 0.0),
+            false => (height, width, cos / sin, sin < 0.0),
+        };
+
+        let mut lines = Lines { horizontal, reversed, slope, offset: 0, length, padded: length.next_power_of_two(), count: 0 };
+        let last = lines.offset_at(length - 1);
+        lines.offset = last.max(0);
+        lines.count = across + (last.max(0) - last.min(0)) as u32;
+        lines
+    }
+
+    // lines a storage buffer of bytes holds, mirrors lines_stored in pixel_sort.wgsl
+    fn stored(&self, bytes: u64) -> u32 {
+        (bytes / (self.padded as u64 * ELEMENT_WORDS * 4)).min(self.count as u64) as u32
+    }
+
+    fn offset_at(&self, along: u32) -> i32 {
+        (along as f32 * self.slope + 0.5).floor() as i32
+    }
+
+    // the pixel at position t of line, None outside the image
+    #[cfg(test)]
+    fn pixel(&self, line: u32, t: u32, width: u32, height: u32) -> Option<(u32, u32)> {
+        let along = if self.reversed { self.length - 1 - t } else { t };
+        let across = line as i32 + self.offset_at(along) - self.offset;
+        let (x, y) = if self.horizontal { (along as i32, across) } else { (across, along as i32) };
+        (x >= 0 && y >= 0 && (x as u32) < width && (y as u32) < height).then_some((x as u32, y as u32))
+    }
+}
+
+// the value of a color that spans are thresholded and sorted on, see SortKey
+#[cfg(test)]
+fn channel(color: [f32; 4], key: u32) -> f32 {
+    let [r, g, b, _] = color;
+    let (max, min) = (r.max(g).max(b), r.min(g).min(b));
+    let chroma = max - min;
+    match key {
+        // hue as a fraction of the circle
+        1 => {
+            let sector = if chroma < 1e-5 {
+                0.0
+            } else if max == r {
+                let x = (g - b) / chroma;
+                x - 6.0 * (x / 6.0).floor()
+            } else if max == g {
+                (b - r) / chroma + 2.0
+            } else {
+                (r - g) / chroma + 4.0
+            };
+            sector / 6.0
+        }
+        2 => if max > 0.0 { chroma / max } else { 0.0 },
+        _ => 0.2126 * r + 0.7152 * g + 0.0722 * b,
+    }
+}
+
+// the effect on the cpu, to check the shader against. input is what the effect reads,
+// display encoded
+#[cfg(test)]
+pub fn sort_reference(input: &image::Rgba32FImage, params: &Parameters) -> image::Rgba32FImage {
+    let (width, height) = input.dimensions();
+    let lines = Lines::new(params.sort_angle, width, height);
+    let mut output = input.clone();
+
+    for line in 0..lines.count {
+        // (span start << 16 | key, position), ordered the way the bitonic sort leaves them.
+        // positions past the line's length are spans of their own and stay put, so they are left out
+        let mut elements = Vec::with_capacity(lines.length as usize);
+        let (mut start, mut previous) = (0, false);
+        for t in 0..lines.length {
+            let (in_span, key) = match lines.pixel(line, t, width, height) {
+                Some((x, y)) => {
+                    let color = input.get_pixel(x, y).0;
+                    let value = channel(color, params.sort_span);
+                    let key = (channel(color, params.sort_key).clamp(0.0, 1.0) * 65535.0 + 0.5).floor() as u32;
+                    (params.sort_low <= value && value <= params.sort_high, if params.sort_descending != 0 { 65535 - key } else { key })
+                }
+                None => (false, 0),
+            };
+            if !(in_span && previous) {
+                start = t;
+            }
+            previous = in_span;
+            elements.push(((start << 16) | if in_span { key } else { 0 }, t));
+        }
+        elements.sort_unstable();
+
+        for (t, &(_, from)) in elements.iter().enumerate() {
+            if let (Some((x, y)), Some((from_x, from_y))) = (lines.pixel(line, t as u32, width, height), lines.pixel(line, from, width, height)) {
+                output.put_pixel(x, y, *input.get_pixel(from_x, from_y));
+            }
+        }
+    }
+    output
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use half::f16;
+    use iced_wgpu::wgpu;
+    use iced_winit::futures;
+    use image::{Rgba, Rgba32FImage};
+    use crate::compute::{self, ComputeEffectRunner};
+    use crate::controls::Controls;
+    use crate::pass;
+
+    const RED: [f32; 4] = [1.0, 0.0, 0.0, 1.0];
+    const GREEN: [f32; 4] = [0.0, 1.0, 0.0, 1.0];
+    const BLUE: [f32; 4] = [0.0, 0.0, 1.0, 1.0];
+
+    fn gray(value: f32) -> [f32; 4] {
+        [value, value, value, 1.0]
+    }
+
+    fn row(pixels: &[[f32; 4]]) -> Rgba32FImage {
+        Rgba32FImage::from_fn(pixels.len() as u32, 1, |x, _| Rgba(pixels[x as usize]))
+    }
+
+    fn pixels(image: &Rgba32FImage) -> Vec<[f32; 4]> {
+        image.pixels().map(|pixel| pixel.0).collect()
+    }
+
+    // every pixel in the default luminance span, 0.25 to 0.8
+    fn params() -> Parameters {
+        Parameters { sort_angle: 0.0, sort_span: 0, sort_low: 0.0, sort_high: 1.0, sort_key: 0, sort_descending: 0, ..Controls::new().params() }
+    }
+
+    #[test]
+    fn sorts_by_luminance_both_ways() {
+        let input = row(&[gray(0.5), gray(0.2), gray(0.9), gray(0.7)]);
+        assert_eq!(pixels(&sort_reference(&input, &params())), [gray(0.2), gray(0.5), gray(0.7), gray(0.9)]);
+
+        let descending = Parameters { sort_descending: 1, ..params() };
+        assert_eq!(pixels(&sort_reference(&input, &descending)), [gray(0.9), gray(0.7), gray(0.5), gray(0.2)]);
+    }
+
+    #[test]
+    fn sorts_by_hue_and_saturation() {
+        let hue = Parameters { sort_key: 1, ..params() };
+        assert_eq!(pixels(&sort_reference(&row(&[BLUE, RED, GREEN]), &hue)), [RED, GREEN, BLUE]);
+
+        let pink = [1.0, 0.5, 0.5, 1.0];
+        let saturation = Parameters { sort_key: 2, ..params() };
+        assert_eq!(pixels(&sort_reference(&row(&[RED, gray(0.5), pink]), &saturation)), [gray(0.5), pink, RED]);
+    }
+
+    #[test]
+    fn sorts_spans_in_place() {
+        // 0.9 is above the span and 0.1 below it, they stay put between two spans sorted on their own
+        let input = row(&[gray(0.9), gray(0.7), gray(0.5), gray(0.1), gray(0.6), gray(0.3)]);
+        let spans = Parameters { sort_low: 0.25, sort_high: 0.8, ..params() };
+        assert_eq!(pixels(&sort_reference(&input, &spans)), [gray(0.9), gray(0.5), gray(0.7), gray(0.1), gray(0.3), gray(0.6)]);
+    }
+
+    #[test]
+    fn sorts_along_the_angle() {
+        let input = row(&[gray(0.5), gray(0.2), gray(0.9)]);
+        let backwards = Parameters { sort_angle: 180.0, ..params() };
+        assert_eq!(pixels(&sort_reference(&input, &backwards)), [gray(0.9), gray(0.5), gray(0.2)]);
+
+        let column = Rgba32FImage::from_fn(1, 3, |_, y| Rgba(gray([0.5, 0.2, 0.9][y as usize])));
+        let down = Parameters { sort_angle: 90.0, ..params() };
+        assert_eq!(pixels(&sort_reference(&column, &down)), [gray(0.2), gray(0.5), gray(0.9)]);
+    }
+
+    // the effect run on the gpu, None without an adapter
+    fn sort_on_gpu(input: &Rgba32FImage, params: &Parameters, effect: &'static ComputeEffect) -> Option<Rgba32FImage> {
+        let (width, height) = input.dimensions();
+        let instance = wgpu::Instance::new(wgpu::InstanceDescriptor::default());
+        let (device, queue) = futures::futures::executor::block_on(async {
+            let adapter = instance.request_adapter(&wgpu::RequestAdapterOptions::default()).await?;
+            adapter.request_device(&wgpu::DeviceDescriptor {
+                label: None,
+                required_features: wgpu::Features::empty(),
+                required_limits: compute::required_limits(&adapter),
+            }, None).await.ok()
+        })?;
+
+        let texture = |label, usage| device.create_texture(&wgpu::TextureDescriptor {
+            label: Some(label),
+            size: wgpu::Extent3d { width, height, depth_or_array_layers: 1 },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: pass::TARGET_FORMAT,
+            usage,
+            view_formats: &[],
+        });
+        let input_texture = texture("Input", wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST);
+        let effect_texture = texture("Effect", wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::STORAGE_BINDING | wgpu::TextureUsages::COPY_SRC);
+        let halves: Vec<u16> = input.as_raw().iter().map(|&value| f16::from_f32(value).to_bits()).collect();
+        queue.write_texture(
+            input_texture.as_image_copy(),
+            bytemuck::cast_slice(&halves),
+            wgpu::ImageDataLayout { offset: 0, bytes_per_row: Some(width * 8), rows_per_image: None },
+            input_texture.size(),
+        );
+
+        let parameters_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: None,
+            size: std::mem::size_of::<Parameters>() as u64,
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        queue.write_buffer(&parameters_buffer, 0, bytemuck::bytes_of(params));
+        // the effect reads no assets
+        let asset_bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor { label: None, entries: &[] });
+        let asset_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor { label: None, layout: &asset_bind_group_layout, entries: &[] });
+
+        let runner = ComputeEffectRunner::new(
+            &device,
+            effect,
+            &input_texture.create_view(&wgpu::TextureViewDescriptor::default()),
+            &effect_texture.create_view(&wgpu::TextureViewDescriptor::default()),
+            &parameters_buffer,
+            &asset_bind_group_layout,
+            width,
+            height,
+        );
+        // rows of the readback are padded to the copy alignment
+        let bytes_per_row = (width * 8).next_multiple_of(wgpu::COPY_BYTES_PER_ROW_ALIGNMENT);
+        let readback = device.create_buffer(&wgpu::BufferDescriptor {
+            label: None,
+            size: (bytes_per_row * height) as u64,
+            usage: wgpu::BufferUsages::MAP_READ | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor { label: None });
+        runner.encode(&mut encoder, &queue, params, &asset_bind_group);
+        encoder.copy_texture_to_buffer(
+            effect_texture.as_image_copy(),
+            wgpu::ImageCopyBuffer {
+                buffer: &readback,
+                layout: wgpu::ImageDataLayout { offset: 0, bytes_per_row: Some(bytes_per_row), rows_per_image: None },
+            },
+            effect_texture.size(),
+        );
+        queue.submit(Some(encoder.finish()));
+
+        let slice = readback.slice(..);
+        slice.map_async(wgpu::MapMode::Read, |_| {});
+        device.poll(wgpu::Maintain::Wait);
+        let data = slice.get_mapped_range();
+        let values: Vec<f32> = data.chunks(bytes_per_row as usize)
+            .flat_map(|row| bytemuck::cast_slice::<u8, u16>(&row[..width as usize * 8]).iter().map(|&bits| f16::from_bits(bits).to_f32()))
+            .collect();
+        Rgba32FImage::from_raw(width, height, values)
+    }
+
+    // the same effect with room for 5 lines at a time, so slanted lines take several rounds
+    static PIXEL_SORT_IN_ROUNDS: ComputeEffect = ComputeEffect {
+        label: "Pixel Sort In Rounds",
+        source: include_str!("../shaders/pixel_sort.wgsl"),
+        passes: PIXEL_SORT.passes,
+        buffer_size: |width, height| 5 * width.max(height).next_power_of_two() as u64 * ELEMENT_WORDS * 4,
+        rounds: PIXEL_SORT.rounds,
+    };
+
+    #[test]
+    fn matches_the_shader() {
+        // noise with stretches of both sides of the span, already rounded to what the texture holds
+        let mut seed = 1u32;
+        let input = Rgba32FImage::from_fn(32, 24, |_, _| {
+            let mut channel = || {
+                seed = seed.wrapping_mul(1664525).wrapping_add(1013904223);
+                f16::from_f32((seed >> 8) as f32 / (1 << 24) as f32).to_f32()
+            };
+            Rgba([channel(), channel(), channel(), 1.0])
+        });
+
+        for (angle, key, descending) in [(0.0, 0, 0), (30.0, 1, 1), (-100.0, 2, 0), (135.0, 0, 1)] {
+            let params = Parameters { sort_angle: angle, sort_key: key, sort_descending: descending, sort_low: 0.2, sort_high: 0.7, ..params() };
+            let expected = sort_reference(&input, &params);
+            for effect in [&PIXEL_SORT, &PIXEL_SORT_IN_ROUNDS] {
+                let Some(output) = sort_on_gpu(&input, &params, effect) else {
+                    eprintln!("no adapter, the shader is not checked");
+                    return;
+                };
+                assert!(output == expected, "{} differs from the reference at {angle} degrees", effect.label);
+            }
+        }
+    }
+}
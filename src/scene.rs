@@ -1,11 +1,25 @@
-use iced_wgpu::wgpu::{self, util::DeviceExt, RenderPass};
+use iced_wgpu::wgpu::{self, util::DeviceExt};
 use iced_winit::core::Color;
-use image;
-use crate::controls::{self, Shader};
+use crate::controls::{Brush, ColorSpace, Controls, Input, Magnification, MaskCommand, Message, Prefilter, Shader};
+use crate::loader::{Decoded, Loader, Slot};
+use crate::ascii::{self, GlyphAtlas, DEFAULT_RAMP};
+use crate::blue_noise::BlueNoise;
+use crate::brush_strokes::BrushTexture;
+use crate::superpixels::{self, SUPERPIXELS};
+use crate::lut::Lut;
+use crate::stage::{Sources, Stage};
+use crate::mask::Mask;
+use crate::pass;
+use crate::source::{self, Source};
+use crate::stitch::Stitch;
+use crate::tiles::{Tile, TileGrid};
+use crate::timing::{GpuTimer, Timings};
+use crate::vector;
+use half::f16;
 
 
 #[repr(C)]
-#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+#[derive(Copy, Clone, Debug, PartialEq, bytemuck::Pod, bytemuck::Zeroable)]
 pub struct Parameters {
     pub sigma1: f32,
     pub tau: f32,
@@ -16,9 +30,269 @@ pub struct Parameters {
     pub shader_index: u32,
     pub colors: f32,
     pub intensity: f32,
+    pub lut_intensity: f32,
+    pub low_threshold: f32,
+    pub high_threshold: f32,
+    pub canny_overlay: u32,
+    pub sigma_space: f32,
+    pub sigma_range: f32,
+    pub guided_radius: i32,
+    pub guided_epsilon: f32,
+    pub prefilter: u32,
+    pub blend_mode: u32,
+    pub opacity: f32,
+    pub tone_operator: u32,
+    pub tone_exposure: f32,
+    pub white_point: f32,
+    pub contrast: f32,
+    pub auto_exposure: u32,
+    pub sort_angle: f32,
+    pub sort_span: u32,
+    pub sort_low: f32,
+    pub sort_high: f32,
+    pub sort_key: u32,
+    pub sort_descending: u32,
+    pub glyph_cell: u32,
+    pub glyph_count: u32,
+    pub glyph_color: u32,
+    pub glyph_edges: u32,
+    pub glyph_edge_threshold: f32,
+    // where the tile being rendered starts in the image
+    pub tile_x: u32,
+    pub tile_y: u32,
+    pub halftone_mode: u32,
+    pub dot_shape: u32,
+    pub halftone_cell: f32,
+    pub screen_cyan: f32,
+    pub screen_magenta: f32,
+    pub screen_yellow: f32,
+    pub screen_black: f32,
+    pub dot_gain: f32,
+    // the size of the whole image, 0 for the size of the stage's input
+    pub image_width: u32,
+    pub image_height: u32,
+    pub crt_mask: u32,
+    pub crt_palette: u32,
+    pub crt_interlace: u32,
+    pub crt_curvature: f32,
+    pub scanline_size: f32,
+    pub scanline_strength: f32,
+    pub mask_strength: f32,
+    pub crt_bloom: f32,
+    pub crt_vignette: f32,
+    pub lens_center_x: f32,
+    pub lens_center_y: f32,
+    pub aberration_falloff: f32,
+    pub distortion_k1: f32,
+    pub distortion_k2: f32,
+    pub spectral_samples: u32,
+    // advances every frame while grain animates, 0 for stills
+    pub frame: u32,
+    pub grain_seed: u32,
+    pub grain_intensity: f32,
+    pub grain_size: f32,
+    pub grain_chroma: f32,
+    pub grain_response: f32,
+    pub noise_type: u32,
+    pub noise_seed: u32,
+    pub noise_scale: f32,
+    pub noise_intensity: f32,
+    pub stroke_width: u32,
+    pub stroke_layers: u32,
+    pub stroke_length: f32,
+    pub stroke_detail: f32,
+    pub stroke_texture: u32,
+    pub superpixel_count: u32,
+    pub superpixel_compactness: f32,
+    pub superpixel_iterations: u32,
+    pub superpixel_boundaries: u32,
+}
+
+// the wgsl struct of Parameters, shared by the fragment shader and the compute effects
+// and prepended to their source
+pub const PARAMETERS_WGSL: &str = include_str!("../shaders/parameters.wgsl");
+
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+struct CompositeParameters {
+    blend_mode: u32,
+    opacity: f32,
+    working_space: u32,
+    // whether the target expects linear values, like an srgb surface or an exr file
+    output_linear: u32,
+    exposure: f32,
+    compress_highlights: u32,
+    // filter past 1:1, see Magnification::get_index
+    magnification: u32,
+    _padding: u32,
+}
+
+
+// what a stage's output depends on besides its source and the loaded resources,
+// see RenderingPipeline::rendered
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct StageKey {
+    shader: Shader,
+    // the smoothing that runs ahead of the effect, see Shader::smoothing
+    prefilter: Prefilter,
+    // only the fields the stage reads, see read_params
+    params: Parameters,
+    // feather of the mask when it is enabled
+    mask: Option<f32>,
+    source_space: ColorSpace,
+    working_space: ColorSpace,
 }
 
+impl StageKey {
+    fn new(shader: Shader, prefilter: Prefilter, params: &Parameters, mask: Option<f32>, source_space: ColorSpace, working_space: ColorSpace) -> StageKey {
+        let prefilter = shader.smoothing(prefilter);
+        let params = read_params(shader, prefilter, params);
+        StageKey { shader, prefilter, params, mask, source_space, working_space }
+    }
+}
 
+// the fields of params a stage with shader and prefilter reads, the rest zeroed so the
+// controls of other effects don't make it stale. blending happens in the composite and
+// the tile is set per tile, so neither is read. mirrors fragment.wgsl, the compute
+// effects and Stage::render
+fn read_params(shader: Shader, prefilter: Prefilter, p: &Parameters) -> Parameters {
+    let mut read: Parameters = bytemuck::Zeroable::zeroed();
+    match prefilter {
+        Prefilter::bilateral => {
+            read.sigma_space = p.sigma_space;
+            read.sigma_range = p.sigma_range;
+        }
+        Prefilter::guided => {
+            read.guided_radius = p.guided_radius;
+            read.guided_epsilon = p.guided_epsilon;
+        }
+        Prefilter::none => {}
+    }
+    match shader {
+        Shader::none | Shader::invert | Shader::sobel_edge_detection | Shader::bilateral_filter | Shader::guided_filter => {}
+        Shader::gaussian_blur => {
+            read.sigma1 = p.sigma1;
+        }
+        Shader::quantization => {
+            read.prefilter = p.prefilter;
+        }
+        Shader::difference_of_gaussians_DoG => {
+            read.sigma1 = p.sigma1;
+            read.tau = p.tau;
+        }
+        Shader::flow_based_XDoG => {
+            read.sigma1 = p.sigma1;
+            read.tau = p.tau;
+            read.gfact = p.gfact;
+            read.epsilon = p.epsilon;
+            read.num_gvf_iterations = p.num_gvf_iterations;
+            read.enable_xdog = p.enable_xdog;
+        }
+        Shader::edge_direction => {
+            read.sigma1 = p.sigma1;
+            read.enable_xdog = p.enable_xdog;
+        }
+        Shader::bayer_dither => {
+            read.colors = p.colors;
+            read.tau = p.tau;
+        }
+        Shader::chromatic_aberration => {
+            read.intensity = p.intensity;
+            read.lens_center_x = p.lens_center_x;
+            read.lens_center_y = p.lens_center_y;
+            read.aberration_falloff = p.aberration_falloff;
+            read.distortion_k1 = p.distortion_k1;
+            read.distortion_k2 = p.distortion_k2;
+            read.spectral_samples = p.spectral_samples;
+        }
+        Shader::lut => {
+            read.lut_intensity = p.lut_intensity;
+        }
+        Shader::canny_edge_detection => {
+            read.sigma1 = p.sigma1;
+            read.low_threshold = p.low_threshold;
+            read.high_threshold = p.high_threshold;
+            read.num_gvf_iterations = p.num_gvf_iterations;
+            read.canny_overlay = p.canny_overlay;
+        }
+        Shader::tone_mapping => {
+            read.tone_operator = p.tone_operator;
+            read.tone_exposure = p.tone_exposure;
+            read.white_point = p.white_point;
+            read.contrast = p.contrast;
+            read.auto_exposure = p.auto_exposure;
+        }
+        Shader::pixel_sort => {
+            read.sort_angle = p.sort_angle;
+            read.sort_span = p.sort_span;
+            read.sort_low = p.sort_low;
+            read.sort_high = p.sort_high;
+            read.sort_key = p.sort_key;
+            read.sort_descending = p.sort_descending;
+        }
+        Shader::ascii => {
+            read.glyph_cell = p.glyph_cell;
+            read.glyph_count = p.glyph_count;
+            read.glyph_color = p.glyph_color;
+            read.glyph_edges = p.glyph_edges;
+            read.glyph_edge_threshold = p.glyph_edge_threshold;
+        }
+        Shader::halftone => {
+            read.halftone_mode = p.halftone_mode;
+            read.dot_shape = p.dot_shape;
+            read.halftone_cell = p.halftone_cell;
+            read.screen_cyan = p.screen_cyan;
+            read.screen_magenta = p.screen_magenta;
+            read.screen_yellow = p.screen_yellow;
+            read.screen_black = p.screen_black;
+            read.dot_gain = p.dot_gain;
+        }
+        Shader::crt => {
+            read.crt_mask = p.crt_mask;
+            read.crt_palette = p.crt_palette;
+            read.crt_interlace = p.crt_interlace;
+            read.crt_curvature = p.crt_curvature;
+            read.scanline_size = p.scanline_size;
+            read.scanline_strength = p.scanline_strength;
+            read.mask_strength = p.mask_strength;
+            read.crt_bloom = p.crt_bloom;
+            read.crt_vignette = p.crt_vignette;
+        }
+        Shader::film_grain => {
+            read.frame = p.frame;
+            read.grain_seed = p.grain_seed;
+            read.grain_intensity = p.grain_intensity;
+            read.grain_size = p.grain_size;
+            read.grain_chroma = p.grain_chroma;
+            read.grain_response = p.grain_response;
+        }
+        Shader::noise => {
+            read.frame = p.frame;
+            read.noise_type = p.noise_type;
+            read.noise_seed = p.noise_seed;
+            read.noise_scale = p.noise_scale;
+            read.noise_intensity = p.noise_intensity;
+        }
+        Shader::brush_strokes => {
+            read.stroke_width = p.stroke_width;
+            read.stroke_layers = p.stroke_layers;
+            read.stroke_length = p.stroke_length;
+            read.stroke_detail = p.stroke_detail;
+            read.stroke_texture = p.stroke_texture;
+        }
+        Shader::superpixels => {
+            read.superpixel_count = p.superpixel_count;
+            read.superpixel_compactness = p.superpixel_compactness;
+            read.superpixel_iterations = p.superpixel_iterations;
+            read.superpixel_boundaries = p.superpixel_boundaries;
+        }
+    }
+    read
+}
+
+
+#[allow(dead_code)]
 pub struct ImageBufferHolder {
     buffer: wgpu::Buffer,
 }
@@ -34,7 +308,8 @@ struct Vertex {
 
 
 
-fn update_vertex_data(zoom_level: &f32, pan_offset: &[f32; 2], window_aspect_ratio: f32, image_aspect_ratio: f32) -> Vec<Vertex> {
+// half extent of the image quad in clip space
+fn quad_scale(zoom_level: &f32, window_aspect_ratio: f32, image_aspect_ratio: f32) -> (f32, f32) {
     let mut scale_x = *zoom_level;
     let mut scale_y = *zoom_level;
 
@@ -44,32 +319,418 @@ fn update_vertex_data(zoom_level: &f32, pan_offset: &[f32; 2], window_aspect_rat
         scale_y *= window_aspect_ratio / image_aspect_ratio;
     }
 
-    let vertex_data = [
+    (scale_x, scale_y)
+}
+
+fn update_vertex_data(zoom_level: &f32, pan_offset: &[f32; 2], window_aspect_ratio: f32, image_aspect_ratio: f32) -> Vec<Vertex> {
+    let (scale_x, scale_y) = quad_scale(zoom_level, window_aspect_ratio, image_aspect_ratio);
+
+    [
         Vertex { position: [-scale_x + pan_offset[0], -scale_y + pan_offset[1]], texcoord: [0.0, 1.0] }, // Bottom-left
         Vertex { position: [-scale_x + pan_offset[0], scale_y + pan_offset[1]], texcoord: [0.0, 0.0] },  // Top-left
         Vertex { position: [scale_x + pan_offset[0], scale_y + pan_offset[1]], texcoord: [1.0, 0.0] },   // Top-right
         Vertex { position: [scale_x + pan_offset[0], scale_y + pan_offset[1]], texcoord: [1.0, 0.0] },   // Top-right (repeated)
         Vertex { position: [scale_x + pan_offset[0], -scale_y + pan_offset[1]], texcoord: [1.0, 1.0] },  // Bottom-right
         Vertex { position: [-scale_x + pan_offset[0], -scale_y + pan_offset[1]], texcoord: [0.0, 1.0] }, // Bottom-left (repeated)
-    ].to_vec();
-
-    vertex_data
+    ].to_vec()
 }
 
 
+// effects render offscreen as two stages, a base and the selected effect on top.
+// the composite pass blends the effect stage over the base stage onto the screen.
+// images over the texture limit are split into tiles that run through the stages one
+// after another, see TileGrid
 struct RenderingPipeline {
     render_pipeline: wgpu::RenderPipeline,
+    composite_pipeline: wgpu::RenderPipeline,
     texture_bind_group_layout: wgpu::BindGroupLayout,
-    texture_bind_group: wgpu::BindGroup,
-    parameters_bind_group: wgpu::BindGroup,
-    parameters_buffer: wgpu::Buffer,
+    parameters_bind_group_layout: wgpu::BindGroupLayout,
+    // the textures effects look things up in, loaded from files the controls pick
+    asset_bind_group_layout: wgpu::BindGroupLayout,
+    asset_bind_group: wgpu::BindGroup,
+    lut_texture: wgpu::Texture,
+    glyph_texture: wgpu::Texture,
+    brush_texture: wgpu::Texture,
+    // the same for every image, see BlueNoise
+    blue_noise_texture: wgpu::Texture,
+    composite_bind_group_layout: wgpu::BindGroupLayout,
+    composite_bind_group: wgpu::BindGroup,
+    composite_buffer: wgpu::Buffer,
+    // covers the whole stage output, unlike vertex_buffer which follows pan and zoom
+    quad_buffer: wgpu::Buffer,
     vertex_buffer: wgpu::Buffer,
     image_aspect_ratio: f32,
+    // 8 bit display encoded copy for generating masks
+    image: image::RgbaImage,
+    image_source: Source,
+    second_source: Option<Source>,
+    // one per stage, base then effect
+    masks: [Mask; 2],
+    base_input: Input,
+    effect_input: Input,
+    base: Stage,
+    effect: Stage,
+    // the stage outputs stitched together when the image is tiled, what the composite reads then
+    stitch: Option<Stitch>,
+    // what each stage last rendered, base then effect, None once its output is stale.
+    // stages whose key is unchanged keep their output, so pans, zooms and composite
+    // changes only draw the composite again
+    rendered: [Option<StageKey>; 2],
+    // the frame animated grain is drawn for, see Controls::animating
+    frame: u32,
+}
+
+// the loaded image an input reads. until a second image is loaded it falls back to the original
+fn input_image<'a>(image_source: &'a Source, second_source: &'a Option<Source>, input: Input) -> &'a Source {
+    match input {
+        Input::second_image => second_source.as_ref().unwrap_or(image_source),
+        _ => image_source,
+    }
+}
+
+// what a stage reads for one tile, previous is the base output for the effect stage
+fn stage_sources<'a>(image_source: &'a Source, second_source: &'a Option<Source>, input: Input, previous: Option<&'a wgpu::Texture>, tile: usize) -> Sources<'a> {
+    let second_image = input_image(image_source, second_source, Input::second_image);
+    let source = match (input, previous) {
+        (Input::previous_stage, Some(previous)) => previous,
+        _ => input_image(image_source, second_source, input).texture(tile),
+    };
+    Sources {
+        source,
+        original: (image_source.texture(tile), image_source.color_space()),
+        previous_stage: previous,
+        second_image: (second_image.texture(tile), second_image.color_space()),
+    }
+}
+
+impl RenderingPipeline {
+    // the parameters for one tile, effects with patterns anchored to the image need to know where it lies.
+    // what can't be drawn tile by tile is left out of tiled images, see untiled_only
+    fn tile_params(&self, controls: &Controls, tile: &Tile) -> Parameters {
+        let params = Parameters {
+            tile_x: tile.region.x,
+            tile_y: tile.region.y,
+            image_width: self.grid().width,
+            image_height: self.grid().height,
+            frame: self.frame,
+            ..controls.params()
+        };
+        match self.grid().is_tiled() {
+            true => Parameters { intensity: 0.0, distortion_k1: 0.0, distortion_k2: 0.0, crt_curvature: 0.0, ..params },
+            false => params,
+        }
+    }
+
+    // images keep the space they were stored in, stage outputs are in the working space
+    fn source_space(&self, input: Input, working_space: ColorSpace) -> ColorSpace {
+        match input {
+            Input::previous_stage => working_space,
+            _ => input_image(&self.image_source, &self.second_source, input).color_space(),
+        }
+    }
+
+    fn grid(&self) -> &TileGrid {
+        self.image_source.grid()
+    }
+
+    fn rebuild_stages(&mut self, device: &wgpu::Device, params: &Parameters) {
+        let tiled = self.grid().is_tiled();
+        let sources = stage_sources(&self.image_source, &self.second_source, self.base_input, None, 0);
+        self.base = Stage::new(device, "base", &self.texture_bind_group_layout, &self.parameters_bind_group_layout, &sources, &self.masks[0], params, tiled);
+        self.rendered[0] = None;
+        self.rebuild_effect_stage(device, params);
+    }
+
+    fn rebuild_effect_stage(&mut self, device: &wgpu::Device, params: &Parameters) {
+        let tiled = self.grid().is_tiled();
+        let sources = stage_sources(&self.image_source, &self.second_source, self.effect_input, Some(self.base.output()), 0);
+        self.effect = Stage::new(device, "effect", &self.texture_bind_group_layout, &self.parameters_bind_group_layout, &sources, &self.masks[1], params, tiled);
+        self.rendered[1] = None;
+        // a new stitch starts out empty, the base is stitched again with the effect
+        if tiled {
+            self.rendered[0] = None;
+        }
+
+        self.stitch = tiled.then(|| {
+            let (width, height) = self.grid().display_size(device.limits().max_texture_dimension_2d);
+            Stitch::new(device, self.base.output_view(), self.effect.output_view(), width, height)
+        });
+        let (base_view, effect_view) = match &self.stitch {
+            Some(stitch) => (stitch.base_view(), stitch.effect_view()),
+            None => (self.base.display_view(), self.effect.display_view()),
+        };
+        self.composite_bind_group = create_composite_bind_group(
+            device,
+            &self.composite_bind_group_layout,
+            base_view,
+            effect_view,
+            &self.composite_buffer,
+        );
+    }
+
+    // points the stages and masks at one tile of a tiled image
+    fn select_tile(&mut self, device: &wgpu::Device, tile: usize) {
+        for mask in self.masks.iter_mut() {
+            mask.select_tile(device, tile);
+        }
+        let sources = stage_sources(&self.image_source, &self.second_source, self.base_input, None, tile);
+        self.base.set_source(device, &self.texture_bind_group_layout, &sources, &self.masks[0]);
+        // the previous stage is the base output, which always holds the current tile
+        let sources = stage_sources(&self.image_source, &self.second_source, self.effect_input, Some(self.base.output()), tile);
+        self.effect.set_source(device, &self.texture_bind_group_layout, &sources, &self.masks[1]);
+    }
+
+    fn stage_keys(&self, controls: &Controls) -> [StageKey; 2] {
+        let params = Parameters { frame: self.frame, ..controls.params() };
+        let working_space = controls.color_space;
+        let mask = |i: usize| controls.mask_enabled[i].then_some(controls.mask_feather[i]);
+        [
+            StageKey::new(controls.base_shader, controls.prefilter, &params, mask(0), self.source_space(self.base_input, working_space), working_space),
+            StageKey::new(controls.selected_shader.unwrap(), controls.prefilter, &params, mask(1), self.source_space(self.effect_input, working_space), working_space),
+        ]
+    }
+
+    // the stages that need to render again, the effect follows the base it reads
+    fn stale_stages(&self, keys: &[StageKey; 2]) -> [bool; 2] {
+        let base = self.rendered[0] != Some(keys[0]);
+        let reads_base = self.effect_input == Input::previous_stage || keys[1].shader.inputs().reads(Input::previous_stage);
+        let effect = self.rendered[1] != Some(keys[1]) || (base && reads_base);
+        [base, effect]
+    }
+
+    // the selected stages for the selected tile, the only one unless the image is tiled
+    #[allow(clippy::too_many_arguments)]
+    fn render_stages(&mut self, encoder: &mut wgpu::CommandEncoder, queue: &wgpu::Queue, device: &wgpu::Device, controls: &Controls, tile: &Tile, stages: [bool; 2], timer: &mut GpuTimer) {
+        let params = self.tile_params(controls, tile);
+        let working_space = controls.color_space;
+        let base_space = self.source_space(self.base_input, working_space);
+        let effect_space = self.source_space(self.effect_input, working_space);
+        let masks = [0, 1].map(|i| controls.mask_enabled[i].then_some((&self.masks[i], controls.mask_feather[i])));
+
+        if stages[0] {
+            self.base.render(encoder, queue, device, &self.render_pipeline, &self.quad_buffer, &self.asset_bind_group, controls.base_shader, controls.prefilter, &params, masks[0], base_space, working_space, timer);
+        }
+        if stages[1] {
+            self.effect.render(encoder, queue, device, &self.render_pipeline, &self.quad_buffer, &self.asset_bind_group, controls.selected_shader.unwrap(), controls.prefilter, &params, masks[1], effect_space, working_space, timer);
+        }
+    }
+
+    // renders every tile of a tiled image, each in its own submission since uniforms
+    // written through the queue only take effect per submission.
+    // finish records what happens to the tile's stage outputs and submits the encoder
+    fn render_tiles(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        controls: &Controls,
+        timer: &mut GpuTimer,
+        mut finish: impl FnMut(&RenderingPipeline, &mut GpuTimer, &Tile, wgpu::CommandEncoder),
+    ) {
+        let tiles = self.grid().tiles.clone();
+        self.measure_exposure(device, queue, controls, &tiles, timer);
+
+        for (i, tile) in tiles.iter().enumerate() {
+            let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor { label: Some("Tile Encoder") });
+            self.select_tile(device, i);
+            self.render_stages(&mut encoder, queue, device, controls, tile, [true, true], timer);
+            finish(self, timer, tile, encoder);
+        }
+    }
+
+    // auto exposure over the cores of all tiles, ahead of rendering them,
+    // so every tile is tone mapped with the same exposure
+    fn measure_exposure(&mut self, device: &wgpu::Device, queue: &wgpu::Queue, controls: &Controls, tiles: &[Tile], timer: &mut GpuTimer) {
+        if !controls.auto_exposure {
+            return;
+        }
+        let working_space = controls.color_space;
+        let base_space = self.source_space(self.base_input, working_space);
+        let effect_space = self.source_space(self.effect_input, working_space);
+        let submit = |mut encoder: wgpu::CommandEncoder, timer: &mut GpuTimer| {
+            timer.end(&mut encoder);
+            queue.submit(Some(encoder.finish()));
+        };
+        let create_encoder = || device.create_command_encoder(&wgpu::CommandEncoderDescriptor { label: Some("Exposure Encoder") });
+
+        if controls.base_shader == Shader::tone_mapping {
+            for (i, tile) in tiles.iter().enumerate() {
+                let mut encoder = create_encoder();
+                self.select_tile(device, i);
+                self.base.measure_exposure(&mut encoder, queue, Shader::tone_mapping, base_space, working_space, tile.core_in_region(), timer);
+                submit(encoder, timer);
+            }
+            let mut encoder = create_encoder();
+            self.base.average_exposure(&mut encoder, timer);
+            submit(encoder, timer);
+        }
+
+        if controls.selected_shader == Some(Shader::tone_mapping) {
+            for (i, tile) in tiles.iter().enumerate() {
+                let mut encoder = create_encoder();
+                self.select_tile(device, i);
+                // the effect measures what the base renders for this tile
+                if self.effect_input == Input::previous_stage {
+                    let params = self.tile_params(controls, tile);
+                    let mask = controls.mask_enabled[0].then_some((&self.masks[0], controls.mask_feather[0]));
+                    self.base.render(&mut encoder, queue, device, &self.render_pipeline, &self.quad_buffer, &self.asset_bind_group, controls.base_shader, controls.prefilter, &params, mask, base_space, working_space, timer);
+                }
+                self.effect.measure_exposure(&mut encoder, queue, Shader::tone_mapping, effect_space, working_space, tile.core_in_region(), timer);
+                submit(encoder, timer);
+            }
+            let mut encoder = create_encoder();
+            self.effect.average_exposure(&mut encoder, timer);
+            submit(encoder, timer);
+        }
+    }
+
+    // composites two stage outputs of width x height into a float image, after the passes
+    // already recorded in encoder. linear selects linear or display encoded values
+    #[allow(clippy::too_many_arguments)]
+    fn composite_offscreen(
+        &self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        mut encoder: wgpu::CommandEncoder,
+        base_view: &wgpu::TextureView,
+        effect_view: &wgpu::TextureView,
+        width: u32,
+        height: u32,
+        controls: &Controls,
+        linear: bool,
+    ) -> image::Rgba32FImage {
+        let extent = wgpu::Extent3d { width, height, depth_or_array_layers: 1 };
+        let format = wgpu::TextureFormat::Rgba32Float;
+
+        let output_texture = device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("Export Texture"),
+            size: extent,
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::COPY_SRC,
+            view_formats: &[],
+        });
+
+        let params = controls.params();
+        let composite_buffer = device.create_buffer_init(
+            &wgpu::util::BufferInitDescriptor {
+                label: Some("Export Composite Buffer"),
+                contents: bytemuck::cast_slice(&[CompositeParameters {
+                    blend_mode: params.blend_mode,
+                    opacity: params.opacity,
+                    working_space: controls.color_space.get_index(),
+                    output_linear: linear as u32,
+                    exposure: 0.0,
+                    compress_highlights: 0,
+                    // exports are 1:1
+                    magnification: Magnification::linear.get_index(),
+                    _padding: 0,
+                }]),
+                usage: wgpu::BufferUsages::UNIFORM
+            }
+        );
+        let composite_pipeline = create_composite_pipeline(device, &self.composite_bind_group_layout, format);
+        let composite_bind_group = create_composite_bind_group(
+            device,
+            &self.composite_bind_group_layout,
+            base_view,
+            effect_view,
+            &composite_buffer,
+        );
+
+        {
+            let output_view = output_texture.create_view(&wgpu::TextureViewDescriptor::default());
+            let mut render_pass = Scene::clear(&output_view, &mut encoder, Color::TRANSPARENT);
+            render_pass.set_pipeline(&composite_pipeline);
+            render_pass.set_bind_group(0, &composite_bind_group, &[]);
+            render_pass.set_vertex_buffer(0, self.quad_buffer.slice(..));
+            render_pass.draw(0..6, 0..1);
+        }
+
+        queue.submit(Some(encoder.finish()));
+
+        // read back in bands of rows that each fit in a buffer
+        let padded_bytes_per_row = (16 * width).div_ceil(256) * 256;
+        let band_height = ((device.limits().max_buffer_size / padded_bytes_per_row as u64) as u32).clamp(1, height);
+        let mut data = Vec::with_capacity((width * height * 4) as usize);
+
+        for band_y in (0..height).step_by(band_height as usize) {
+            let rows = band_height.min(height - band_y);
+            let output_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+                label: Some("Export Buffer"),
+                size: padded_bytes_per_row as u64 * rows as u64,
+                usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::MAP_READ,
+                mapped_at_creation: false,
+            });
+
+            let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor { label: Some("Export Copy Encoder") });
+            encoder.copy_texture_to_buffer(
+                wgpu::ImageCopyTexture {
+                    aspect: wgpu::TextureAspect::All,
+                    texture: &output_texture,
+                    mip_level: 0,
+                    origin: wgpu::Origin3d { x: 0, y: band_y, z: 0 },
+                },
+                wgpu::ImageCopyBuffer {
+                    buffer: &output_buffer,
+                    layout: wgpu::ImageDataLayout {
+                        offset: 0,
+                        bytes_per_row: Some(padded_bytes_per_row),
+                        rows_per_image: Some(rows),
+                    },
+                },
+                wgpu::Extent3d { width, height: rows, depth_or_array_layers: 1 },
+            );
+            queue.submit(Some(encoder.finish()));
+
+            let slice = output_buffer.slice(..);
+            slice.map_async(wgpu::MapMode::Read, |_| {});
+            device.poll(wgpu::Maintain::Wait);
+            {
+                let bytes = slice.get_mapped_range();
+                for row in bytes.chunks(padded_bytes_per_row as usize) {
+                    data.extend_from_slice(bytemuck::cast_slice::<u8, f32>(&row[..(16 * width) as usize]));
+                }
+            }
+            output_buffer.unmap();
+        }
+
+        image::Rgba32FImage::from_raw(width, height, data).unwrap()
+    }
 }
 
 pub struct Scene {
     pipeline: RenderingPipeline,
+    loader: Loader,
     rendering_image: String,
+    // last image handed to the loader, the current one stays up until it arrives
+    requested_image: String,
+    requested_second_image: String,
+    rendering_lut: String,
+    // font, ramp and cell width the glyph atlas was rasterized with
+    rendering_glyphs: Option<(String, String, u32)>,
+    // the brush file the brush texture was loaded from, empty for the bundled brush
+    rendering_brush: Option<String>,
+    texture_format: wgpu::TextureFormat,
+    timer: GpuTimer,
+    // what the image was last drawn without for being tiled, see untiled_only
+    left_out: Option<&'static str>,
+}
+
+// what of the selected effects only works on the whole image. lens distortion and aberration
+// and the curve of a crt screen move pixels further than the halo of a tile reaches,
+// tiled images are drawn without them
+fn untiled_only(controls: &Controls) -> Option<&'static str> {
+    let shaders = [Some(controls.base_shader), controls.selected_shader];
+    let lens = controls.intensity != 0.0 || controls.distortion_k1 != 0.0 || controls.distortion_k2 != 0.0;
+    if shaders.contains(&Some(Shader::chromatic_aberration)) && lens {
+        Some("lens distortion and chromatic aberration")
+    } else if shaders.contains(&Some(Shader::crt)) && controls.crt_curvature != 0.0 {
+        Some("crt curvature")
+    } else {
+        None
+    }
 }
 
 impl Scene {
@@ -78,12 +739,22 @@ impl Scene {
         texture_format: wgpu::TextureFormat,
         queue: &wgpu::Queue,
         shader: Option<Shader>,
+        loader: Loader,
     ) -> Scene {
-        let (rendering_pipeline, rendering_image) = build_pipeline(device, texture_format, queue, shader.unwrap());
+        let rendering_pipeline = build_pipeline(device, texture_format, queue, shader.unwrap());
 
         Scene { 
             pipeline: rendering_pipeline,
-            rendering_image
+            loader,
+            rendering_image: String::new(),
+            requested_image: String::new(),
+            requested_second_image: String::new(),
+            rendering_lut: String::new(),
+            rendering_glyphs: None,
+            rendering_brush: None,
+            texture_format,
+            timer: GpuTimer::new(device, queue),
+            left_out: None,
         }
     }
 
@@ -118,155 +789,714 @@ impl Scene {
         })
     }
 
-    pub fn update_texture_bind_group(&mut self, texture_bind_group_layout: wgpu::BindGroupLayout, texture_bind_group: wgpu::BindGroup) {
-        self.pipeline.texture_bind_group_layout = texture_bind_group_layout;
-        self.pipeline.texture_bind_group = texture_bind_group;
+    // loads newly selected files and renders both stages offscreen,
+    // ahead of the on screen composite. returns what happened to the loads for the controls
+    pub fn prepare(
+        &mut self,
+        encoder: &mut wgpu::CommandEncoder,
+        queue: &wgpu::Queue,
+        device: &wgpu::Device,
+        controls: &Controls,
+    ) -> Vec<Message> {
+        let params = controls.params();
+        let mut messages = Vec::new();
+
+        if controls.selected_lut != self.rendering_lut {
+
+            self.rendering_lut = controls.selected_lut.clone();
+
+            match Lut::open(&controls.selected_lut) {
+                Ok(lut) => {
+                    self.pipeline.lut_texture = lut.create_texture(device, queue);
+                    self.pipeline.asset_bind_group = create_asset_bind_group(device, &self.pipeline.asset_bind_group_layout, &self.pipeline.lut_texture, &self.pipeline.glyph_texture, &self.pipeline.brush_texture, &self.pipeline.blue_noise_texture);
+                    self.pipeline.rendered = [None, None];
+                }
+                Err(error) => {
+                    messages.push(Message::ShowError(format!("could not load lut: {error}")));
+                }
+            }
+        }
+
+        let glyphs = (controls.glyph_font.clone(), controls.glyph_ramp.clone(), controls.glyph_cell);
+        if self.rendering_glyphs.as_ref() != Some(&glyphs) {
+            // a font that fails to load falls back to the bundled one, so the atlas
+            // always holds the ramp the effect indexes
+            let atlas = GlyphAtlas::new(&glyphs.0, &glyphs.1, glyphs.2).unwrap_or_else(|error| {
+                messages.push(Message::ShowError(format!("could not load font: {error}")));
+                GlyphAtlas::new("", &glyphs.1, glyphs.2).unwrap()
+            });
+            self.pipeline.glyph_texture = atlas.create_texture(device, queue);
+            self.pipeline.asset_bind_group = create_asset_bind_group(device, &self.pipeline.asset_bind_group_layout, &self.pipeline.lut_texture, &self.pipeline.glyph_texture, &self.pipeline.brush_texture, &self.pipeline.blue_noise_texture);
+            self.pipeline.rendered = [None, None];
+            self.rendering_glyphs = Some(glyphs);
+        }
+
+        if self.rendering_brush.as_ref() != Some(&controls.stroke_brush) {
+            // like fonts, a brush that fails to load falls back to the bundled one
+            let brush = BrushTexture::new(&controls.stroke_brush).unwrap_or_else(|error| {
+                messages.push(Message::ShowError(format!("could not load brush: {error}")));
+                BrushTexture::new("").unwrap()
+            });
+            self.pipeline.brush_texture = brush.create_texture(device, queue);
+            self.pipeline.asset_bind_group = create_asset_bind_group(device, &self.pipeline.asset_bind_group_layout, &self.pipeline.lut_texture, &self.pipeline.glyph_texture, &self.pipeline.brush_texture, &self.pipeline.blue_noise_texture);
+            self.pipeline.rendered = [None, None];
+            self.rendering_brush = Some(controls.stroke_brush.clone());
+        }
+
+        let mut rebuild = false;
+
+        // loads that finished since the last frame. ones that were replaced
+        // by a newer selection while decoding are dropped
+        while let Some(loaded) = self.loader.try_recv() {
+            match (loaded.slot, loaded.result) {
+                (Slot::image, _) if loaded.path != self.requested_image => {}
+                (Slot::image, Ok(decoded)) => {
+                    self.load_image(device, decoded);
+                    self.rendering_image = loaded.path.clone();
+                    messages.push(Message::ImageLoaded(loaded.path));
+                    rebuild = true;
+                }
+                // the previous image stays up, the controls switch back to it
+                (Slot::image, Err(error)) => {
+                    messages.push(Message::ImageFailed(loaded.path, error));
+                }
+                (Slot::second_image, _) if loaded.path != self.requested_second_image => {}
+                // resampled to the size of an image that has since been replaced
+                (Slot::second_image, Ok(decoded)) if decoded.preview.dimensions() != self.pipeline.image.dimensions() => {}
+                (Slot::second_image, Ok(decoded)) => {
+                    self.pipeline.second_source = Some(decoded.source);
+                    rebuild = true;
+                }
+                (Slot::second_image, Err(error)) => {
+                    messages.push(Message::SecondImageFailed(error));
+                }
+            }
+        }
+
+        if controls.selected_image != self.requested_image {
+            // switching back to the image on screen cancels the pending load
+            if self.requested_image != self.rendering_image && controls.selected_image == self.rendering_image {
+                messages.push(Message::ImageLoaded(self.rendering_image.clone()));
+            }
+            self.requested_image = controls.selected_image.clone();
+            if self.requested_image != self.rendering_image {
+                self.loader.load(Slot::image, self.requested_image.clone(), None);
+                messages.push(Message::ImageLoading(self.requested_image.clone()));
+            }
+        }
+
+        // waits for the first image, whose size it is resampled to
+        if controls.second_image != self.requested_second_image && !self.rendering_image.is_empty() {
+            self.requested_second_image = controls.second_image.clone();
+            if controls.second_image.is_empty() {
+                self.pipeline.second_source = None;
+                rebuild = true;
+            } else {
+                self.loader.load(Slot::second_image, controls.second_image.clone(), Some(self.pipeline.image.dimensions()));
+            }
+        }
+
+        let mut rebuild_effect = false;
+
+        if controls.base_input != self.pipeline.base_input {
+            self.pipeline.base_input = controls.base_input;
+            rebuild = true;
+        }
+
+        if controls.effect_input != self.pipeline.effect_input {
+            self.pipeline.effect_input = controls.effect_input;
+            rebuild_effect = true;
+        }
+
+        if rebuild {
+            self.pipeline.rebuild_stages(device, &params);
+        } else if rebuild_effect {
+            self.pipeline.rebuild_effect_stage(device, &params);
+        }
+
+        let left_out = if self.pipeline.grid().is_tiled() { untiled_only(controls) } else { None };
+        if left_out != self.left_out {
+            if let Some(effect) = left_out {
+                messages.push(Message::ShowError(format!("{effect} can't be drawn tile by tile, this image is too large for it")));
+            }
+            self.left_out = left_out;
+        }
+
+        // a mask only changes what a stage renders while it gates the effect or the effect reads it
+        let pipeline = &mut self.pipeline;
+        let shaders = [Some(controls.base_shader), controls.selected_shader];
+        for (i, mask) in pipeline.masks.iter_mut().enumerate() {
+            if mask.upload(queue) && (controls.mask_enabled[i] || shaders[i].is_some_and(|shader| shader.inputs().mask)) {
+                pipeline.rendered[i] = None;
+            }
+        }
+
+        let timer = &mut self.timer;
+        if controls.show_timings {
+            timer.begin();
+        }
+
+        pipeline.frame = if controls.animating() { pipeline.frame.wrapping_add(1) } else { 0 };
+        let keys = pipeline.stage_keys(controls);
+        let stale = pipeline.stale_stages(&keys);
+        if pipeline.grid().is_tiled() {
+            // tiles keep no outputs of their own, every tile of both stages renders again
+            if stale.contains(&true) {
+                pipeline.render_tiles(device, queue, controls, timer, |pipeline, timer, tile, mut encoder| {
+                    timer.mark(&mut encoder, "stitch");
+                    pipeline.stitch.as_ref().unwrap().encode(&mut encoder, queue, pipeline.grid(), tile);
+                    timer.end(&mut encoder);
+                    queue.submit(Some(encoder.finish()));
+                });
+                timer.mark(encoder, "mipmaps");
+                pipeline.stitch.as_ref().unwrap().encode_mipmaps(encoder);
+            }
+        } else if stale.contains(&true) {
+            let tile = pipeline.grid().tiles[0];
+            pipeline.render_stages(encoder, queue, device, controls, &tile, stale, timer);
+            timer.mark(encoder, "mipmaps");
+            if stale[0] {
+                pipeline.base.encode_mipmaps(encoder);
+            }
+            if stale[1] {
+                pipeline.effect.encode_mipmaps(encoder);
+            }
+        }
+        pipeline.rendered = keys.map(Some);
+        // the composite is drawn by the caller into encoder, up to finish_timing
+        timer.mark(encoder, "composite");
+
+        queue.write_buffer(&pipeline.composite_buffer, 0, bytemuck::cast_slice(&[CompositeParameters {
+            blend_mode: params.blend_mode,
+            opacity: params.opacity,
+            working_space: controls.color_space.get_index(),
+            output_linear: self.texture_format.is_srgb() as u32,
+            exposure: controls.view_exposure,
+            compress_highlights: controls.compress_highlights as u32,
+            magnification: controls.magnification.get_index(),
+            _padding: 0,
+        }]));
+
+        messages
     }
 
+    // replaces the main image, its masks start over at its size
+    fn load_image(&mut self, device: &wgpu::Device, decoded: Decoded) {
+        let (width, height) = decoded.preview.dimensions();
+
+        let image_aspect_ratio = width as f32 / height as f32;
+        self.pipeline.image_aspect_ratio = image_aspect_ratio;
+
+        self.pipeline.image_source = decoded.source;
+        self.pipeline.image = decoded.preview;
+        self.pipeline.masks = [Mask::new(device, self.pipeline.grid()), Mask::new(device, self.pipeline.grid())];
+        // the second image follows the size of the first, so it is loaded again
+        self.requested_second_image = String::new();
+    }
+
+    #[allow(clippy::too_many_arguments)]
     pub fn draw<'a>(
         &'a mut self, render_pass: &mut wgpu::RenderPass<'a>,
         queue: &wgpu::Queue,
-        device: &wgpu::Device,
+        _device: &wgpu::Device,
         window_aspect_ratio: f32,
-        x: i32,
-        y: i32,
-        width: u32, 
-        height: u32,
+        _x: i32,
+        _y: i32,
+        _width: u32, 
+        _height: u32,
         pan_offset: &[f32; 2],
         zoom_level: &f32,
-        params: &[u8],
-        image_file: &str,
     ) {
 
-        
-        if image_file != self.rendering_image {
+        // UPDATE VERTEX CANVAS POSITION
+        let vertex_data = update_vertex_data(zoom_level, pan_offset, window_aspect_ratio, self.pipeline.image_aspect_ratio);
+        queue.write_buffer(&self.pipeline.vertex_buffer, 0, unsafe {
+            std::slice::from_raw_parts(
+                vertex_data.as_ptr() as *const u8,
+                vertex_data.len() * std::mem::size_of::<Vertex>(),
+            )
+        });
 
-            self.rendering_image = String::from(image_file);
+        // render_pass.set_viewport(x as f32, y as f32, width as f32, height as f32, 0.0, 1.0);
+        render_pass.set_pipeline(&self.pipeline.composite_pipeline);
+        render_pass.set_bind_group(0, &self.pipeline.composite_bind_group, &[]);
+        render_pass.set_vertex_buffer(0, self.pipeline.vertex_buffer.slice(..));
+        render_pass.draw(0..6, 0..1);
 
-            let img = image::open(image_file).expect("failed to open image");
-            let img_ = img.to_rgba8();
-            let (mut width, mut height) = img_.dimensions();
-            let image = img.resize(width, height, image::imageops::FilterType::Gaussian).to_rgba8();
-            let image_data = image.into_vec();
-        
-            let image_aspect_ratio = width as f32 / height as f32;
-            self.pipeline.image_aspect_ratio = image_aspect_ratio;
         
-            let image_texture = device.create_texture(
-                &wgpu::TextureDescriptor {
-                    label: Some("Image Texture"),
-                    size: wgpu::Extent3d {
-                        width,
-                        height,
-                        depth_or_array_layers: 1
+    }
+
+    // renders both stages on the next prepare even if nothing they depend on changed
+    pub fn invalidate(&mut self) {
+        self.pipeline.rendered = [None, None];
+    }
+
+    // ends the frame's gpu timing after the composite is drawn into encoder,
+    // timings_submitted follows once encoder is submitted
+    pub fn finish_timing(&mut self, encoder: &mut wgpu::CommandEncoder) {
+        self.timer.finish(encoder);
+    }
+
+    pub fn timings_submitted(&mut self) {
+        self.timer.after_submit();
+    }
+
+    pub fn gpu_timing_supported(&self) -> bool {
+        self.timer.is_supported()
+    }
+
+    // per pass gpu times of an earlier frame once they are read back, wait blocks until then
+    pub fn poll_timings(&mut self, device: &wgpu::Device, wait: bool) -> Option<Vec<(String, f32)>> {
+        self.timer.poll(device, wait)
+    }
+
+    // the frame's times together with the sizes they were measured at
+    pub fn timings(&self, passes: Vec<(String, f32)>, frame: f32, cpu: f32) -> Timings {
+        let (width, height) = self.pipeline.image.dimensions();
+        let (stage_width, stage_height) = self.pipeline.grid().tile_size();
+        Timings {
+            passes,
+            frame,
+            cpu,
+            width,
+            height,
+            stage_width,
+            stage_height,
+            tiles: self.pipeline.grid().tiles.len(),
+        }
+    }
+
+    // image texel under a cursor position in window pixels, following the same
+    // pan and zoom placement as the quad in update_vertex_data
+    pub fn cursor_to_texel(&self, cursor: (f32, f32), window_size: (f32, f32), pan_offset: &[f32; 2], zoom_level: &f32) -> (f32, f32) {
+        let (scale_x, scale_y) = quad_scale(zoom_level, window_size.0 / window_size.1, self.pipeline.image_aspect_ratio);
+
+        let x = cursor.0 / window_size.0 * 2.0 - 1.0;
+        let y = 1.0 - cursor.1 / window_size.1 * 2.0;
+
+        let u = (x - pan_offset[0] + scale_x) / (2.0 * scale_x);
+        let v = 1.0 - (y - pan_offset[1] + scale_y) / (2.0 * scale_y);
+
+        (u * self.pipeline.image.width() as f32, v * self.pipeline.image.height() as f32)
+    }
+
+    // brush stroke on the selected mask between two texel positions
+    pub fn paint_mask(&mut self, controls: &Controls, from: (f32, f32), to: (f32, f32)) {
+        let value = match controls.brush {
+            Brush::paint => 1.0,
+            Brush::erase => 0.0,
+            Brush::off => return,
+        };
+        let mask = &mut self.pipeline.masks[controls.mask_layer.get_index()];
+        mask.stroke(from, to, controls.brush_size * 0.5, controls.brush_hardness, value);
+    }
+
+    pub fn apply_mask_command(&mut self, device: &wgpu::Device, queue: &wgpu::Queue, controls: &Controls, command: &MaskCommand) -> Result<(), String> {
+        // read back ahead of borrowing the mask
+        let labels = match command {
+            MaskCommand::superpixels => self.superpixel_labels(device, queue, controls)?,
+            _ => Vec::new(),
+        };
+        let mask = &mut self.pipeline.masks[controls.mask_layer.get_index()];
+        match command {
+            MaskCommand::load(path) => mask.load(path)?,
+            MaskCommand::luminance => mask.from_luminance(&self.pipeline.image, controls.mask_low, controls.mask_high, controls.mask_softness),
+            MaskCommand::hue => mask.from_hue(&self.pipeline.image, controls.mask_hue, controls.mask_hue_width, controls.mask_softness * 360.0),
+            MaskCommand::invert => mask.invert(),
+            MaskCommand::clear => mask.fill(0),
+            MaskCommand::fill => mask.fill(255),
+            MaskCommand::superpixels => mask.from_regions(&self.pipeline.image, &labels, controls.mask_low, controls.mask_high, controls.mask_softness),
+        }
+        Ok(())
+    }
+
+    // the region of every pixel in the last render of the superpixels effect
+    fn superpixel_labels(&self, device: &wgpu::Device, queue: &wgpu::Queue, controls: &Controls) -> Result<Vec<u32>, String> {
+        if self.pipeline.grid().is_tiled() {
+            return Err(String::from("superpixel masks are only made for images under the texture size limit"));
+        }
+        let stage = self.stage_showing(controls, Shader::superpixels).ok_or("the superpixels effect is not selected")?;
+        let buffer = stage.read_compute_buffer(device, queue, &SUPERPIXELS).ok_or("the superpixels effect has not rendered yet")?;
+        let (width, height) = self.pipeline.image.dimensions();
+        superpixels::labels(&buffer, width, height).map(<[u32]>::to_vec).ok_or_else(|| String::from("the image is too large to hold its superpixels"))
+    }
+
+    // the stage whose effect shows for shader, the effect stage on top when both run it
+    fn stage_showing(&self, controls: &Controls, shader: Shader) -> Option<&Stage> {
+        if controls.selected_shader == Some(shader) {
+            Some(&self.pipeline.effect)
+        } else if controls.base_shader == shader {
+            Some(&self.pipeline.base)
+        } else {
+            None
+        }
+    }
+
+    // bakes the current stages and their composite into a .cube file by rendering an
+    // identity lattice through them. only meaningful when both effects are pure per-pixel
+    // color transforms of the original image.
+    pub fn export_cube(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        controls: &Controls,
+        size: u32,
+        path: &str,
+    ) -> Result<(), String> {
+        let width = size * size;
+        let height = size;
+        let extent = wgpu::Extent3d { width, height, depth_or_array_layers: 1 };
+
+        // the lattice is laid out with red along x inside each blue slice and green along y.
+        // it holds display encoded values, like the images the viewer loads
+        let identity = Lut::identity(size);
+        let mut lattice: Vec<u16> = vec![0; (width * height * 4) as usize];
+        for (i, [r, g, b]) in identity.data.iter().enumerate() {
+            let i = i as u32;
+            let (x, y) = (i % size + (i / (size * size)) * size, (i / size) % size);
+            let texel = ((y * width + x) * 4) as usize;
+            lattice[texel] = f16::from_f32(*r).to_bits();
+            lattice[texel + 1] = f16::from_f32(*g).to_bits();
+            lattice[texel + 2] = f16::from_f32(*b).to_bits();
+            lattice[texel + 3] = f16::ONE.to_bits();
+        }
+
+        let lattice_texture = device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("LUT Lattice Texture"),
+            size: extent,
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: wgpu::TextureFormat::Rgba16Float,
+            usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
+            view_formats: &[],
+        });
+
+        queue.write_texture(
+            wgpu::ImageCopyTexture {
+                texture: &lattice_texture,
+                mip_level: 0,
+                origin: wgpu::Origin3d::ZERO,
+                aspect: wgpu::TextureAspect::All
+            },
+            bytemuck::cast_slice(&lattice),
+            wgpu::ImageDataLayout {
+                offset: 0,
+                bytes_per_row: Some(8 * width),
+                rows_per_image: Some(height),
+            },
+            extent,
+        );
+
+        // the smoothed texture would hold the filtered lattice, not the lattice itself
+        let params = Parameters { prefilter: Prefilter::none.get_index(), ..controls.params() };
+
+        // masks are spatial, so they never apply to a lut
+        let mask = Mask::new(device, &TileGrid::single(width, height));
+        let working_space = controls.color_space;
+        // every image input reads the lattice
+        let lattice = (&lattice_texture, ColorSpace::display);
+        let sources = Sources { source: &lattice_texture, original: lattice, previous_stage: None, second_image: lattice };
+        let mut base = Stage::new(device, "base", &self.pipeline.texture_bind_group_layout, &self.pipeline.parameters_bind_group_layout, &sources, &mask, &params, false);
+        let (effect_source, effect_space) = match controls.effect_input {
+            Input::previous_stage => (base.output(), working_space),
+            _ => (&lattice_texture, ColorSpace::display),
+        };
+        let sources = Sources { source: effect_source, previous_stage: Some(base.output()), ..sources };
+        let mut effect = Stage::new(device, "effect", &self.pipeline.texture_bind_group_layout, &self.pipeline.parameters_bind_group_layout, &sources, &mask, &params, false);
+
+        let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor { label: Some("LUT Export Encoder") });
+        base.render(&mut encoder, queue, device, &self.pipeline.render_pipeline, &self.pipeline.quad_buffer, &self.pipeline.asset_bind_group, controls.base_shader, Prefilter::none, &params, None, ColorSpace::display, working_space, &mut self.timer);
+        effect.render(&mut encoder, queue, device, &self.pipeline.render_pipeline, &self.pipeline.quad_buffer, &self.pipeline.asset_bind_group, controls.selected_shader.unwrap(), Prefilter::none, &params, None, effect_space, working_space, &mut self.timer);
+
+        // .cube files map display encoded values
+        let output = self.pipeline.composite_offscreen(device, queue, encoder, base.output_view(), effect.output_view(), width, height, controls, false);
+
+        let mut lut = Lut {
+            title: String::from("rts export"),
+            size,
+            data: Vec::with_capacity(identity.data.len()),
+        };
+        for i in 0..(size * size * size) {
+            let (x, y) = (i % size + (i / (size * size)) * size, (i / size) % size);
+            let [r, g, b, _] = output.get_pixel(x, y).0;
+            lut.data.push([r, g, b].map(|c| c.clamp(0.0, 1.0)));
+        }
+
+        lut.save_cube(path)
+    }
+
+    // saves the composite at image resolution. exr keeps linear float values,
+    // png and tiff are written display encoded at 16 bits, svg traces the display encoded colors.
+    // the view exposure is not applied.
+    // a tiled image is rendered again tile by tile at full resolution and stitched on the cpu
+    pub fn export_image(&mut self, device: &wgpu::Device, queue: &wgpu::Queue, controls: &Controls, path: &str) -> Result<(), String> {
+        let traced = std::path::Path::new(path).extension().is_some_and(|extension| extension.eq_ignore_ascii_case("svg"));
+        let linear = match image::ImageFormat::from_path(path) {
+            _ if traced => false,
+            Ok(image::ImageFormat::OpenExr) => true,
+            Ok(image::ImageFormat::Png | image::ImageFormat::Tiff) => false,
+            Ok(format) => return Err(format!("can not export {format:?}, use png, tiff, exr or svg")),
+            Err(error) => return Err(error.to_string()),
+        };
+
+        // exports are stills, animated grain is drawn as it is when it stands still
+        let frame = std::mem::take(&mut self.pipeline.frame);
+
+        let (width, height) = self.pipeline.image.dimensions();
+        let output = if self.pipeline.grid().is_tiled() {
+            let mut output = image::Rgba32FImage::new(width, height);
+            self.pipeline.render_tiles(device, queue, controls, &mut self.timer, |pipeline, _, tile, encoder| {
+                let rendered = pipeline.composite_offscreen(device, queue, encoder, pipeline.base.output_view(), pipeline.effect.output_view(), tile.region.width, tile.region.height, controls, linear);
+                let core = tile.core_in_region();
+                let core = image::imageops::crop_imm(&rendered, core.x, core.y, core.width, core.height);
+                image::imageops::replace(&mut output, &*core, tile.core.x as i64, tile.core.y as i64);
+            });
+            output
+        } else {
+            let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor { label: Some("Image Export Encoder") });
+            if frame != 0 {
+                let tile = self.pipeline.grid().tiles[0];
+                self.pipeline.render_stages(&mut encoder, queue, device, controls, &tile, [true, true], &mut self.timer);
+            }
+            self.pipeline.composite_offscreen(device, queue, encoder, self.pipeline.base.output_view(), self.pipeline.effect.output_view(), width, height, controls, linear)
+        };
+
+        // the view goes back to the frame it was on
+        self.pipeline.frame = frame;
+        if frame != 0 {
+            self.pipeline.rendered = [None, None];
+        }
+
+        if traced {
+            // in the colors the crt's console palette or quantization snaps to
+            let svg = vector::svg(&output, controls.crt_palette.get_index(), controls.vector_paths, controls.vector_tolerance, controls.vector_speckle, controls.vector_smoothing);
+            std::fs::write(path, svg).map_err(|error| format!("could not write {path}: {error}"))?;
+        } else {
+            let output = image::DynamicImage::ImageRgba32F(output);
+            let output = if linear { output } else { image::DynamicImage::ImageRgba16(output.to_rgba16()) };
+            output.save(path).map_err(|error| error.to_string())?;
+        }
+
+        match self.stage_showing(controls, Shader::ascii) {
+            Some(stage) if controls.export_text => self.export_text(device, queue, controls, stage, path),
+            _ => Ok(()),
+        }
+    }
+
+    // writes the glyphs the ascii effect drew in stage next to an exported image,
+    // as plain text in .txt and with each glyph in its cell's color in .ans
+    fn export_text(&self, device: &wgpu::Device, queue: &wgpu::Queue, controls: &Controls, stage: &Stage, path: &str) -> Result<(), String> {
+        if self.pipeline.grid().is_tiled() {
+            return Err(String::from("text is only exported for images under the texture size limit"));
+        }
+        let cells = stage.read_compute_buffer(device, queue, &ascii::ASCII).ok_or("the ascii effect has not rendered yet")?;
+
+        let (width, height) = self.pipeline.image.dimensions();
+        let (columns, rows) = ascii::grid(controls.glyph_cell, width, height);
+        let path = std::path::Path::new(path);
+        for (extension, ansi) in [("txt", false), ("ans", true)] {
+            let text = ascii::text(&cells, columns, rows, &controls.glyph_ramp, ansi);
+            let text_path = path.with_extension(extension);
+            std::fs::write(&text_path, text).map_err(|error| format!("could not write {}: {error}", text_path.display()))?;
+        }
+        Ok(())
+    }
+}
+
+fn create_composite_bind_group(
+    device: &wgpu::Device,
+    layout: &wgpu::BindGroupLayout,
+    base_view: &wgpu::TextureView,
+    effect_view: &wgpu::TextureView,
+    composite_buffer: &wgpu::Buffer,
+) -> wgpu::BindGroup {
+    device.create_bind_group(&wgpu::BindGroupDescriptor {
+        label: Some("Composite Bind Group"),
+        layout,
+        entries: &[
+            wgpu::BindGroupEntry {
+                binding: 0,
+                resource: wgpu::BindingResource::TextureView(base_view),
+            },
+            wgpu::BindGroupEntry {
+                binding: 1,
+                resource: wgpu::BindingResource::TextureView(effect_view),
+            },
+            wgpu::BindGroupEntry {
+                binding: 2,
+                // trilinear and anisotropic across the stage outputs' mip chains when zoomed out
+                resource: wgpu::BindingResource::Sampler(&device.create_sampler(&wgpu::SamplerDescriptor {
+                    address_mode_u: wgpu::AddressMode::ClampToEdge,
+                    address_mode_v: wgpu::AddressMode::ClampToEdge,
+                    mag_filter: wgpu::FilterMode::Linear,
+                    min_filter: wgpu::FilterMode::Linear,
+                    mipmap_filter: wgpu::FilterMode::Linear,
+                    anisotropy_clamp: 16,
+                    ..Default::default()
+                })),
+            },
+            wgpu::BindGroupEntry {
+                binding: 3,
+                resource: composite_buffer.as_entire_binding(),
+            },
+        ],
+    })
+}
+
+fn create_asset_bind_group(device: &wgpu::Device, layout: &wgpu::BindGroupLayout, lut_texture: &wgpu::Texture, glyph_texture: &wgpu::Texture, brush_texture: &wgpu::Texture, blue_noise_texture: &wgpu::Texture) -> wgpu::BindGroup {
+    device.create_bind_group(&wgpu::BindGroupDescriptor {
+        label: Some("Asset Bind Group"),
+        layout,
+        entries: &[
+            wgpu::BindGroupEntry {
+                binding: 0,
+                resource: wgpu::BindingResource::TextureView(&lut_texture.create_view(&wgpu::TextureViewDescriptor::default())),
+            },
+            wgpu::BindGroupEntry {
+                binding: 1,
+                resource: wgpu::BindingResource::Sampler(&device.create_sampler(&wgpu::SamplerDescriptor {
+                    address_mode_u: wgpu::AddressMode::ClampToEdge,
+                    address_mode_v: wgpu::AddressMode::ClampToEdge,
+                    address_mode_w: wgpu::AddressMode::ClampToEdge,
+                    mag_filter: wgpu::FilterMode::Linear,
+                    min_filter: wgpu::FilterMode::Linear,
+                    mipmap_filter: wgpu::FilterMode::Nearest,
+                    ..Default::default()
+                })),
+            },
+            wgpu::BindGroupEntry {
+                binding: 2,
+                resource: wgpu::BindingResource::TextureView(&glyph_texture.create_view(&wgpu::TextureViewDescriptor::default())),
+            },
+            wgpu::BindGroupEntry {
+                binding: 3,
+                resource: wgpu::BindingResource::TextureView(&brush_texture.create_view(&wgpu::TextureViewDescriptor::default())),
+            },
+            wgpu::BindGroupEntry {
+                binding: 4,
+                resource: wgpu::BindingResource::TextureView(&blue_noise_texture.create_view(&wgpu::TextureViewDescriptor::default())),
+            },
+        ],
+    })
+}
+
+fn create_render_pipeline(
+    device: &wgpu::Device,
+    pipeline_layout: &wgpu::PipelineLayout,
+    texture_format: wgpu::TextureFormat,
+) -> wgpu::RenderPipeline {
+
+    let (vert_module, frag_module) = (
+        device.create_shader_module(wgpu::include_wgsl!("../shaders/vertex.wgsl")),
+        device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("fragment.wgsl"),
+            source: wgpu::ShaderSource::Wgsl(format!("{PARAMETERS_WGSL}{}", include_str!("../shaders/fragment.wgsl")).into()),
+        }),
+    );
+
+    device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+        label: Some("Render Pipeline"),
+        layout: Some(pipeline_layout),
+        vertex: wgpu::VertexState {
+            module: &vert_module,
+            entry_point: "vert_main",
+            buffers: &[wgpu::VertexBufferLayout {
+                array_stride: std::mem::size_of::<Vertex>() as wgpu::BufferAddress,
+                step_mode: wgpu::VertexStepMode::Vertex,
+                attributes: &[
+                    wgpu::VertexAttribute {
+                        offset: 0,
+                        shader_location: 0,
+                        format: wgpu::VertexFormat::Float32x2,
                     },
-                    mip_level_count: 1,
-                    sample_count: 1,
-                    dimension: wgpu::TextureDimension::D2,
-                    format: wgpu::TextureFormat::Rgba8UnormSrgb,
-                    usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
-                    view_formats: &[wgpu::TextureFormat::Rgba8UnormSrgb],
-                }
-            );
-        
-            queue.write_texture(
-                wgpu::ImageCopyTexture {
-                    texture: &image_texture,
-                    mip_level: 0,
-                    origin: wgpu::Origin3d::ZERO,
-                    aspect: wgpu::TextureAspect::All
-                },
-                &image_data,
-                wgpu::ImageDataLayout {
-                    offset: 0,
-                    bytes_per_row: Some(4 * width),
-                    rows_per_image: Some(height),
-                },
-                wgpu::Extent3d {
-                    width,
-                    height,
-                    depth_or_array_layers: 1
-                },
-            );
+                    wgpu::VertexAttribute {
+                        offset: std::mem::size_of::<[f32; 2]>() as wgpu::BufferAddress,
+                        shader_location: 1,
+                        format: wgpu::VertexFormat::Float32x2,
+                    },
+                ],
+            }],
+        },
+        fragment: Some(wgpu::FragmentState {
+            module: &frag_module,
+            entry_point: "frag_main",
+            targets: &[Some(wgpu::ColorTargetState {
+                format: texture_format,
+                blend: Some(wgpu::BlendState::REPLACE),
+                write_mask: wgpu::ColorWrites::ALL,
+            })],
+        }),
+        primitive: wgpu::PrimitiveState::default(),
+        depth_stencil: None,
+        multisample: wgpu::MultisampleState::default(),
+        multiview: None,
+    })
+}
 
-            let texture_bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
-                label: Some("Texture Bind Group Layout"),
-                entries: &[
-                    wgpu::BindGroupLayoutEntry {
-                        binding: 0,
-                        visibility: wgpu::ShaderStages::FRAGMENT,
-                        ty: wgpu::BindingType::Texture {
-                            multisampled: false,
-                            view_dimension: wgpu::TextureViewDimension::D2,
-                            sample_type: wgpu::TextureSampleType::Float { filterable: true },
-                        },
-                        count: None,
-                    },
-                    wgpu::BindGroupLayoutEntry {
-                        binding: 1,
-                        visibility: wgpu::ShaderStages::FRAGMENT,
-                        ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
-                        count: None,
-                    },
-                ],
-            });
+fn create_composite_pipeline(
+    device: &wgpu::Device,
+    composite_bind_group_layout: &wgpu::BindGroupLayout,
+    texture_format: wgpu::TextureFormat,
+) -> wgpu::RenderPipeline {
+
+    let (vert_module, frag_module) = (
+        device.create_shader_module(wgpu::include_wgsl!("../shaders/vertex.wgsl")),
+        device.create_shader_module(wgpu::include_wgsl!("../shaders/composite.wgsl")),
+    );
+
+    let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+        label: Some("Composite Pipeline Layout"),
+        bind_group_layouts: &[composite_bind_group_layout],
+        push_constant_ranges: &[],
+    });
 
-            let texture_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
-                label: Some("Texture Bind Group"),
-                layout: &texture_bind_group_layout,
-                entries: &[
-                    wgpu::BindGroupEntry {
-                        binding: 0,
-                        resource: wgpu::BindingResource::TextureView(&image_texture.create_view(&wgpu::TextureViewDescriptor::default())),
+    device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+        label: Some("Composite Pipeline"),
+        layout: Some(&pipeline_layout),
+        vertex: wgpu::VertexState {
+            module: &vert_module,
+            entry_point: "vert_main",
+            buffers: &[wgpu::VertexBufferLayout {
+                array_stride: std::mem::size_of::<Vertex>() as wgpu::BufferAddress,
+                step_mode: wgpu::VertexStepMode::Vertex,
+                attributes: &[
+                    wgpu::VertexAttribute {
+                        offset: 0,
+                        shader_location: 0,
+                        format: wgpu::VertexFormat::Float32x2,
                     },
-                    wgpu::BindGroupEntry {
-                        binding: 1,
-                        resource: wgpu::BindingResource::Sampler(&device.create_sampler(&wgpu::SamplerDescriptor {
-                            address_mode_u: wgpu::AddressMode::ClampToEdge,
-                            address_mode_v: wgpu::AddressMode::ClampToEdge,
-                            mag_filter: wgpu::FilterMode::Linear,
-                            min_filter: wgpu::FilterMode::Nearest,
-                            mipmap_filter: wgpu::FilterMode::Nearest,
-                            ..Default::default()
-                        })),
+                    wgpu::VertexAttribute {
+                        offset: std::mem::size_of::<[f32; 2]>() as wgpu::BufferAddress,
+                        shader_location: 1,
+                        format: wgpu::VertexFormat::Float32x2,
                     },
                 ],
-            });
-
-            &self.update_texture_bind_group(texture_bind_group_layout, texture_bind_group);
-
-        }
-
-        // UPDATE VERTEX CANVAS POSITION
-        let vertex_data = update_vertex_data(&zoom_level, &pan_offset, window_aspect_ratio, self.pipeline.image_aspect_ratio);
-        queue.write_buffer(&self.pipeline.vertex_buffer, 0, unsafe {
-            std::slice::from_raw_parts(
-                vertex_data.as_ptr() as *const u8,
-                vertex_data.len() * std::mem::size_of::<Vertex>(),
-            )
-        });
-
-        // UPDATE PARAMETERS
-        let new_parameters_bytes = params;
-        queue.write_buffer(&self.pipeline.parameters_buffer, 0, new_parameters_bytes);
-
-        // render_pass.set_viewport(x as f32, y as f32, width as f32, height as f32, 0.0, 1.0);
-        render_pass.set_pipeline(&self.pipeline.render_pipeline);
-        render_pass.set_bind_group(0, &self.pipeline.texture_bind_group, &[]);
-        render_pass.set_bind_group(1, &self.pipeline.parameters_bind_group, &[]);
-        render_pass.set_vertex_buffer(0, self.pipeline.vertex_buffer.slice(..));
-        render_pass.draw(0..6, 0..1);
-
-        
-    }
+            }],
+        },
+        fragment: Some(wgpu::FragmentState {
+            module: &frag_module,
+            entry_point: "frag_main",
+            targets: &[Some(wgpu::ColorTargetState {
+                format: texture_format,
+                // float32 export targets can not blend, and the composite replaces anyway
+                blend: None,
+                write_mask: wgpu::ColorWrites::ALL,
+            })],
+        }),
+        primitive: wgpu::PrimitiveState::default(),
+        depth_stencil: None,
+        multisample: wgpu::MultisampleState::default(),
+        multiview: None,
+    })
 }
 
 fn build_pipeline(
     device: &wgpu::Device,
     texture_format: wgpu::TextureFormat,
     queue: &wgpu::Queue,
-    shader: Shader,
-) -> (RenderingPipeline, String) {
+    _shader: Shader,
+) -> RenderingPipeline {
 
     
     // let (vert_module, frag_module) = (
@@ -274,11 +1504,6 @@ fn build_pipeline(
     //     device.create_shader_module(wgpu::include_wgsl!("../shaders/flow-based-xdog/fragment.wgsl")),
     // );
 
-    let (vert_module, frag_module) = (
-        device.create_shader_module(wgpu::include_wgsl!("../shaders/vertex.wgsl")),
-        device.create_shader_module(wgpu::include_wgsl!("../shaders/fragment.wgsl")),
-    );
-
     let vertex_data = [
         Vertex { position: [-1.0, -1.0], texcoord: [0.0, 1.0] }, // Bottom-left
         Vertex { position: [-1.0, 1.0], texcoord: [0.0, 0.0] },  // Top-left
@@ -299,52 +1524,15 @@ fn build_pipeline(
         usage: wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST,
     });
 
-    // let img = image::load_from_memory(include_bytes!("../images/cat.png")).unwrap();
-    let img = image::open("C:/Users/astotts/rust/rts/images/cat.png").expect("failed to open image");
-    let img_ = img.to_rgba8();
-    let (mut width, mut height) = img_.dimensions();
-    let image = img.resize(width, height, image::imageops::FilterType::Gaussian).to_rgba8();
-    // window.set_inner_size(LogicalSize::new(width, height));
-    let image_data = image.into_vec();
+    // stands in until the loader delivers the first image
+    let image = image::DynamicImage::ImageRgba8(image::RgbaImage::new(1, 1));
+    let (width, height) = (image.width(), image.height());
 
     let image_aspect_ratio = width as f32 / height as f32;
 
-    let image_texture = device.create_texture(
-        &wgpu::TextureDescriptor {
-            label: Some("Image Texture"),
-            size: wgpu::Extent3d {
-                width,
-                height,
-                depth_or_array_layers: 1
-            },
-            mip_level_count: 1,
-            sample_count: 1,
-            dimension: wgpu::TextureDimension::D2,
-            format: wgpu::TextureFormat::Rgba8UnormSrgb,
-            usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
-            view_formats: &[wgpu::TextureFormat::Rgba8UnormSrgb],
-        }
-    );
-
-    queue.write_texture(
-        wgpu::ImageCopyTexture {
-            texture: &image_texture,
-            mip_level: 0,
-            origin: wgpu::Origin3d::ZERO,
-            aspect: wgpu::TextureAspect::All
-        },
-        &image_data,
-        wgpu::ImageDataLayout {
-            offset: 0,
-            bytes_per_row: Some(4 * width),
-            rows_per_image: Some(height),
-        },
-        wgpu::Extent3d {
-            width,
-            height,
-            depth_or_array_layers: 1
-        },
-    );
+    let image_source = Source::new(device, queue, &image);
+    let image = source::preview(&image);
+    let masks = [Mask::new(device, image_source.grid()), Mask::new(device, image_source.grid())];
 
 
     let texture_bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
@@ -366,35 +1554,68 @@ fn build_pipeline(
                 ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
                 count: None,
             },
-        ],
-    });
-    
-
-    let texture_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
-        label: Some("Texture Bind Group"),
-        layout: &texture_bind_group_layout,
-        entries: &[
-            wgpu::BindGroupEntry {
-                binding: 0,
-                resource: wgpu::BindingResource::TextureView(&image_texture.create_view(&wgpu::TextureViewDescriptor::default())),
+            wgpu::BindGroupLayoutEntry {
+                binding: 2,
+                visibility: wgpu::ShaderStages::FRAGMENT,
+                ty: wgpu::BindingType::Texture {
+                    multisampled: false,
+                    view_dimension: wgpu::TextureViewDimension::D2,
+                    sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                },
+                count: None,
             },
-            wgpu::BindGroupEntry {
-                binding: 1,
-                resource: wgpu::BindingResource::Sampler(&device.create_sampler(&wgpu::SamplerDescriptor {
-                    address_mode_u: wgpu::AddressMode::ClampToEdge,
-                    address_mode_v: wgpu::AddressMode::ClampToEdge,
-                    mag_filter: wgpu::FilterMode::Linear,
-                    min_filter: wgpu::FilterMode::Nearest,
-                    mipmap_filter: wgpu::FilterMode::Nearest,
-                    ..Default::default()
-                })),
+            wgpu::BindGroupLayoutEntry {
+                binding: 3,
+                visibility: wgpu::ShaderStages::FRAGMENT,
+                ty: wgpu::BindingType::Texture {
+                    multisampled: false,
+                    view_dimension: wgpu::TextureViewDimension::D2,
+                    sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                },
+                count: None,
+            },
+            wgpu::BindGroupLayoutEntry {
+                binding: 4,
+                visibility: wgpu::ShaderStages::FRAGMENT,
+                ty: wgpu::BindingType::Texture {
+                    multisampled: false,
+                    view_dimension: wgpu::TextureViewDimension::D2,
+                    sample_type: wgpu::TextureSampleType::Float { filterable: false },
+                },
+                count: None,
+            },
+            wgpu::BindGroupLayoutEntry {
+                binding: 5,
+                visibility: wgpu::ShaderStages::FRAGMENT,
+                ty: wgpu::BindingType::Texture {
+                    multisampled: false,
+                    view_dimension: wgpu::TextureViewDimension::D2,
+                    sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                },
+                count: None,
+            },
+            wgpu::BindGroupLayoutEntry {
+                binding: 6,
+                visibility: wgpu::ShaderStages::FRAGMENT,
+                ty: wgpu::BindingType::Texture {
+                    multisampled: false,
+                    view_dimension: wgpu::TextureViewDimension::D2,
+                    sample_type: wgpu::TextureSampleType::Float { filterable: false },
+                },
+                count: None,
             },
+            // the inputs an effect can declare, see Shader::inputs
+            pass::load_texture_entry(7),
+            pass::load_texture_entry(8),
+            pass::load_texture_entry(9),
+            pass::texture_entry(10),
+            pass::uniform_entry(11),
         ],
     });
-
     
 
 
+
     let params = Parameters { 
         sigma1: 4.75,
         tau: 0.075,
@@ -405,17 +1626,82 @@ fn build_pipeline(
         shader_index: 0,
         colors: 32.0,
         intensity: 0.005,
+        lut_intensity: 1.0,
+        low_threshold: 0.1,
+        high_threshold: 0.3,
+        canny_overlay: 0,
+        sigma_space: 3.0,
+        sigma_range: 0.1,
+        guided_radius: 4,
+        guided_epsilon: 0.01,
+        prefilter: 0,
+        blend_mode: 0,
+        opacity: 1.0,
+        tone_operator: 0,
+        tone_exposure: 0.0,
+        white_point: 4.0,
+        contrast: 1.0,
+        auto_exposure: 0,
+        sort_angle: 0.0,
+        sort_span: 0,
+        sort_low: 0.25,
+        sort_high: 0.8,
+        sort_key: 0,
+        sort_descending: 0,
+        glyph_cell: 8,
+        glyph_count: DEFAULT_RAMP.chars().count() as u32,
+        glyph_color: 0,
+        glyph_edges: 0,
+        glyph_edge_threshold: 0.5,
+        tile_x: 0,
+        tile_y: 0,
+        halftone_mode: 0,
+        dot_shape: 0,
+        halftone_cell: 7.5,
+        screen_cyan: 15.0,
+        screen_magenta: 75.0,
+        screen_yellow: 0.0,
+        screen_black: 45.0,
+        dot_gain: 0.0,
+        image_width: 0,
+        image_height: 0,
+        crt_mask: 1,
+        crt_palette: 0,
+        crt_interlace: 0,
+        crt_curvature: 0.1,
+        scanline_size: 4.0,
+        scanline_strength: 0.7,
+        mask_strength: 0.3,
+        crt_bloom: 0.2,
+        crt_vignette: 0.3,
+        lens_center_x: 0.5,
+        lens_center_y: 0.5,
+        aberration_falloff: 1.0,
+        distortion_k1: 0.0,
+        distortion_k2: 0.0,
+        spectral_samples: 7,
+        frame: 0,
+        grain_seed: 0,
+        grain_intensity: 0.1,
+        grain_size: 1.5,
+        grain_chroma: 0.2,
+        grain_response: 1.0,
+        noise_type: 0,
+        noise_seed: 0,
+        noise_scale: 1.0,
+        noise_intensity: 0.1,
+        stroke_width: 16,
+        stroke_layers: 3,
+        stroke_length: 3.0,
+        stroke_detail: 0.05,
+        stroke_texture: 0,
+        superpixel_count: 400,
+        superpixel_compactness: 10.0,
+        superpixel_iterations: 10,
+        superpixel_boundaries: 0,
     };
 
 
-    let parameters_buffer = device.create_buffer_init(
-        &wgpu::util::BufferInitDescriptor {
-            label: Some("Parameter buffer"),
-            contents: bytemuck::cast_slice(&[params]),
-            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST
-        }
-    );
-
     let params_bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
         label: Some("paramerters bind group layout"),
         entries: &[
@@ -432,75 +1718,157 @@ fn build_pipeline(
         ]
     });
 
-    let parameters_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
-        label: Some("parameters bind group"),
-        layout: &params_bind_group_layout,
+    // each stage owns a parameters buffer since the stages run different effects
+    let base = Stage::new(device, "base", &texture_bind_group_layout, &params_bind_group_layout, &stage_sources(&image_source, &None, Input::original, None, 0), &masks[0], &params, false);
+    let effect = Stage::new(device, "effect", &texture_bind_group_layout, &params_bind_group_layout, &stage_sources(&image_source, &None, Input::original, Some(base.output()), 0), &masks[1], &params, false);
+
+
+
+    // compute effects bind it too, see ComputeEffect
+    let asset_bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+        label: Some("Asset Bind Group Layout"),
         entries: &[
-            wgpu::BindGroupEntry {
+            wgpu::BindGroupLayoutEntry {
                 binding: 0,
-                resource: parameters_buffer.as_entire_binding(),
-            }
-        ]
+                visibility: wgpu::ShaderStages::FRAGMENT | wgpu::ShaderStages::COMPUTE,
+                ty: wgpu::BindingType::Texture {
+                    multisampled: false,
+                    view_dimension: wgpu::TextureViewDimension::D3,
+                    sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                },
+                count: None,
+            },
+            wgpu::BindGroupLayoutEntry {
+                binding: 1,
+                visibility: wgpu::ShaderStages::FRAGMENT | wgpu::ShaderStages::COMPUTE,
+                ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                count: None,
+            },
+            wgpu::BindGroupLayoutEntry {
+                binding: 2,
+                visibility: wgpu::ShaderStages::FRAGMENT | wgpu::ShaderStages::COMPUTE,
+                ty: wgpu::BindingType::Texture {
+                    multisampled: false,
+                    view_dimension: wgpu::TextureViewDimension::D2,
+                    sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                },
+                count: None,
+            },
+            wgpu::BindGroupLayoutEntry {
+                binding: 3,
+                visibility: wgpu::ShaderStages::FRAGMENT | wgpu::ShaderStages::COMPUTE,
+                ty: wgpu::BindingType::Texture {
+                    multisampled: false,
+                    view_dimension: wgpu::TextureViewDimension::D2,
+                    sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                },
+                count: None,
+            },
+            wgpu::BindGroupLayoutEntry {
+                binding: 4,
+                visibility: wgpu::ShaderStages::FRAGMENT | wgpu::ShaderStages::COMPUTE,
+                ty: wgpu::BindingType::Texture {
+                    multisampled: false,
+                    view_dimension: wgpu::TextureViewDimension::D2,
+                    sample_type: wgpu::TextureSampleType::Float { filterable: false },
+                },
+                count: None,
+            },
+        ],
     });
 
+    // until a lut is loaded the lut effect passes colors through unchanged
+    let lut_texture = Lut::identity(2).create_texture(device, queue);
+    // replaced by the scene as soon as it sees the controls, see prepare
+    let glyph_texture = GlyphAtlas::new("", DEFAULT_RAMP, 8).unwrap().create_texture(device, queue);
+    let brush_texture = BrushTexture::new("").unwrap().create_texture(device, queue);
+    let blue_noise_texture = BlueNoise::new().create_texture(device, queue);
+    let asset_bind_group = create_asset_bind_group(device, &asset_bind_group_layout, &lut_texture, &glyph_texture, &brush_texture, &blue_noise_texture);
 
 
     let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
         label: Some("Render Pipeline Layout"),
         // bind_group_layouts: &[&texture_bind_group_layout, &palette_bind_group_layout],
-        bind_group_layouts: &[&texture_bind_group_layout, &params_bind_group_layout,],
+        bind_group_layouts: &[&texture_bind_group_layout, &params_bind_group_layout, &asset_bind_group_layout],
         push_constant_ranges: &[],
     });
 
 
-    let render_pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
-        label: Some("Render Pipeline"),
-        layout: Some(&pipeline_layout),
-        vertex: wgpu::VertexState {
-            module: &vert_module,
-            entry_point: "vert_main",
-            buffers: &[wgpu::VertexBufferLayout {
-                array_stride: std::mem::size_of::<Vertex>() as wgpu::BufferAddress,
-                step_mode: wgpu::VertexStepMode::Vertex,
-                attributes: &[
-                    wgpu::VertexAttribute {
-                        offset: 0,
-                        shader_location: 0,
-                        format: wgpu::VertexFormat::Float32x2,
-                    },
-                    wgpu::VertexAttribute {
-                        offset: std::mem::size_of::<[f32; 2]>() as wgpu::BufferAddress,
-                        shader_location: 1,
-                        format: wgpu::VertexFormat::Float32x2,
-                    },
-                ],
-            }],
+    // stages render offscreen, only the composite writes to the surface
+    let render_pipeline = create_render_pipeline(device, &pipeline_layout, pass::TARGET_FORMAT);
+
+    let quad_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+        label: Some("Quad Buffer"),
+        contents: unsafe {
+            std::slice::from_raw_parts(
+                vertex_data.as_ptr() as *const u8,
+                vertex_data.len() * std::mem::size_of::<Vertex>(),
+            )
         },
-        fragment: Some(wgpu::FragmentState {
-            module: &frag_module,
-            entry_point: "frag_main",
-            targets: &[Some(wgpu::ColorTargetState {
-                format: texture_format,
-                blend: Some(wgpu::BlendState::REPLACE),
-                write_mask: wgpu::ColorWrites::ALL,
-            })],
-        }),
-        primitive: wgpu::PrimitiveState::default(),
-        depth_stencil: None,
-        multisample: wgpu::MultisampleState::default(),
-        multiview: None,
+        usage: wgpu::BufferUsages::VERTEX,
+    });
+
+
+    let composite_bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+        label: Some("Composite Bind Group Layout"),
+        entries: &[
+            pass::texture_entry(0),
+            pass::texture_entry(1),
+            pass::sampler_entry(2),
+            pass::uniform_entry(3),
+        ],
     });
 
+    let composite_buffer = device.create_buffer_init(
+        &wgpu::util::BufferInitDescriptor {
+            label: Some("Composite Buffer"),
+            contents: bytemuck::cast_slice(&[CompositeParameters {
+                blend_mode: params.blend_mode,
+                opacity: params.opacity,
+                working_space: ColorSpace::linear.get_index(),
+                output_linear: texture_format.is_srgb() as u32,
+                exposure: 0.0,
+                compress_highlights: 0,
+                magnification: Magnification::linear.get_index(),
+                _padding: 0,
+            }]),
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST
+        }
+    );
+
+    let composite_bind_group = create_composite_bind_group(device, &composite_bind_group_layout, base.display_view(), effect.display_view(), &composite_buffer);
+    let composite_pipeline = create_composite_pipeline(device, &composite_bind_group_layout, texture_format);
 
-    return (RenderingPipeline {
+
+    RenderingPipeline {
         render_pipeline,
+        composite_pipeline,
         texture_bind_group_layout,
-        texture_bind_group,
-        parameters_bind_group,
-        parameters_buffer,
+        parameters_bind_group_layout: params_bind_group_layout,
+        asset_bind_group_layout,
+        asset_bind_group,
+        lut_texture,
+        glyph_texture,
+        brush_texture,
+        blue_noise_texture,
+        composite_bind_group_layout,
+        composite_bind_group,
+        composite_buffer,
+        quad_buffer,
         vertex_buffer,
         image_aspect_ratio,
-    }, String::from("C:/Users/astotts/rust/renderer/images/cat.png"));
+        image,
+        image_source,
+        second_source: None,
+        masks,
+        base_input: Input::original,
+        effect_input: Input::original,
+        base,
+        effect,
+        stitch: None,
+        rendered: [None, None],
+        frame: 0,
+    }
 
 
     // device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
@@ -536,4 +1904,50 @@ fn build_pipeline(
     //     },
     //     multiview: None,
     // })
-}
\ No newline at end of file
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn key(shader: Shader, prefilter: Prefilter, params: &Parameters) -> StageKey {
+        StageKey::new(shader, prefilter, params, None, ColorSpace::linear, ColorSpace::linear)
+    }
+
+    #[test]
+    fn effect_parameters_leave_the_base_stage_clean() {
+        let params: Parameters = bytemuck::Zeroable::zeroed();
+        let sorted = Parameters { sort_angle: 45.0, sort_descending: 1, ..params };
+        assert_eq!(key(Shader::gaussian_blur, Prefilter::none, &params), key(Shader::gaussian_blur, Prefilter::none, &sorted));
+        assert_ne!(key(Shader::pixel_sort, Prefilter::none, &params), key(Shader::pixel_sort, Prefilter::none, &sorted));
+
+        let blurred = Parameters { sigma1: 4.0, ..params };
+        assert_eq!(key(Shader::pixel_sort, Prefilter::none, &params), key(Shader::pixel_sort, Prefilter::none, &blurred));
+        assert_ne!(key(Shader::gaussian_blur, Prefilter::none, &params), key(Shader::gaussian_blur, Prefilter::none, &blurred));
+
+        // blending is the composite's
+        let blended = Parameters { blend_mode: 2, opacity: 0.5, ..params };
+        assert_eq!(key(Shader::gaussian_blur, Prefilter::none, &params), key(Shader::gaussian_blur, Prefilter::none, &blended));
+    }
+
+    #[test]
+    fn prefilters_only_reach_the_effects_that_run_them() {
+        let params: Parameters = bytemuck::Zeroable::zeroed();
+        let smoothed = Parameters { sigma_space: 6.0, ..params };
+        assert_eq!(key(Shader::invert, Prefilter::none, &params), key(Shader::invert, Prefilter::bilateral, &smoothed));
+        assert_ne!(key(Shader::quantization, Prefilter::none, &params), key(Shader::quantization, Prefilter::bilateral, &params));
+        assert_ne!(key(Shader::quantization, Prefilter::bilateral, &params), key(Shader::quantization, Prefilter::bilateral, &smoothed));
+        // the standalone filter runs without the prefilter selected
+        assert_ne!(key(Shader::bilateral_filter, Prefilter::none, &params), key(Shader::bilateral_filter, Prefilter::none, &smoothed));
+        assert_eq!(key(Shader::bilateral_filter, Prefilter::none, &params), key(Shader::bilateral_filter, Prefilter::guided, &params));
+    }
+
+    #[test]
+    fn only_animated_effects_follow_the_frame() {
+        let params: Parameters = bytemuck::Zeroable::zeroed();
+        let next = Parameters { frame: 1, ..params };
+        assert_eq!(key(Shader::crt, Prefilter::none, &params), key(Shader::crt, Prefilter::none, &next));
+        assert_ne!(key(Shader::film_grain, Prefilter::none, &params), key(Shader::film_grain, Prefilter::none, &next));
+    }
+}
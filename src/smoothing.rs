@@ -0,0 +1,206 @@
+use iced_wgpu::wgpu;
+use crate::blur::SeparableBlur;
+use crate::pass;
+
+
+const MAX_BILATERAL_RADIUS: i32 = 32;
+// the widest boxes the guided filter averages over
+pub const MAX_GUIDED_RADIUS: u32 = 62;
+
+// texels either filter reaches out from the center. the guided filter averages twice,
+// its coefficients over boxes of the source and its output over boxes of the coefficients
+pub const REACH: u32 = if 2 * MAX_GUIDED_RADIUS > MAX_BILATERAL_RADIUS as u32 { 2 * MAX_GUIDED_RADIUS } else { MAX_BILATERAL_RADIUS as u32 };
+
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+struct BilateralParameters {
+    direction: [i32; 2],
+    radius: i32,
+    sigma_space: f32,
+    sigma_range: f32,
+    _padding: f32,
+}
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+struct GuidedParameters {
+    epsilon: f32,
+    _padding: [f32; 3],
+}
+
+
+// edge preserving smoothing of the source image. either filter writes into the same
+// output texture, so effects can read the result without knowing which one ran
+pub struct Smoothing {
+    bilateral_pipeline: wgpu::RenderPipeline,
+    bilateral_buffers: [wgpu::Buffer; 2],
+    bilateral_bind_groups: [wgpu::BindGroup; 2],
+    bilateral_view: wgpu::TextureView,
+    square_pipeline: wgpu::RenderPipeline,
+    coefficients_pipeline: wgpu::RenderPipeline,
+    combine_pipeline: wgpu::RenderPipeline,
+    square_bind_group: wgpu::BindGroup,
+    coefficients_bind_group: wgpu::BindGroup,
+    combine_bind_group: wgpu::BindGroup,
+    guided_buffer: wgpu::Buffer,
+    mean: SeparableBlur,
+    // blurs the squared image first, then the a coefficients, which share a texture
+    mean_a: SeparableBlur,
+    mean_b: SeparableBlur,
+    a_view: wgpu::TextureView,
+    b_view: wgpu::TextureView,
+    output_view: wgpu::TextureView,
+}
+
+impl Smoothing {
+    pub fn new(device: &wgpu::Device, input: &wgpu::TextureView, width: u32, height: u32) -> Smoothing {
+        let create_view = |label| pass::create_target(device, label, width, height)
+            .create_view(&wgpu::TextureViewDescriptor::default());
+        let create_buffer = |label, size| device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some(label),
+            size: size as wgpu::BufferAddress,
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+
+        let output_view = create_view("Smoothing Output Texture");
+
+        // bilateral: horizontal pass into the intermediate texture, vertical pass into the output
+        let bilateral_module = device.create_shader_module(wgpu::include_wgsl!("../shaders/bilateral.wgsl"));
+        let bilateral_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("Bilateral Bind Group Layout"),
+            entries: &[
+                pass::texture_entry(0),
+                pass::uniform_entry(1),
+            ],
+        });
+        let bilateral_pipeline = pass::create_pipeline(device, "Bilateral Pipeline", &bilateral_module, "frag_main", &bilateral_layout, 1);
+        let bilateral_view = create_view("Bilateral Intermediate Texture");
+        let bilateral_buffers = [
+            create_buffer("Horizontal Bilateral Buffer", std::mem::size_of::<BilateralParameters>()),
+            create_buffer("Vertical Bilateral Buffer", std::mem::size_of::<BilateralParameters>()),
+        ];
+        let bilateral_bind_groups = [
+            (input, &bilateral_buffers[0]),
+            (&bilateral_view, &bilateral_buffers[1]),
+        ].map(|(view, buffer)| device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Bilateral Bind Group"),
+            layout: &bilateral_layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::TextureView(view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: buffer.as_entire_binding(),
+                },
+            ],
+        }));
+
+        // guided: box means of the image and its square -> per pixel linear coefficients a, b
+        // -> box means of a and b -> mean(a) * image + mean(b)
+        let guided_module = device.create_shader_module(wgpu::include_wgsl!("../shaders/guided.wgsl"));
+        let create_layout = |entries: &[wgpu::BindGroupLayoutEntry]| device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("Guided Bind Group Layout"),
+            entries,
+        });
+        let square_layout = create_layout(&[pass::texture_entry(0)]);
+        let coefficients_layout = create_layout(&[pass::texture_entry(0), pass::texture_entry(1), pass::uniform_entry(3)]);
+        let combine_layout = create_layout(&[pass::texture_entry(0), pass::texture_entry(1), pass::texture_entry(2)]);
+
+        let square_pipeline = pass::create_pipeline(device, "Guided Square Pipeline", &guided_module, "square_main", &square_layout, 1);
+        let coefficients_pipeline = pass::create_pipeline(device, "Guided Coefficients Pipeline", &guided_module, "coefficients_main", &coefficients_layout, 2);
+        let combine_pipeline = pass::create_pipeline(device, "Guided Combine Pipeline", &guided_module, "combine_main", &combine_layout, 1);
+
+        let guided_buffer = create_buffer("Guided Buffer", std::mem::size_of::<GuidedParameters>());
+        let a_view = create_view("Guided A Texture");
+        let b_view = create_view("Guided B Texture");
+
+        let mean = SeparableBlur::new(device, input, width, height);
+        let mean_a = SeparableBlur::new(device, &a_view, width, height);
+        let mean_b = SeparableBlur::new(device, &b_view, width, height);
+
+        let create_bind_group = |layout: &wgpu::BindGroupLayout, views: &[&wgpu::TextureView], with_parameters: bool| {
+            let mut entries: Vec<_> = views.iter().enumerate().map(|(i, view)| wgpu::BindGroupEntry {
+                binding: i as u32,
+                resource: wgpu::BindingResource::TextureView(view),
+            }).collect();
+            if with_parameters {
+                entries.push(wgpu::BindGroupEntry {
+                    binding: 3,
+                    resource: guided_buffer.as_entire_binding(),
+                });
+            }
+            device.create_bind_group(&wgpu::BindGroupDescriptor {
+                label: Some("Guided Bind Group"),
+                layout,
+                entries: &entries,
+            })
+        };
+        let square_bind_group = create_bind_group(&square_layout, &[input], false);
+        let coefficients_bind_group = create_bind_group(&coefficients_layout, &[mean.output_view(), mean_a.output_view()], true);
+        let combine_bind_group = create_bind_group(&combine_layout, &[input, mean_a.output_view(), mean_b.output_view()], false);
+
+        Smoothing {
+            bilateral_pipeline,
+            bilateral_buffers,
+            bilateral_bind_groups,
+            bilateral_view,
+            square_pipeline,
+            coefficients_pipeline,
+            combine_pipeline,
+            square_bind_group,
+            coefficients_bind_group,
+            combine_bind_group,
+            guided_buffer,
+            mean,
+            mean_a,
+            mean_b,
+            a_view,
+            b_view,
+            output_view,
+        }
+    }
+
+    pub fn output_view(&self) -> &wgpu::TextureView {
+        &self.output_view
+    }
+
+    pub fn encode_bilateral(&self, encoder: &mut wgpu::CommandEncoder, queue: &wgpu::Queue, sigma_space: f32, sigma_range: f32) {
+        let sigma_space = sigma_space.max(0.01);
+        let mut params = BilateralParameters {
+            direction: [1, 0],
+            radius: ((sigma_space * 2.0).ceil() as i32).min(MAX_BILATERAL_RADIUS),
+            sigma_space,
+            sigma_range: sigma_range.max(0.001),
+            _padding: 0.0,
+        };
+        queue.write_buffer(&self.bilateral_buffers[0], 0, bytemuck::cast_slice(&[params]));
+
+        params.direction = [0, 1];
+        queue.write_buffer(&self.bilateral_buffers[1], 0, bytemuck::cast_slice(&[params]));
+
+        pass::run(encoder, "Bilateral Pass", &self.bilateral_pipeline, &self.bilateral_bind_groups[0], &[&self.bilateral_view]);
+        pass::run(encoder, "Bilateral Pass", &self.bilateral_pipeline, &self.bilateral_bind_groups[1], &[&self.output_view]);
+    }
+
+    pub fn encode_guided(&self, encoder: &mut wgpu::CommandEncoder, queue: &wgpu::Queue, radius: u32, epsilon: f32) {
+        let radius = radius.min(MAX_GUIDED_RADIUS);
+        queue.write_buffer(&self.guided_buffer, 0, bytemuck::cast_slice(&[GuidedParameters {
+            epsilon: epsilon.max(1e-6),
+            _padding: [0.0; 3],
+        }]));
+
+        self.mean.encode_box(encoder, queue, radius);
+        pass::run(encoder, "Guided Square Pass", &self.square_pipeline, &self.square_bind_group, &[&self.a_view]);
+        self.mean_a.encode_box(encoder, queue, radius);
+
+        pass::run(encoder, "Guided Coefficients Pass", &self.coefficients_pipeline, &self.coefficients_bind_group, &[&self.a_view, &self.b_view]);
+        self.mean_a.encode_box(encoder, queue, radius);
+        self.mean_b.encode_box(encoder, queue, radius);
+
+        pass::run(encoder, "Guided Combine Pass", &self.combine_pipeline, &self.combine_bind_group, &[&self.output_view]);
+    }
+}
@@ -0,0 +1,155 @@
+use iced_wgpu::wgpu;
+use image::{DynamicImage, RgbaImage};
+use crate::controls::ColorSpace;
+use crate::mipmap::Mipmaps;
+use crate::pass;
+use crate::tiles::{Region, TileGrid};
+
+
+// an input image on the gpu at the precision it was stored with.
+// 8 bit images stay 8 bit, 16 bit and float images are uploaded as 32 bit float.
+// images over the device's texture limit are held as one texture per tile.
+// each texture is mipmapped like the stage outputs, see Mipmaps
+pub struct Source {
+    tiles: Vec<wgpu::Texture>,
+    grid: TileGrid,
+    // float formats like exr and hdr hold linear light, everything else is display encoded
+    color_space: ColorSpace,
+}
+
+impl Source {
+    pub fn new(device: &wgpu::Device, queue: &wgpu::Queue, image: &DynamicImage) -> Source {
+        let (width, height) = (image.width(), image.height());
+        let grid = TileGrid::new(width, height, device.limits().max_texture_dimension_2d);
+
+        let (format, bytes_per_texel, data) = if is_8bit(image) {
+            (wgpu::TextureFormat::Rgba8Unorm, 4, image.to_rgba8().into_raw())
+        } else {
+            (wgpu::TextureFormat::Rgba32Float, 16, bytemuck::cast_slice(image.to_rgba32f().as_raw()).to_vec())
+        };
+
+        let tiles = grid.tiles.iter().map(|tile| {
+            let region = tile.region;
+            let texture = device.create_texture(
+                &wgpu::TextureDescriptor {
+                    label: Some("Image Texture"),
+                    size: wgpu::Extent3d {
+                        width: region.width,
+                        height: region.height,
+                        depth_or_array_layers: 1
+                    },
+                    mip_level_count: pass::mip_level_count(region.width, region.height),
+                    sample_count: 1,
+                    dimension: wgpu::TextureDimension::D2,
+                    // each stage converts from color_space into the space its effect expects
+                    format,
+                    usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST | wgpu::TextureUsages::RENDER_ATTACHMENT,
+                    view_formats: &[format],
+                }
+            );
+
+            // the tile's rows are read straight out of the whole image
+            queue.write_texture(
+                wgpu::ImageCopyTexture {
+                    texture: &texture,
+                    mip_level: 0,
+                    origin: wgpu::Origin3d::ZERO,
+                    aspect: wgpu::TextureAspect::All
+                },
+                &data,
+                wgpu::ImageDataLayout {
+                    offset: region_offset(&region, width, bytes_per_texel),
+                    bytes_per_row: Some(bytes_per_texel * width),
+                    rows_per_image: Some(region.height),
+                },
+                wgpu::Extent3d {
+                    width: region.width,
+                    height: region.height,
+                    depth_or_array_layers: 1
+                },
+            );
+            texture
+        }).collect::<Vec<_>>();
+
+        // the writes above land ahead of the passes filling the smaller levels
+        let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor { label: Some("Image Mipmap Encoder") });
+        for texture in &tiles {
+            Mipmaps::new(device, texture).encode(&mut encoder);
+        }
+        queue.submit(Some(encoder.finish()));
+
+        Source {
+            tiles,
+            grid,
+            color_space: if is_linear(image) { ColorSpace::linear } else { ColorSpace::display },
+        }
+    }
+
+    pub fn texture(&self, tile: usize) -> &wgpu::Texture {
+        &self.tiles[tile]
+    }
+
+    pub fn grid(&self) -> &TileGrid {
+        &self.grid
+    }
+
+    pub fn color_space(&self) -> ColorSpace {
+        self.color_space
+    }
+}
+
+// bytes before the region's first texel in the rows of an image width texels wide.
+// float images large enough to need tiles run past what u32 holds
+fn region_offset(region: &Region, width: u32, bytes_per_texel: u32) -> u64 {
+    (region.y as u64 * width as u64 + region.x as u64) * bytes_per_texel as u64
+}
+
+fn is_8bit(image: &DynamicImage) -> bool {
+    matches!(image, DynamicImage::ImageLuma8(_) | DynamicImage::ImageLumaA8(_) | DynamicImage::ImageRgb8(_) | DynamicImage::ImageRgba8(_))
+}
+
+fn is_linear(image: &DynamicImage) -> bool {
+    matches!(image, DynamicImage::ImageRgb32F(_) | DynamicImage::ImageRgba32F(_))
+}
+
+// resamples without dropping to 8 bits
+pub fn resize(image: &DynamicImage, width: u32, height: u32) -> DynamicImage {
+    let filter = image::imageops::FilterType::Triangle;
+    if is_8bit(image) {
+        DynamicImage::ImageRgba8(image::imageops::resize(&image.to_rgba8(), width, height, filter))
+    } else {
+        DynamicImage::ImageRgba32F(image::imageops::resize(&image.to_rgba32f(), width, height, filter))
+    }
+}
+
+// 8 bit display encoded copy for the cpu side, like generating masks
+pub fn preview(image: &DynamicImage) -> RgbaImage {
+    if !is_linear(image) {
+        return image.to_rgba8();
+    }
+    let linear = image.to_rgba32f();
+    RgbaImage::from_fn(linear.width(), linear.height(), |x, y| {
+        let [r, g, b, a] = linear.get_pixel(x, y).0;
+        image::Rgba([linear_to_srgb(r), linear_to_srgb(g), linear_to_srgb(b), a].map(|c| (c.clamp(0.0, 1.0) * 255.0).round() as u8))
+    })
+}
+
+fn linear_to_srgb(c: f32) -> f32 {
+    if c <= 0.0031308 { c * 12.92 } else { 1.055 * c.powf(1.0 / 2.4) - 0.055 }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn offsets_regions_past_4_gib() {
+        // the last tile of a 32768 x 32768 float image starts 12 gib in
+        let grid = TileGrid::new(32768, 32768, 8192);
+        let region = grid.tiles.last().unwrap().region;
+        assert_eq!((region.x, region.y), (24576, 24576));
+        assert_eq!(region_offset(&region, 32768, 16), 12_885_295_104);
+        assert_eq!(region_offset(&region, 32768, 4), 12_885_295_104 / 4);
+    }
+}
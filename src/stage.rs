@@ -0,0 +1,521 @@
+use iced_wgpu::wgpu::{self, util::DeviceExt};
+use crate::controls::{ColorSpace, Prefilter, Shader};
+use crate::scene::Parameters;
+use crate::blur::SeparableBlur;
+use crate::canny::Canny;
+use crate::compute::{ComputeEffect, ComputeEffectRunner};
+use crate::smoothing::Smoothing;
+use crate::exposure::AutoExposure;
+use crate::mask::Mask;
+use crate::mipmap::Mipmaps;
+use crate::pass;
+use crate::tiles::Region;
+use crate::timing::GpuTimer;
+
+
+// one effect rendered offscreen at image resolution from a source texture,
+// together with the offscreen passes it reads from. the effect is a fragment pass
+// or, for effects declared with Shader::compute, a series of compute dispatches.
+// the input pass converts the source into the color space the effect expects,
+// the output pass converts the effect into the working space and, when masked,
+// mixes it with the source.
+// for a tiled image the stage is created at tile size and renders one tile at a time
+pub struct Stage {
+    // base or effect, what its passes are timed as
+    name: &'static str,
+    input_view: wgpu::TextureView,
+    blur1: SeparableBlur,
+    blur2: SeparableBlur,
+    canny: Canny,
+    smoothing: Smoothing,
+    exposure: AutoExposure,
+    // tiled stages share an exposure measured over every tile ahead of rendering them,
+    // see measure_exposure
+    tiled: bool,
+    // whether the blurs read the smoothed source instead of the source itself
+    blurs_smoothed: bool,
+    texture_bind_group: wgpu::BindGroup,
+    // the spaces the inputs an effect can declare hold, original, previous stage and
+    // second image. None for a stage output, which is in the working space
+    input_spaces: [Option<ColorSpace>; 3],
+    input_spaces_buffer: wgpu::Buffer,
+    parameters_buffer: wgpu::Buffer,
+    parameters_bind_group: wgpu::BindGroup,
+    effect_view: wgpu::TextureView,
+    // the pipelines of the last compute effect rendered, see Shader::compute
+    compute: Option<ComputeEffectRunner>,
+    color_buffer: wgpu::Buffer,
+    input_pipeline: wgpu::RenderPipeline,
+    output_pipeline: wgpu::RenderPipeline,
+    color_layout: wgpu::BindGroupLayout,
+    color_bind_group: wgpu::BindGroup,
+    output: wgpu::Texture,
+    // level 0, what the passes after this stage read and write
+    output_view: wgpu::TextureView,
+    // every level, what the viewer samples
+    display_view: wgpu::TextureView,
+    mipmaps: Mipmaps,
+}
+
+impl Stage {
+    #[allow(clippy::too_many_arguments)]
+    pub fn new(
+        device: &wgpu::Device,
+        name: &'static str,
+        texture_bind_group_layout: &wgpu::BindGroupLayout,
+        parameters_bind_group_layout: &wgpu::BindGroupLayout,
+        sources: &Sources,
+        mask: &Mask,
+        params: &Parameters,
+        tiled: bool,
+    ) -> Stage {
+        let source = sources.source;
+        let (width, height) = (source.width(), source.height());
+        let input_view = pass::create_target(device, "Stage Input Texture", width, height)
+            .create_view(&wgpu::TextureViewDescriptor::default());
+
+        let blur1 = SeparableBlur::new(device, &input_view, width, height);
+        let blur2 = SeparableBlur::new(device, &input_view, width, height);
+        let canny = Canny::new(device, blur1.output_view(), width, height);
+        let smoothing = Smoothing::new(device, &input_view, width, height);
+        let exposure = AutoExposure::new(device, &input_view, width, height);
+
+        let input_spaces_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Stage Input Spaces Buffer"),
+            size: std::mem::size_of::<InputSpaces>() as u64,
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        let texture_bind_group = create_texture_bind_group(device, texture_bind_group_layout, &input_view, &blur1, &blur2, &canny, &smoothing, &exposure, sources, mask, &input_spaces_buffer);
+
+        let parameters_buffer = device.create_buffer_init(
+            &wgpu::util::BufferInitDescriptor {
+                label: Some("Parameter buffer"),
+                contents: bytemuck::cast_slice(&[*params]),
+                usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST
+            }
+        );
+
+        let parameters_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("parameters bind group"),
+            layout: parameters_bind_group_layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: parameters_buffer.as_entire_binding(),
+                }
+            ]
+        });
+
+        let output = pass::create_mipmapped_target(device, "Stage Output Texture", width, height);
+        let output_view = pass::level_view(&output, 0);
+        let display_view = output.create_view(&wgpu::TextureViewDescriptor::default());
+        let mipmaps = Mipmaps::new(device, &output);
+
+        let effect_view = pass::create_storage_target(device, "Stage Effect Texture", width, height)
+            .create_view(&wgpu::TextureViewDescriptor::default());
+
+        let color_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Stage Color Buffer"),
+            size: std::mem::size_of::<ColorParameters>() as u64,
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+
+        let color_module = device.create_shader_module(wgpu::include_wgsl!("../shaders/stage.wgsl"));
+        let color_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("Stage Color Bind Group Layout"),
+            entries: &[
+                pass::load_texture_entry(0),
+                pass::texture_entry(1),
+                pass::texture_entry(2),
+                pass::uniform_entry(3),
+            ],
+        });
+        let input_pipeline = pass::create_pipeline(device, "Stage Input Pipeline", &color_module, "input_main", &color_layout, 1);
+        let output_pipeline = pass::create_pipeline(device, "Stage Output Pipeline", &color_module, "output_main", &color_layout, 1);
+        let color_bind_group = create_color_bind_group(device, &color_layout, source, &effect_view, mask, &color_buffer);
+
+        Stage {
+            name,
+            input_view,
+            blur1,
+            blur2,
+            canny,
+            smoothing,
+            exposure,
+            tiled,
+            blurs_smoothed: false,
+            texture_bind_group,
+            input_spaces: sources.spaces(),
+            input_spaces_buffer,
+            parameters_buffer,
+            parameters_bind_group,
+            effect_view,
+            compute: None,
+            color_buffer,
+            input_pipeline,
+            output_pipeline,
+            color_layout,
+            color_bind_group,
+            output,
+            output_view,
+            display_view,
+            mipmaps,
+        }
+    }
+
+    pub fn output(&self) -> &wgpu::Texture {
+        &self.output
+    }
+
+    pub fn output_view(&self) -> &wgpu::TextureView {
+        &self.output_view
+    }
+
+    pub fn display_view(&self) -> &wgpu::TextureView {
+        &self.display_view
+    }
+
+    // the output's smaller levels for the viewer, after render
+    pub fn encode_mipmaps(&self, encoder: &mut wgpu::CommandEncoder) {
+        self.mipmaps.encode(encoder);
+    }
+
+    // the storage buffer effect left after the last render, None unless it was the last compute effect
+    pub fn read_compute_buffer(&self, device: &wgpu::Device, queue: &wgpu::Queue, effect: &'static ComputeEffect) -> Option<Vec<u32>> {
+        self.compute.as_ref().filter(|compute| compute.is_for(effect)).map(|compute| compute.read_buffer(device, queue))
+    }
+
+    // points the stage at the next tile, textures of the same size
+    pub fn set_source(&mut self, device: &wgpu::Device, texture_bind_group_layout: &wgpu::BindGroupLayout, sources: &Sources, mask: &Mask) {
+        self.color_bind_group = create_color_bind_group(device, &self.color_layout, sources.source, &self.effect_view, mask, &self.color_buffer);
+        self.texture_bind_group = create_texture_bind_group(
+            device,
+            texture_bind_group_layout,
+            &self.input_view,
+            &self.blur1,
+            &self.blur2,
+            &self.canny,
+            &self.smoothing,
+            &self.exposure,
+            sources,
+            mask,
+            &self.input_spaces_buffer,
+        );
+        self.input_spaces = sources.spaces();
+    }
+
+    // counts the core of the current tile into the auto exposure histogram,
+    // which is averaged once every tile is counted
+    #[allow(clippy::too_many_arguments)]
+    pub fn measure_exposure(
+        &self,
+        encoder: &mut wgpu::CommandEncoder,
+        queue: &wgpu::Queue,
+        shader: Shader,
+        source_space: ColorSpace,
+        working_space: ColorSpace,
+        core: Region,
+        timer: &mut GpuTimer,
+    ) {
+        self.write_color(queue, shader, false, source_space, working_space);
+        timer.mark(encoder, format!("{} input", self.name));
+        pass::run(encoder, "Stage Input Pass", &self.input_pipeline, &self.color_bind_group, &[&self.input_view]);
+        timer.mark(encoder, format!("{} exposure", self.name));
+        self.exposure.encode_histogram(encoder, queue, core);
+    }
+
+    pub fn average_exposure(&self, encoder: &mut wgpu::CommandEncoder, timer: &mut GpuTimer) {
+        timer.mark(encoder, format!("{} exposure", self.name));
+        self.exposure.encode_average(encoder);
+    }
+
+    fn write_color(&self, queue: &wgpu::Queue, shader: Shader, masked: bool, source_space: ColorSpace, working_space: ColorSpace) {
+        let color = ColorParameters {
+            source_space: source_space.get_index(),
+            effect_space: shader.color_space().unwrap_or(working_space).get_index(),
+            working_space: working_space.get_index(),
+            masked: masked as u32,
+        };
+        queue.write_buffer(&self.color_buffer, 0, bytemuck::cast_slice(&[color]));
+    }
+
+    // encodes the offscreen passes the effect reads from, then the effect itself.
+    // mask is the mask this stage was created with and its feather sigma, None leaves the effect ungated.
+    // source_space is the space the source texture holds, the output is always in working_space
+    #[allow(clippy::too_many_arguments)]
+    pub fn render(
+        &mut self,
+        encoder: &mut wgpu::CommandEncoder,
+        queue: &wgpu::Queue,
+        device: &wgpu::Device,
+        render_pipeline: &wgpu::RenderPipeline,
+        vertex_buffer: &wgpu::Buffer,
+        asset_bind_group: &wgpu::BindGroup,
+        shader: Shader,
+        prefilter: Prefilter,
+        params: &Parameters,
+        mask: Option<(&Mask, f32)>,
+        source_space: ColorSpace,
+        working_space: ColorSpace,
+        timer: &mut GpuTimer,
+    ) {
+        let params = Parameters { shader_index: shader.get_index(), ..*params };
+        queue.write_buffer(&self.parameters_buffer, 0, bytemuck::cast_slice(&[params]));
+
+        self.write_color(queue, shader, mask.is_some(), source_space, working_space);
+        let [original, previous_stage, second_image] = self.input_spaces.map(|space| space.unwrap_or(working_space).get_index());
+        let input_spaces = InputSpaces {
+            original,
+            previous_stage,
+            second_image,
+            effect: shader.color_space().unwrap_or(working_space).get_index(),
+        };
+        queue.write_buffer(&self.input_spaces_buffer, 0, bytemuck::cast_slice(&[input_spaces]));
+        timer.mark(encoder, format!("{} input", self.name));
+        pass::run(encoder, "Stage Input Pass", &self.input_pipeline, &self.color_bind_group, &[&self.input_view]);
+
+        let smoothing = shader.smoothing(prefilter);
+        if smoothing != Prefilter::none {
+            timer.mark(encoder, format!("{} {}", self.name, smoothing));
+        }
+        match smoothing {
+            Prefilter::bilateral => {
+                self.smoothing.encode_bilateral(encoder, queue, params.sigma_space, params.sigma_range);
+            }
+            Prefilter::guided => {
+                self.smoothing.encode_guided(encoder, queue, params.guided_radius.max(0) as u32, params.guided_epsilon);
+            }
+            Prefilter::none => {}
+        }
+
+        let blurs_smoothed = smoothing != Prefilter::none && matches!(shader, Shader::difference_of_gaussians_DoG | Shader::flow_based_XDoG);
+        if blurs_smoothed != self.blurs_smoothed {
+            self.blurs_smoothed = blurs_smoothed;
+            let input = if blurs_smoothed { self.smoothing.output_view() } else { &self.input_view };
+            self.blur1.set_input(device, input);
+            self.blur2.set_input(device, input);
+        }
+
+        match shader {
+            Shader::gaussian_blur => {
+                timer.mark(encoder, format!("{} blur", self.name));
+                self.blur1.encode(encoder, queue, params.sigma1);
+            }
+            Shader::difference_of_gaussians_DoG | Shader::flow_based_XDoG => {
+                timer.mark(encoder, format!("{} blur", self.name));
+                self.blur1.encode(encoder, queue, params.sigma1);
+                self.blur2.encode(encoder, queue, params.sigma1 / 16.0);
+            }
+            Shader::canny_edge_detection => {
+                timer.mark(encoder, format!("{} blur", self.name));
+                self.blur1.encode(encoder, queue, params.sigma1);
+                timer.mark(encoder, format!("{} canny", self.name));
+                self.canny.encode(encoder, queue, params.low_threshold, params.high_threshold, params.num_gvf_iterations.max(0) as u32);
+            }
+            Shader::crt if params.crt_bloom > 0.0 => {
+                timer.mark(encoder, format!("{} bloom", self.name));
+                self.blur1.encode(encoder, queue, params.scanline_size * 2.0);
+            }
+            Shader::tone_mapping if params.auto_exposure != 0 && !self.tiled => {
+                timer.mark(encoder, format!("{} exposure", self.name));
+                self.exposure.encode(encoder, queue);
+            }
+            _ => {}
+        }
+
+        if let Some((mask, feather)) = mask {
+            timer.mark(encoder, format!("{} mask", self.name));
+            mask.encode(encoder, queue, feather);
+        }
+
+        timer.mark(encoder, format!("{} {}", self.name, shader));
+
+        if let Some(effect) = shader.compute() {
+            if !self.compute.as_ref().is_some_and(|compute| compute.is_for(effect)) {
+                let (width, height) = (self.output.width(), self.output.height());
+                let asset_bind_group_layout = render_pipeline.get_bind_group_layout(2);
+                self.compute = Some(ComputeEffectRunner::new(device, effect, &self.input_view, &self.effect_view, &self.parameters_buffer, &asset_bind_group_layout, width, height));
+            }
+            self.compute.as_ref().unwrap().encode(encoder, queue, &params, asset_bind_group);
+        } else {
+            let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: Some("Stage Pass"),
+                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                    view: &self.effect_view,
+                    resolve_target: None,
+                    ops: wgpu::Operations {
+                        load: wgpu::LoadOp::Clear(wgpu::Color::TRANSPARENT),
+                        store: wgpu::StoreOp::Store,
+                    },
+                })],
+                depth_stencil_attachment: None,
+                timestamp_writes: None,
+                occlusion_query_set: None,
+            });
+            render_pass.set_pipeline(render_pipeline);
+            render_pass.set_bind_group(0, &self.texture_bind_group, &[]);
+            render_pass.set_bind_group(1, &self.parameters_bind_group, &[]);
+            render_pass.set_bind_group(2, asset_bind_group, &[]);
+            render_pass.set_vertex_buffer(0, vertex_buffer.slice(..));
+            render_pass.draw(0..6, 0..1);
+        }
+
+        timer.mark(encoder, format!("{} output", self.name));
+        pass::run(encoder, "Stage Output Pass", &self.output_pipeline, &self.color_bind_group, &[&self.output_view]);
+    }
+}
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+struct ColorParameters {
+    source_space: u32,
+    effect_space: u32,
+    working_space: u32,
+    masked: u32,
+}
+
+// the spaces of the inputs an effect can declare, and the space it runs in
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+struct InputSpaces {
+    original: u32,
+    previous_stage: u32,
+    second_image: u32,
+    effect: u32,
+}
+
+// what a stage reads for one tile: its source and the inputs an effect can declare
+// with the spaces they hold, see Shader::inputs.
+// the base stage has no previous stage, it reads the original in its place
+pub struct Sources<'a> {
+    pub source: &'a wgpu::Texture,
+    pub original: (&'a wgpu::Texture, ColorSpace),
+    pub previous_stage: Option<&'a wgpu::Texture>,
+    pub second_image: (&'a wgpu::Texture, ColorSpace),
+}
+
+impl Sources<'_> {
+    fn previous_stage(&self) -> &wgpu::Texture {
+        self.previous_stage.unwrap_or(self.original.0)
+    }
+
+    fn spaces(&self) -> [Option<ColorSpace>; 3] {
+        let previous_stage = match self.previous_stage {
+            Some(_) => None,
+            None => Some(self.original.1),
+        };
+        [Some(self.original.1), previous_stage, Some(self.second_image.1)]
+    }
+}
+
+fn create_color_bind_group(
+    device: &wgpu::Device,
+    layout: &wgpu::BindGroupLayout,
+    source: &wgpu::Texture,
+    effect_view: &wgpu::TextureView,
+    mask: &Mask,
+    color_buffer: &wgpu::Buffer,
+) -> wgpu::BindGroup {
+    device.create_bind_group(&wgpu::BindGroupDescriptor {
+        label: Some("Stage Color Bind Group"),
+        layout,
+        entries: &[
+            wgpu::BindGroupEntry {
+                binding: 0,
+                resource: wgpu::BindingResource::TextureView(&source.create_view(&wgpu::TextureViewDescriptor::default())),
+            },
+            wgpu::BindGroupEntry {
+                binding: 1,
+                resource: wgpu::BindingResource::TextureView(effect_view),
+            },
+            wgpu::BindGroupEntry {
+                binding: 2,
+                resource: wgpu::BindingResource::TextureView(mask.view()),
+            },
+            wgpu::BindGroupEntry {
+                binding: 3,
+                resource: color_buffer.as_entire_binding(),
+            },
+        ],
+    })
+}
+
+#[allow(clippy::too_many_arguments)]
+fn create_texture_bind_group(
+    device: &wgpu::Device,
+    layout: &wgpu::BindGroupLayout,
+    image_view: &wgpu::TextureView,
+    blur1: &SeparableBlur,
+    blur2: &SeparableBlur,
+    canny: &Canny,
+    smoothing: &Smoothing,
+    exposure: &AutoExposure,
+    sources: &Sources,
+    mask: &Mask,
+    input_spaces_buffer: &wgpu::Buffer,
+) -> wgpu::BindGroup {
+    let view = |texture: &wgpu::Texture| texture.create_view(&wgpu::TextureViewDescriptor::default());
+    device.create_bind_group(&wgpu::BindGroupDescriptor {
+        label: Some("Texture Bind Group"),
+        layout,
+        entries: &[
+            wgpu::BindGroupEntry {
+                binding: 0,
+                resource: wgpu::BindingResource::TextureView(image_view),
+            },
+            wgpu::BindGroupEntry {
+                binding: 1,
+                resource: wgpu::BindingResource::Sampler(&device.create_sampler(&wgpu::SamplerDescriptor {
+                    address_mode_u: wgpu::AddressMode::ClampToEdge,
+                    address_mode_v: wgpu::AddressMode::ClampToEdge,
+                    mag_filter: wgpu::FilterMode::Linear,
+                    min_filter: wgpu::FilterMode::Nearest,
+                    mipmap_filter: wgpu::FilterMode::Nearest,
+                    ..Default::default()
+                })),
+            },
+            wgpu::BindGroupEntry {
+                binding: 2,
+                resource: wgpu::BindingResource::TextureView(blur1.output_view()),
+            },
+            wgpu::BindGroupEntry {
+                binding: 3,
+                resource: wgpu::BindingResource::TextureView(blur2.output_view()),
+            },
+            wgpu::BindGroupEntry {
+                binding: 4,
+                resource: wgpu::BindingResource::TextureView(canny.edge_view()),
+            },
+            wgpu::BindGroupEntry {
+                binding: 5,
+                resource: wgpu::BindingResource::TextureView(smoothing.output_view()),
+            },
+            wgpu::BindGroupEntry {
+                binding: 6,
+                resource: wgpu::BindingResource::TextureView(exposure.average_view()),
+            },
+            wgpu::BindGroupEntry {
+                binding: 7,
+                resource: wgpu::BindingResource::TextureView(&view(sources.original.0)),
+            },
+            wgpu::BindGroupEntry {
+                binding: 8,
+                resource: wgpu::BindingResource::TextureView(&view(sources.previous_stage())),
+            },
+            wgpu::BindGroupEntry {
+                binding: 9,
+                resource: wgpu::BindingResource::TextureView(&view(sources.second_image.0)),
+            },
+            wgpu::BindGroupEntry {
+                binding: 10,
+                resource: wgpu::BindingResource::TextureView(mask.painted_view()),
+            },
+            wgpu::BindGroupEntry {
+                binding: 11,
+                resource: input_spaces_buffer.as_entire_binding(),
+            },
+        ],
+    })
+}
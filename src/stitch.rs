@@ -0,0 +1,161 @@
+use iced_wgpu::wgpu;
+use crate::mipmap::Mipmaps;
+use crate::pass;
+use crate::tiles::{Tile, TileGrid};
+
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+struct StitchParameters {
+    destination: [f32; 4],
+    core: [f32; 4],
+}
+
+
+// what the viewer shows of a tiled image: both stage outputs, assembled tile by tile
+// into textures that fit the device's texture limit. the composite reads these in place
+// of the stage outputs, so the viewer shows the image downscaled to that limit
+pub struct Stitch {
+    pipeline: wgpu::RenderPipeline,
+    parameters_buffer: wgpu::Buffer,
+    bind_group: wgpu::BindGroup,
+    // level 0 the tiles are drawn into
+    base_target: wgpu::TextureView,
+    effect_target: wgpu::TextureView,
+    // every level, what the composite samples
+    base_view: wgpu::TextureView,
+    effect_view: wgpu::TextureView,
+    mipmaps: [Mipmaps; 2],
+    width: u32,
+    height: u32,
+}
+
+impl Stitch {
+    pub fn new(device: &wgpu::Device, base_output: &wgpu::TextureView, effect_output: &wgpu::TextureView, width: u32, height: u32) -> Stitch {
+        let module = device.create_shader_module(wgpu::include_wgsl!("../shaders/stitch.wgsl"));
+
+        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("Stitch Bind Group Layout"),
+            entries: &[
+                pass::texture_entry(0),
+                pass::texture_entry(1),
+                pass::sampler_entry(2),
+                pass::uniform_entry(3),
+            ],
+        });
+
+        let pipeline = pass::create_pipeline(device, "Stitch Pipeline", &module, "frag_main", &bind_group_layout, 2);
+
+        let parameters_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Stitch Parameters Buffer"),
+            size: std::mem::size_of::<StitchParameters>() as wgpu::BufferAddress,
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Stitch Bind Group"),
+            layout: &bind_group_layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::TextureView(base_output),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::TextureView(effect_output),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: wgpu::BindingResource::Sampler(&device.create_sampler(&wgpu::SamplerDescriptor {
+                        address_mode_u: wgpu::AddressMode::ClampToEdge,
+                        address_mode_v: wgpu::AddressMode::ClampToEdge,
+                        mag_filter: wgpu::FilterMode::Linear,
+                        min_filter: wgpu::FilterMode::Linear,
+                        mipmap_filter: wgpu::FilterMode::Nearest,
+                        ..Default::default()
+                    })),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 3,
+                    resource: parameters_buffer.as_entire_binding(),
+                },
+            ],
+        });
+
+        let base = pass::create_mipmapped_target(device, "Stitched Base Texture", width, height);
+        let effect = pass::create_mipmapped_target(device, "Stitched Effect Texture", width, height);
+        let mipmaps = [Mipmaps::new(device, &base), Mipmaps::new(device, &effect)];
+
+        Stitch {
+            pipeline,
+            parameters_buffer,
+            bind_group,
+            base_target: pass::level_view(&base, 0),
+            effect_target: pass::level_view(&effect, 0),
+            base_view: base.create_view(&wgpu::TextureViewDescriptor::default()),
+            effect_view: effect.create_view(&wgpu::TextureViewDescriptor::default()),
+            mipmaps,
+            width,
+            height,
+        }
+    }
+
+    pub fn base_view(&self) -> &wgpu::TextureView {
+        &self.base_view
+    }
+
+    pub fn effect_view(&self) -> &wgpu::TextureView {
+        &self.effect_view
+    }
+
+    // the smaller levels for the viewer, once every tile is drawn
+    pub fn encode_mipmaps(&self, encoder: &mut wgpu::CommandEncoder) {
+        for mipmaps in &self.mipmaps {
+            mipmaps.encode(encoder);
+        }
+    }
+
+    // draws the core of the tile the stages just rendered into its place.
+    // the parameters are written through the queue, so each tile needs its own submission
+    pub fn encode(&self, encoder: &mut wgpu::CommandEncoder, queue: &wgpu::Queue, grid: &TileGrid, tile: &Tile) {
+        let scale_x = self.width as f32 / grid.width as f32;
+        let scale_y = self.height as f32 / grid.height as f32;
+        // whole pixels, some backends round fractional viewports which leaves gaps.
+        // neighbouring tiles round their shared edge the same way
+        let x0 = (tile.core.x as f32 * scale_x).round();
+        let y0 = (tile.core.y as f32 * scale_y).round();
+        let x1 = ((tile.core.x + tile.core.width) as f32 * scale_x).round().min(self.width as f32);
+        let y1 = ((tile.core.y + tile.core.height) as f32 * scale_y).round().min(self.height as f32);
+        if x1 <= x0 || y1 <= y0 {
+            return;
+        }
+        let destination = [x0, y0, x1 - x0, y1 - y0];
+        let core = tile.core_in_region();
+        queue.write_buffer(&self.parameters_buffer, 0, bytemuck::cast_slice(&[StitchParameters {
+            destination,
+            core: [core.x as f32, core.y as f32, core.width as f32, core.height as f32],
+        }]));
+
+        let color_attachments = [&self.base_target, &self.effect_target].map(|view| Some(wgpu::RenderPassColorAttachment {
+            view,
+            resolve_target: None,
+            // the other tiles' parts stay
+            ops: wgpu::Operations {
+                load: wgpu::LoadOp::Load,
+                store: wgpu::StoreOp::Store,
+            },
+        }));
+        let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("Stitch Pass"),
+            color_attachments: &color_attachments,
+            depth_stencil_attachment: None,
+            timestamp_writes: None,
+            occlusion_query_set: None,
+        });
+        render_pass.set_viewport(destination[0], destination[1], destination[2], destination[3], 0.0, 1.0);
+        render_pass.set_pipeline(&self.pipeline);
+        render_pass.set_bind_group(0, &self.bind_group, &[]);
+        render_pass.draw(0..3, 0..1);
+    }
+}
@@ -0,0 +1,78 @@
+use crate::compute::{ComputeEffect, ComputePass, Domain};
+use crate::scene::Parameters;
+
+
+// the closest superpixels may be seeded, in pixels. what the storage buffer is sized for
+pub const MIN_STEP: u32 = 4;
+pub const MAX_REGIONS: u32 = 20000;
+pub const MAX_ITERATIONS: u32 = 50;
+
+// f32 words per region center: x and y in the tile, then its average color in cielab
+const CENTER_WORDS: u64 = 5;
+
+
+// slic superpixels, see superpixels.wgsl. centers are seeded on a grid anchored to the whole
+// image and moved off edges to the flattest pixel nearby. every iteration assigns each pixel
+// to the closest of the centers of the nine grid cells around it, by color and distance
+// weighed by compactness, then moves each center to the average of its pixels.
+// a pass per pixel fills every region with its average color and can outline it.
+// a tiled image is segmented tile by tile, regions only match across tiles where they
+// settle within the halo
+pub static SUPERPIXELS: ComputeEffect = ComputeEffect {
+    label: "Superpixels",
+    source: include_str!("../shaders/superpixels.wgsl"),
+    passes: &[
+        ComputePass {
+            entry_point: "seed_main",
+            workgroup_size: [8, 8],
+            domain: Domain::custom(grid),
+            iterations: |_, _, _| 1,
+            writes: false,
+        },
+        // assigning pixels on even iterations and updating centers on odd ones,
+        // the centers one per invocation counted row by row
+        ComputePass {
+            entry_point: "iterate_main",
+            workgroup_size: [8, 8],
+            domain: Domain::pixels,
+            iterations: |params, _, _| 2 * params.superpixel_iterations.clamp(1, MAX_ITERATIONS),
+            writes: false,
+        },
+        ComputePass {
+            entry_point: "draw_main",
+            workgroup_size: [8, 8],
+            domain: Domain::pixels,
+            iterations: |_, _, _| 1,
+            writes: true,
+        },
+    ],
+    // a label per pixel, then the centers at the closest seeds
+    buffer_size: |width, height| {
+        (width as u64 * height as u64 + (width / MIN_STEP + 2) as u64 * (height / MIN_STEP + 2) as u64 * CENTER_WORDS) * 4
+    },
+    rounds: |_, _, _, _| 1,
+};
+
+// the spacing of seeds for the requested number of regions over the whole image,
+// mirrors seed_step in superpixels.wgsl
+fn seed_step(params: &Parameters, width: u32, height: u32) -> u32 {
+    let (width, height) = match params.image_width {
+        0 => (width, height),
+        _ => (params.image_width, params.image_height),
+    };
+    let area = width as f32 * height as f32 / params.superpixel_count.clamp(1, MAX_REGIONS) as f32;
+    (area.sqrt().round() as u32).max(MIN_STEP)
+}
+
+// columns and rows of seeds over the tile of width x height at the tile's origin
+fn grid(params: &Parameters, width: u32, height: u32) -> (u32, u32) {
+    let step = seed_step(params, width, height);
+    let count = |origin: u32, length: u32| (origin + length.max(1) - 1) / step - origin / step + 1;
+    (count(params.tile_x, width), count(params.tile_y, height))
+}
+
+// the region of every pixel of an image of width x height, row by row,
+// from the storage buffer the effect left
+pub fn labels(buffer: &[u32], width: u32, height: u32) -> Option<&[u32]> {
+    buffer.get(..(width * height) as usize)
+}
@@ -0,0 +1,195 @@
+use crate::blur;
+use crate::smoothing;
+
+
+// images larger than the device's texture limit are split into tiles the stages can hold.
+// every tile is processed with a halo of neighbouring pixels around the part it contributes,
+// so kernels reaching across a seam see the pixels they would see untiled. HALO covers the
+// longest chain inside a stage, a smoothing prefilter, then the widest blur, then the edge
+// flow or canny. chains of both stages or long canny hysteresis can reach further and
+// differ slightly at the seams
+pub const HALO: u32 = smoothing::REACH + blur::REACH + FLOW_REACH;
+
+// the sobel gradients of the edge flow and canny, and canny's non maximum suppression
+// comparing neighbouring gradients
+const FLOW_REACH: u32 = 2;
+
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Region {
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Tile {
+    // the pixels the stages process, the same size for every tile
+    pub region: Region,
+    // the part of region this tile contributes to the stitched image, in image pixels
+    pub core: Region,
+}
+
+impl Tile {
+    // core relative to region, where it sits in the stage textures
+    pub fn core_in_region(&self) -> Region {
+        Region {
+            x: self.core.x - self.region.x,
+            y: self.core.y - self.region.y,
+            ..self.core
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TileGrid {
+    pub width: u32,
+    pub height: u32,
+    pub tiles: Vec<Tile>,
+}
+
+impl TileGrid {
+    // a single tile covering the image when it fits in max_dimension
+    pub fn new(width: u32, height: u32, max_dimension: u32) -> TileGrid {
+        let columns = split(width, max_dimension);
+        let rows = split(height, max_dimension);
+
+        let mut tiles = Vec::with_capacity(columns.len() * rows.len());
+        for &(y, core_y, core_height, region_height) in &rows {
+            for &(x, core_x, core_width, region_width) in &columns {
+                tiles.push(Tile {
+                    region: Region { x, y, width: region_width, height: region_height },
+                    core: Region { x: core_x, y: core_y, width: core_width, height: core_height },
+                });
+            }
+        }
+
+        TileGrid { width, height, tiles }
+    }
+
+    // for offscreen renders that are known to fit, like the lut lattice
+    pub fn single(width: u32, height: u32) -> TileGrid {
+        TileGrid::new(width, height, width.max(height))
+    }
+
+    pub fn is_tiled(&self) -> bool {
+        self.tiles.len() > 1
+    }
+
+    // size of every tile's region, which the stages are created at
+    pub fn tile_size(&self) -> (u32, u32) {
+        (self.tiles[0].region.width, self.tiles[0].region.height)
+    }
+
+    // largest size within max_dimension with the image's aspect ratio,
+    // what a tiled image is stitched into for the viewer
+    pub fn display_size(&self, max_dimension: u32) -> (u32, u32) {
+        let scale = (max_dimension as f32 / self.width.max(self.height) as f32).min(1.0);
+        (((self.width as f32 * scale).round() as u32).max(1), ((self.height as f32 * scale).round() as u32).max(1))
+    }
+}
+
+// splits one axis into (region start, core start, core length, region length).
+// regions all have the same length, at the image borders they are shifted inwards
+// instead of shrunk, so the border pixels are handled like they are untiled
+fn split(length: u32, max_dimension: u32) -> Vec<(u32, u32, u32, u32)> {
+    if length <= max_dimension {
+        return vec![(0, 0, length, length)];
+    }
+    let halo = HALO.min(max_dimension / 4);
+    let core = max_dimension - 2 * halo;
+
+    (0..length.div_ceil(core)).map(|i| {
+        let core_start = i * core;
+        let core_length = core.min(length - core_start);
+        let region_start = core_start.saturating_sub(halo).min(length - max_dimension);
+        (region_start, core_start, core_length, max_dimension)
+    }).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // how many cores cover every pixel, row by row
+    fn coverage(grid: &TileGrid) -> Vec<u32> {
+        let mut covered = vec![0; (grid.width * grid.height) as usize];
+        for tile in &grid.tiles {
+            for y in tile.core.y..tile.core.y + tile.core.height {
+                for x in tile.core.x..tile.core.x + tile.core.width {
+                    covered[(y * grid.width + x) as usize] += 1;
+                }
+            }
+        }
+        covered
+    }
+
+    #[test]
+    fn cores_cover_the_image_once() {
+        for (width, height, max_dimension) in [(1000, 700, 300), (1201, 601, 600), (257, 2000, 256), (900, 300, 300)] {
+            let grid = TileGrid::new(width, height, max_dimension);
+            assert!(grid.is_tiled());
+            assert!(coverage(&grid).iter().all(|&count| count == 1), "{width}x{height} in {max_dimension}");
+        }
+    }
+
+    #[test]
+    fn regions_keep_their_size_inside_the_image() {
+        let (width, height, max_dimension) = (1000, 700, 300);
+        let grid = TileGrid::new(width, height, max_dimension);
+        for tile in &grid.tiles {
+            let (region, core) = (tile.region, tile.core);
+            assert_eq!((region.width, region.height), (max_dimension, max_dimension));
+            assert!(region.x + region.width <= width && region.y + region.height <= height);
+            assert!(region.x <= core.x && core.x + core.width <= region.x + region.width);
+            assert!(region.y <= core.y && core.y + core.height <= region.y + region.height);
+        }
+    }
+
+    #[test]
+    fn border_regions_shift_inwards() {
+        let columns = split(1000, 300);
+        let halo = HALO.min(300 / 4);
+        let (first, last) = (columns[0], columns[columns.len() - 1]);
+        // the first region starts at the border rather than a halo before it
+        assert_eq!((first.0, first.1), (0, 0));
+        // the last one ends at the border, reaching back further than its halo
+        assert_eq!(last.0 + last.3, 1000);
+        assert!(last.1 - last.0 > halo);
+        // the ones between have their halo on both sides
+        for &(region_start, core_start, core_length, region_length) in &columns[1..columns.len() - 1] {
+            assert_eq!(core_start - region_start, halo);
+            assert!(region_start + region_length >= core_start + core_length + halo);
+        }
+    }
+
+    #[test]
+    fn halo_holds_the_widest_kernels() {
+        // the last folded tap samples the texels on either side of its offset
+        let reach = |taps: Vec<[f32; 2]>| taps.last().unwrap()[0].ceil() as u32;
+        assert_eq!(reach(blur::gaussian_taps(1000.0)), blur::REACH);
+        assert_eq!(reach(blur::box_taps(1000)), blur::REACH);
+        assert_eq!(reach(blur::box_taps(smoothing::MAX_GUIDED_RADIUS)) * 2, smoothing::REACH);
+    }
+
+    #[test]
+    fn fitting_images_are_one_tile() {
+        let grid = TileGrid::new(300, 200, 300);
+        assert!(!grid.is_tiled());
+        assert_eq!(grid.tile_size(), (300, 200));
+        let tile = grid.tiles[0];
+        assert_eq!(tile.region, tile.core);
+        assert_eq!(tile.core_in_region(), Region { x: 0, y: 0, width: 300, height: 200 });
+    }
+
+    #[test]
+    fn core_in_region_is_relative() {
+        let grid = TileGrid::new(1000, 700, 300);
+        for tile in &grid.tiles {
+            let inner = tile.core_in_region();
+            assert_eq!((inner.x + tile.region.x, inner.y + tile.region.y), (tile.core.x, tile.core.y));
+            assert_eq!((inner.width, inner.height), (tile.core.width, tile.core.height));
+        }
+    }
+}
@@ -0,0 +1,187 @@
+use std::sync::{Arc, Mutex};
+use iced_wgpu::wgpu;
+
+
+// timestamps per frame, a tiled image marks every pass once per tile
+const MAX_QUERIES: u32 = 1024;
+
+// requested from adapters that have it, without it only frame times are measured
+pub const FEATURES: wgpu::Features = wgpu::Features::TIMESTAMP_QUERY;
+
+// what the overlay and the benchmark report for one frame
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Timings {
+    // gpu milliseconds per pass in the order they first ran, empty without timestamp queries
+    pub passes: Vec<(String, f32)>,
+    // milliseconds between frames, or for the whole frame when nothing else is measured
+    pub frame: f32,
+    // milliseconds the cpu spent recording and submitting the frame
+    pub cpu: f32,
+    pub width: u32,
+    pub height: u32,
+    // stage texture size, smaller than the image when it is tiled
+    pub stage_width: u32,
+    pub stage_height: u32,
+    pub tiles: usize,
+}
+
+impl Timings {
+    pub fn gpu_total(&self) -> f32 {
+        self.passes.iter().map(|(_, ms)| ms).sum()
+    }
+}
+
+struct Queries {
+    set: wgpu::QuerySet,
+    resolve_buffer: wgpu::Buffer,
+    readback_buffer: wgpu::Buffer,
+    // nanoseconds per timestamp tick
+    period: f32,
+    // labels of the frame whose timestamps are being read back
+    pending: Option<Vec<String>>,
+    mapping: bool,
+    // set by the map callback, whether the readback buffer could be mapped
+    mapped: Arc<Mutex<Option<bool>>>,
+}
+
+// gpu time between marks in the command stream. every mark writes a timestamp from an
+// empty compute pass, which only needs TIMESTAMP_QUERY, and starts a span that runs until
+// the next mark. an empty label ends the current span without starting one, for the gaps
+// between submissions. results are read back a frame or more later without stalling
+pub struct GpuTimer {
+    queries: Option<Queries>,
+    labels: Vec<String>,
+    recording: bool,
+}
+
+impl GpuTimer {
+    pub fn new(device: &wgpu::Device, queue: &wgpu::Queue) -> GpuTimer {
+        let queries = device.features().contains(wgpu::Features::TIMESTAMP_QUERY).then(|| {
+            let size = MAX_QUERIES as u64 * std::mem::size_of::<u64>() as u64;
+            Queries {
+                set: device.create_query_set(&wgpu::QuerySetDescriptor {
+                    label: Some("Timing Query Set"),
+                    ty: wgpu::QueryType::Timestamp,
+                    count: MAX_QUERIES,
+                }),
+                resolve_buffer: device.create_buffer(&wgpu::BufferDescriptor {
+                    label: Some("Timing Resolve Buffer"),
+                    size,
+                    usage: wgpu::BufferUsages::QUERY_RESOLVE | wgpu::BufferUsages::COPY_SRC,
+                    mapped_at_creation: false,
+                }),
+                readback_buffer: device.create_buffer(&wgpu::BufferDescriptor {
+                    label: Some("Timing Readback Buffer"),
+                    size,
+                    usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::MAP_READ,
+                    mapped_at_creation: false,
+                }),
+                period: queue.get_timestamp_period(),
+                pending: None,
+                mapping: false,
+                mapped: Arc::new(Mutex::new(None)),
+            }
+        });
+
+        GpuTimer {
+            queries,
+            labels: Vec::new(),
+            recording: false,
+        }
+    }
+
+    // whether per pass times are measured, otherwise only frame times are
+    pub fn is_supported(&self) -> bool {
+        self.queries.is_some()
+    }
+
+    // starts recording marks for a frame, skipped while the last one is still being read back
+    pub fn begin(&mut self) {
+        self.labels.clear();
+        self.recording = self.queries.as_ref().is_some_and(|queries| queries.pending.is_none());
+    }
+
+    pub fn mark(&mut self, encoder: &mut wgpu::CommandEncoder, label: impl Into<String>) {
+        let Some(queries) = self.queries.as_ref().filter(|_| self.recording) else {
+            return;
+        };
+        // spans past the limit are dropped, the last one recorded never ends
+        if self.labels.len() as u32 >= MAX_QUERIES {
+            return;
+        }
+        encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
+            label: Some("Timing Pass"),
+            timestamp_writes: Some(wgpu::ComputePassTimestampWrites {
+                query_set: &queries.set,
+                beginning_of_pass_write_index: Some(self.labels.len() as u32),
+                end_of_pass_write_index: None,
+            }),
+        });
+        self.labels.push(label.into());
+    }
+
+    // ends the current span
+    pub fn end(&mut self, encoder: &mut wgpu::CommandEncoder) {
+        self.mark(encoder, "");
+    }
+
+    // ends the frame's last span and copies its timestamps out, encoder has to be
+    // the last one submitted for the frame. call after_submit once it is
+    pub fn finish(&mut self, encoder: &mut wgpu::CommandEncoder) {
+        self.end(encoder);
+        let Some(queries) = self.queries.as_mut().filter(|_| self.recording) else {
+            return;
+        };
+        self.recording = false;
+        let count = self.labels.len() as u32;
+        encoder.resolve_query_set(&queries.set, 0..count, &queries.resolve_buffer, 0);
+        encoder.copy_buffer_to_buffer(&queries.resolve_buffer, 0, &queries.readback_buffer, 0, count as u64 * std::mem::size_of::<u64>() as u64);
+        queries.pending = Some(std::mem::take(&mut self.labels));
+    }
+
+    pub fn after_submit(&mut self) {
+        let Some(queries) = self.queries.as_mut() else {
+            return;
+        };
+        if queries.pending.is_some() && !queries.mapping {
+            queries.mapping = true;
+            let mapped = queries.mapped.clone();
+            queries.readback_buffer.slice(..).map_async(wgpu::MapMode::Read, move |result| {
+                *mapped.lock().unwrap() = Some(result.is_ok());
+            });
+        }
+    }
+
+    // milliseconds per label for the last finished frame once its timestamps arrived.
+    // wait blocks until they have, for the benchmark
+    pub fn poll(&mut self, device: &wgpu::Device, wait: bool) -> Option<Vec<(String, f32)>> {
+        let queries = self.queries.as_mut().filter(|queries| queries.mapping)?;
+        device.poll(if wait { wgpu::Maintain::Wait } else { wgpu::Maintain::Poll });
+        let mapped = queries.mapped.lock().unwrap().take()?;
+        let labels = queries.pending.take().unwrap_or_default();
+        queries.mapping = false;
+        if !mapped {
+            return None;
+        }
+
+        let ticks: Vec<u64> = {
+            let range = queries.readback_buffer.slice(..).get_mapped_range();
+            bytemuck::cast_slice(&range)[..labels.len()].to_vec()
+        };
+        queries.readback_buffer.unmap();
+
+        // tiles repeat the same labels, their spans add up
+        let mut passes: Vec<(String, f32)> = Vec::new();
+        for (i, label) in labels.iter().enumerate().take(labels.len().saturating_sub(1)) {
+            if label.is_empty() {
+                continue;
+            }
+            let ms = ticks[i + 1].saturating_sub(ticks[i]) as f32 * queries.period / 1_000_000.0;
+            match passes.iter_mut().find(|(name, _)| name == label) {
+                Some((_, total)) => *total += ms,
+                None => passes.push((label.clone(), ms)),
+            }
+        }
+        Some(passes)
+    }
+}
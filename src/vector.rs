@@ -0,0 +1,362 @@
+use std::collections::HashMap;
+use image::Rgba32FImage;
+use crate::controls::VectorPaths;
+
+
+// the palettes of nearest_color in fragment.wgsl, display encoded. 0 the colors of quantization,
+// then the palettes of ConsolePalette
+const BASIC_PALETTE: [[f32; 3]; 8] = [
+    [0.0, 0.0, 0.0], [1.0, 1.0, 1.0], [1.0, 0.0, 0.0], [0.0, 1.0, 0.0],
+    [0.0, 0.0, 1.0], [1.0, 1.0, 0.0], [1.0, 0.0, 1.0], [0.0, 1.0, 1.0],
+];
+
+const GAME_BOY_PALETTE: [[f32; 3]; 4] = [
+    [0.059, 0.220, 0.059], [0.188, 0.384, 0.188], [0.545, 0.675, 0.059], [0.608, 0.737, 0.059],
+];
+
+const CGA_PALETTE: [[f32; 3]; 4] = [
+    [0.000, 0.000, 0.000], [0.333, 1.000, 1.000], [1.000, 0.333, 1.000], [1.000, 1.000, 1.000],
+];
+
+const NES_PALETTE: [[f32; 3]; 55] = [
+    [0.486, 0.486, 0.486], [0.000, 0.000, 0.988], [0.000, 0.000, 0.737], [0.267, 0.157, 0.737],
+    [0.580, 0.000, 0.518], [0.659, 0.000, 0.125], [0.659, 0.063, 0.000], [0.533, 0.078, 0.000],
+    [0.314, 0.188, 0.000], [0.000, 0.471, 0.000], [0.000, 0.408, 0.000], [0.000, 0.345, 0.000],
+    [0.000, 0.251, 0.345],
+    [0.737, 0.737, 0.737], [0.000, 0.471, 0.973], [0.000, 0.345, 0.973], [0.408, 0.267, 0.988],
+    [0.847, 0.000, 0.800], [0.894, 0.000, 0.345], [0.973, 0.220, 0.000], [0.894, 0.361, 0.063],
+    [0.675, 0.486, 0.000], [0.000, 0.722, 0.000], [0.000, 0.659, 0.000], [0.000, 0.659, 0.267],
+    [0.000, 0.533, 0.533],
+    [0.973, 0.973, 0.973], [0.235, 0.737, 0.988], [0.408, 0.533, 0.988], [0.596, 0.471, 0.973],
+    [0.973, 0.471, 0.973], [0.973, 0.345, 0.596], [0.973, 0.471, 0.345], [0.988, 0.627, 0.267],
+    [0.973, 0.722, 0.000], [0.722, 0.973, 0.094], [0.345, 0.847, 0.329], [0.345, 0.973, 0.596],
+    [0.000, 0.910, 0.847], [0.471, 0.471, 0.471],
+    [0.988, 0.988, 0.988], [0.643, 0.894, 0.988], [0.722, 0.722, 0.973], [0.847, 0.722, 0.973],
+    [0.973, 0.722, 0.973], [0.973, 0.643, 0.753], [0.941, 0.816, 0.690], [0.988, 0.878, 0.659],
+    [0.973, 0.847, 0.471], [0.847, 0.973, 0.471], [0.722, 0.973, 0.722], [0.722, 0.973, 0.847],
+    [0.000, 0.988, 0.988], [0.973, 0.847, 0.973], [0.000, 0.000, 0.000],
+];
+
+// the label of transparent pixels and of everything around the image, never traced
+const NONE: u16 = u16::MAX;
+
+
+fn palette(index: u32) -> &'static [[f32; 3]] {
+    match index {
+        1 => &GAME_BOY_PALETTE,
+        2 => &CGA_PALETTE,
+        3 => &NES_PALETTE,
+        _ => &BASIC_PALETTE,
+    }
+}
+
+// the position of the closest color in the palette, like nearest_color picks it
+fn nearest(color: [f32; 3], index: u32) -> u16 {
+    let colors = palette(index);
+    // the game boy shows shades rather than colors, picked by luminance
+    if index == 1 {
+        let luminance = 0.2126 * color[0] + 0.7152 * color[1] + 0.0722 * color[2];
+        return ((luminance * 4.0).max(0.0) as u16).min(3);
+    }
+    let distance = |other: &[f32; 3]| (0..3).map(|c| (color[c] - other[c]).powi(2)).sum::<f32>();
+    let mut closest = 0;
+    for (i, other) in colors.iter().enumerate().skip(1) {
+        if distance(other) < distance(&colors[closest]) {
+            closest = i;
+        }
+    }
+    closest as u16
+}
+
+// traces an exported composite into svg paths, one per color of the palette it is snapped to.
+// the outlines are marching squares contours through the pixel centers, simplified to within
+// tolerance pixels and smoothed into curves where they bend gently, the way potrace does.
+// outlines enclosing less than speckle pixels are dropped, smoothing is potrace's alphamax,
+// 0 keeps every vertex a corner. filled regions paint the most covered color as the background
+// and the others over it, stroked outlines draw every color's outlines but the background's
+pub fn svg(image: &Rgba32FImage, palette_index: u32, paths: VectorPaths, tolerance: f32, speckle: f32, smoothing: f32) -> String {
+    let (width, height) = image.dimensions();
+    let colors = palette(palette_index);
+
+    // pixels mostly transparent are left out, the background has to leave them out too
+    let labels: Vec<u16> = image.pixels().map(|pixel| match pixel[3] < 0.5 {
+        true => NONE,
+        false => nearest([pixel[0], pixel[1], pixel[2]], palette_index),
+    }).collect();
+    let mut coverage = vec![0usize; colors.len()];
+    for &label in labels.iter().filter(|&&label| label != NONE) {
+        coverage[label as usize] += 1;
+    }
+    let transparent = coverage.iter().sum::<usize>() < labels.len();
+    let background = (0..colors.len()).max_by_key(|&label| coverage[label]).unwrap_or(0);
+
+    let mut out = String::new();
+    out.push_str("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+    out.push_str(&format!("<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{width}\" height=\"{height}\" viewBox=\"0 0 {width} {height}\">\n"));
+    out.push_str("<!-- exported by rts -->\n");
+    if paths == VectorPaths::fill && !transparent && coverage[background] > 0 {
+        out.push_str(&format!("<rect width=\"{width}\" height=\"{height}\" fill=\"{}\"/>\n", hex(colors[background])));
+    }
+
+    // the larger regions first, the smaller ones over them where saddles overlap
+    let mut order: Vec<usize> = (0..colors.len()).filter(|&label| coverage[label] > 0).collect();
+    order.sort_by_key(|&label| std::cmp::Reverse(coverage[label]));
+    let mut contours = contours(&labels, width, height, colors.len());
+    for label in order {
+        let traced = match paths {
+            VectorPaths::fill => label != background || transparent,
+            VectorPaths::stroke => label != background,
+        };
+        if !traced {
+            continue;
+        }
+
+        let mut data = String::new();
+        for contour in std::mem::take(&mut contours[label]) {
+            if area(&contour).abs() < speckle {
+                continue;
+            }
+            let polygon = simplify(&contour, tolerance);
+            if polygon.len() >= 3 {
+                data.push_str(&path_data(&polygon, smoothing));
+            }
+        }
+        if data.is_empty() {
+            continue;
+        }
+
+        let color = hex(colors[label]);
+        match paths {
+            VectorPaths::fill => out.push_str(&format!("<path fill=\"{color}\" fill-rule=\"evenodd\" d=\"{}\"/>\n", data.trim_end())),
+            VectorPaths::stroke => out.push_str(&format!("<path fill=\"none\" stroke=\"{color}\" stroke-width=\"1\" stroke-linejoin=\"round\" d=\"{}\"/>\n", data.trim_end())),
+        }
+    }
+
+    out.push_str("</svg>\n");
+    out
+}
+
+// the closed outlines of every label, in pixels. each 2x2 block of pixel centers is a marching
+// squares cell, its corners numbered clockwise from the top left and its edges after the corner
+// they start at. a segment runs from an edge leaving a label clockwise to the next edge entering
+// it, so diagonal pixels of a label connect and outlines keep the label on the same side
+fn contours(labels: &[u16], width: u32, height: u32, count: usize) -> Vec<Vec<Vec<[f32; 2]>>> {
+    let (w, h) = (width as i32, height as i32);
+    let at = |x: i32, y: i32| match x >= 0 && y >= 0 && x < w && y < h {
+        true => labels[(y * w + x) as usize],
+        false => NONE,
+    };
+    // edge midpoints in half pixels, packed into one key
+    let key = |x: i32, y: i32| ((x as u32 as u64) << 32) | y as u32 as u64;
+
+    let mut next: Vec<HashMap<u64, u64>> = vec![HashMap::new(); count];
+    for y in -1..h {
+        for x in -1..w {
+            let corners = [at(x, y), at(x + 1, y), at(x + 1, y + 1), at(x, y + 1)];
+            let edges = [
+                key(2 * x + 2, 2 * y + 1),
+                key(2 * x + 3, 2 * y + 2),
+                key(2 * x + 2, 2 * y + 3),
+                key(2 * x + 1, 2 * y + 2),
+            ];
+            for (i, &label) in corners.iter().enumerate() {
+                // every label once per cell, at its first corner
+                if label == NONE || corners[..i].contains(&label) {
+                    continue;
+                }
+                let inside = corners.map(|corner| corner == label);
+                for exit in 0..4 {
+                    if !inside[exit] || inside[(exit + 1) % 4] {
+                        continue;
+                    }
+                    let enter = (1..4).map(|step| (exit + step) % 4).find(|&edge| !inside[edge] && inside[(edge + 1) % 4]).unwrap();
+                    next[label as usize].insert(edges[exit], edges[enter]);
+                }
+            }
+        }
+    }
+
+    next.into_iter().map(|mut segments| {
+        // followed from sorted starts so the same image traces to the same file
+        let mut starts: Vec<u64> = segments.keys().copied().collect();
+        starts.sort_unstable();
+        let mut loops = Vec::new();
+        for start in starts {
+            let mut points = Vec::new();
+            let mut point = start;
+            while let Some(following) = segments.remove(&point) {
+                points.push([(point >> 32) as u32 as i32 as f32 * 0.5, point as u32 as i32 as f32 * 0.5]);
+                point = following;
+            }
+            if !points.is_empty() {
+                loops.push(points);
+            }
+        }
+        loops
+    }).collect()
+}
+
+// signed area of a closed outline, by the shoelace formula
+fn area(points: &[[f32; 2]]) -> f32 {
+    let mut sum = 0.0;
+    for (i, a) in points.iter().enumerate() {
+        let b = points[(i + 1) % points.len()];
+        sum += a[0] * b[1] - b[0] * a[1];
+    }
+    sum * 0.5
+}
+
+fn segment_distance(point: [f32; 2], a: [f32; 2], b: [f32; 2]) -> f32 {
+    let (dx, dy) = (b[0] - a[0], b[1] - a[1]);
+    let length = dx * dx + dy * dy;
+    let t = match length > 0.0 {
+        true => (((point[0] - a[0]) * dx + (point[1] - a[1]) * dy) / length).clamp(0.0, 1.0),
+        false => 0.0,
+    };
+    ((a[0] + t * dx - point[0]).powi(2) + (a[1] + t * dy - point[1]).powi(2)).sqrt()
+}
+
+// douglas-peucker on a closed outline, split at its first point and the point farthest from it
+fn simplify(points: &[[f32; 2]], tolerance: f32) -> Vec<[f32; 2]> {
+    if points.len() < 4 {
+        return points.to_vec();
+    }
+    let first = points[0];
+    let farthest = (1..points.len())
+        .max_by(|&a, &b| segment_distance(points[a], first, first).total_cmp(&segment_distance(points[b], first, first)))
+        .unwrap();
+
+    let mut keep = vec![false; points.len() + 1];
+    keep[0] = true;
+    keep[farthest] = true;
+    // the outline closes back on its first point at the end
+    let point = |i: usize| points[i % points.len()];
+    let mut spans = vec![(0, farthest), (farthest, points.len())];
+    while let Some((start, end)) = spans.pop() {
+        let (a, b) = (point(start), point(end));
+        let split = (start + 1..end).map(|i| (i, segment_distance(point(i), a, b))).max_by(|a, b| a.1.total_cmp(&b.1));
+        if let Some((i, distance)) = split {
+            if distance > tolerance {
+                keep[i] = true;
+                spans.push((start, i));
+                spans.push((i, end));
+            }
+        }
+    }
+    (0..points.len()).filter(|&i| keep[i]).map(|i| points[i]).collect()
+}
+
+// the outline as a closed path through the midpoints of its edges. every vertex between them is
+// a corner or a bezier curve, potrace's alpha deciding which and how far the curve reaches into it
+fn path_data(polygon: &[[f32; 2]], smoothing: f32) -> String {
+    let n = polygon.len();
+    let mid = |a: [f32; 2], b: [f32; 2]| [(a[0] + b[0]) * 0.5, (a[1] + b[1]) * 0.5];
+    let lerp = |t: f32, a: [f32; 2], b: [f32; 2]| [a[0] + t * (b[0] - a[0]), a[1] + t * (b[1] - a[1])];
+
+    let start = mid(polygon[n - 1], polygon[0]);
+    let mut data = format!("M{} {}", number(start[0]), number(start[1]));
+    for i in 0..n {
+        let (previous, vertex, following) = (polygon[(i + n - 1) % n], polygon[i], polygon[(i + 1) % n]);
+        let end = mid(vertex, following);
+
+        // how far the vertex stands off the line between its neighbours, over their l1 distance
+        let denominator = (following[0] - previous[0]).abs() + (following[1] - previous[1]).abs();
+        let alpha = match denominator > 0.0 {
+            true => {
+                let cross = (vertex[0] - previous[0]) * (following[1] - previous[1]) - (vertex[1] - previous[1]) * (following[0] - previous[0]);
+                let distance = cross.abs() / denominator;
+                let alpha = if distance > 1.0 { 1.0 - 1.0 / distance } else { 0.0 };
+                alpha / 0.75
+            }
+            false => 4.0 / 3.0,
+        };
+
+        if alpha >= smoothing {
+            data.push_str(&format!(" L{} {} L{} {}", number(vertex[0]), number(vertex[1]), number(end[0]), number(end[1])));
+        } else {
+            let alpha = alpha.clamp(0.55, 1.0);
+            let a = lerp(0.5 + 0.5 * alpha, previous, vertex);
+            let b = lerp(0.5 + 0.5 * alpha, following, vertex);
+            data.push_str(&format!(" C{} {} {} {} {} {}", number(a[0]), number(a[1]), number(b[0]), number(b[1]), number(end[0]), number(end[1])));
+        }
+    }
+    data.push_str("Z ");
+    data
+}
+
+// coordinates to two decimals without trailing zeros
+fn number(value: f32) -> String {
+    let text = format!("{value:.2}");
+    let text = text.trim_end_matches('0').trim_end_matches('.');
+    match text {
+        "-0" => String::from("0"),
+        _ => text.to_string(),
+    }
+}
+
+fn hex(color: [f32; 3]) -> String {
+    let [r, g, b] = color.map(|c| (c.clamp(0.0, 1.0) * 255.0).round() as u8);
+    format!("#{r:02x}{g:02x}{b:02x}")
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn traces_a_pixel_as_a_diamond() {
+        assert_eq!(contours(&[0], 1, 1, 1), [[[[0.0, 0.5], [0.5, 0.0], [1.0, 0.5], [0.5, 1.0]]]]);
+    }
+
+    #[test]
+    fn connects_diagonal_pixels() {
+        // a saddle, both labels touch diagonally and each outlines one region through the middle
+        let traced = contours(&[0, 1, 1, 0], 2, 2, 2);
+        assert_eq!(traced[0], [[[0.0, 0.5], [0.5, 0.0], [1.0, 0.5], [1.5, 1.0], [2.0, 1.5], [1.5, 2.0], [1.0, 1.5], [0.5, 1.0]]]);
+        assert_eq!(traced[1], [[[0.0, 1.5], [0.5, 1.0], [1.0, 0.5], [1.5, 0.0], [2.0, 0.5], [1.5, 1.0], [1.0, 1.5], [0.5, 2.0]]]);
+    }
+
+    #[test]
+    fn traces_holes_the_other_way_around() {
+        let traced = contours(&[0, 0, 0, 0, 1, 0, 0, 0, 0], 3, 3, 2);
+        assert_eq!(traced[0].len(), 2);
+        assert_eq!(traced[1].len(), 1);
+        let areas: Vec<f32> = traced[0].iter().map(|contour| area(contour)).collect();
+        // the square with its corners cut off, less the diamond of the middle pixel
+        assert_eq!(areas[0] + areas[1], 9.0 - 4.0 * 0.125 - 0.5);
+        assert!(areas[0] * areas[1] < 0.0);
+        assert_eq!(area(&traced[1][0]), -areas[1]);
+    }
+
+    #[test]
+    fn simplifies_within_tolerance() {
+        // a wobbly circle
+        let points: Vec<[f32; 2]> = (0..200).map(|i| {
+            let angle = i as f32 / 200.0 * std::f32::consts::TAU;
+            let radius = 40.0 + 1.5 * (angle * 17.0).sin();
+            [50.0 + radius * angle.cos(), 50.0 + radius * angle.sin()]
+        }).collect();
+        for tolerance in [0.5, 2.0, 5.0] {
+            let simplified = simplify(&points, tolerance);
+            assert!(simplified.len() < points.len());
+            assert_eq!(simplified[0], points[0]);
+            for &point in &points {
+                let distance = (0..simplified.len())
+                    .map(|i| segment_distance(point, simplified[i], simplified[(i + 1) % simplified.len()]))
+                    .fold(f32::INFINITY, f32::min);
+                assert!(distance <= tolerance, "{point:?} is {distance} off at tolerance {tolerance}");
+            }
+        }
+    }
+
+    #[test]
+    fn formats_numbers_short() {
+        assert_eq!(number(1.50), "1.5");
+        assert_eq!(number(-0.001), "0");
+        assert_eq!(number(-0.0), "0");
+        assert_eq!(number(10.0), "10");
+        assert_eq!(number(-2.25), "-2.25");
+        assert_eq!(number(0.004), "0");
+    }
+}